@@ -0,0 +1,34 @@
+//! Finds the shortest route between two solar systems using the library, as a starting point for
+//! programs of your own.
+//!
+//!     cargo run --example route -- [SDE_ZIP] [FROM] [TO]
+//!
+//! Without arguments it routes from Amarr to Jita on the small fixture SDE in examples/fixtures,
+//! which has just a handful of systems, so it runs without downloading the full SDE.
+use eyre::{eyre, WrapErr};
+use rust_eve_astar::evemap::Map;
+use rust_eve_astar::route::RouteSummary;
+use rust_eve_astar::sde::SdeZipReader;
+
+fn main() -> eyre::Result<()> {
+    let mut args = std::env::args().skip(1);
+    let sde_path = args
+        .next()
+        .unwrap_or_else(|| concat!(env!("CARGO_MANIFEST_DIR"), "/examples/fixtures/sde.zip").to_string());
+    let from = args.next().unwrap_or_else(|| "Amarr".to_string());
+    let to = args.next().unwrap_or_else(|| "Jita".to_string());
+
+    let file = std::fs::File::open(&sde_path).wrap_err_with(|| format!("unable to open {sde_path}"))?;
+    let map = Map::new(&mut SdeZipReader::new(std::io::BufReader::new(file)))?;
+
+    let from = map.find_solarsystem(&from)?;
+    let to = map.find_solarsystem(&to)?;
+    let route = map.shortest_route(from, to).map_err(|e| eyre!("{e}"))?;
+
+    for i in &route {
+        let info = map.get_extended_solarsystem_info(i);
+        println!("{} ({:.1})", info.name, info.security_rounded());
+    }
+    println!("{}", RouteSummary::new(&map, &route));
+    Ok(())
+}
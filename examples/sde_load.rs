@@ -0,0 +1,40 @@
+//! Compares how long building a Map takes when the SDE zip is read through a BufReader and when
+//! it's read through a memory map (the mmap feature).
+//!
+//!     cargo run --release --features mmap --example sde_load -- [SDE_ZIP] [RUNS]
+//!
+//! Each way is timed RUNS times (5 by default), alternating so that neither gets a warmer page
+//! cache than the other, and the best time of each is printed.
+use eyre::WrapErr;
+use rust_eve_astar::evemap::Map;
+use rust_eve_astar::sde::{MmapSdeSource, SdeZipReader};
+use std::time::{Duration, Instant};
+
+fn main() -> eyre::Result<()> {
+    let mut args = std::env::args().skip(1);
+    let sde_path = args
+        .next()
+        .unwrap_or_else(|| concat!(env!("CARGO_MANIFEST_DIR"), "/examples/fixtures/sde.zip").to_string());
+    let runs: usize = args.next().map(|r| r.parse()).transpose()?.unwrap_or(5);
+
+    let (mut buffered, mut mapped) = (Duration::MAX, Duration::MAX);
+    let mut systems = 0;
+    for _ in 0..runs {
+        let now = Instant::now();
+        let file = std::fs::File::open(&sde_path).wrap_err_with(|| format!("unable to open {sde_path}"))?;
+        let map = Map::new(&mut SdeZipReader::new(std::io::BufReader::new(file)))?;
+        buffered = buffered.min(now.elapsed());
+        systems = map.system_count();
+
+        let now = Instant::now();
+        let source = MmapSdeSource::open(&sde_path).wrap_err_with(|| format!("unable to map {sde_path}"))?;
+        let map = Map::new(&mut source.reader())?;
+        mapped = mapped.min(now.elapsed());
+        assert_eq!(map.system_count(), systems);
+    }
+
+    println!("{sde_path}: {systems} systems, best of {runs}");
+    println!("BufReader: {:.3} seconds", buffered.as_secs_f64());
+    println!("mmap:      {:.3} seconds", mapped.as_secs_f64());
+    Ok(())
+}
@@ -4,15 +4,38 @@ use std::cmp::Ordering;
 use std::fmt::Debug;
 use crate::astar::AStarError::*;
 use crate::astar::ClosedListState::*;
+// validate_path checks any path, but lives with the other route helpers
+pub use crate::route::{validate_path, ValidationError};
 
 /// OpenList is a general trait to allow templating of a priority queue implementation for the
 /// AStar algorithm.
 pub trait OpenList<Element> {
     fn is_empty(&self) -> bool;
+    fn len(&self) -> usize;
     fn push_open(&mut self, e: Element);
     fn pop_min(&mut self) -> Option<Element>;
 }
 
+/// A borrowed open list is an open list too, so a caller can keep one (and its allocation) for
+/// search after search, rather than a search making its own.
+impl<Element, L: OpenList<Element> + ?Sized> OpenList<Element> for &mut L {
+    fn is_empty(&self) -> bool {
+        (**self).is_empty()
+    }
+
+    fn len(&self) -> usize {
+        (**self).len()
+    }
+
+    fn push_open(&mut self, e: Element) {
+        (**self).push_open(e)
+    }
+
+    fn pop_min(&mut self) -> Option<Element> {
+        (**self).pop_min()
+    }
+}
+
 /// OpenItem is an item in the OpenList.
 /// It needs to implement Ord so that it is sortable / ordered
 /// Additionally, in the implementation of ordering for BinaryHeap, it must reverse the Ordering
@@ -47,22 +70,109 @@ pub trait ClosedList<Node: Copy + Clone, Cost>: std::ops::IndexMut<Node, Output
     }
 }
 
+/// AStarStats is a sink for counters of the work a search did, to help explain why a search
+/// was slow and how well the heuristic guided it
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct AStarStats {
+    /// nodes popped from the openlist and had their neighbours explored
+    pub nodes_expanded: usize,
+    /// items pushed onto the openlist by the search (not including the starting points)
+    pub nodes_pushed: usize,
+    /// the most items that were in the openlist at once
+    pub queue_peak: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum AStarError {
     OpenItemNotInClosedList,
-    FoundHigherCostPath,
-    PathNotFound
+    PathNotFound,
+    /// there may be a path, but not one within the maximum cost given to astar_bounded
+    ExceedsMaxCost,
+    /// the openlist was empty to start with, so there was nowhere to search from
+    EmptyStart,
+    /// adding up the cost of a path overflowed the Cost type, so large edge costs need a wider one
+    CostOverflow,
+}
+
+impl std::fmt::Display for AStarError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OpenItemNotInClosedList => write!(f, "item in the openlist was not in the closed list"),
+            PathNotFound => write!(f, "no path found"),
+            ExceedsMaxCost => write!(f, "no path found within the maximum cost"),
+            CostOverflow => write!(f, "the cost of a path overflowed"),
+            EmptyStart => write!(f, "the search was not given a starting point"),
+        }
+    }
 }
 
+impl std::error::Error for AStarError {}
+
+/// CheckedCost is adding up costs without silently wrapping around. Integer costs with large
+/// penalties on each edge can overflow over a long path, which would make an expensive path look
+/// cheap; astar reports CostOverflow instead.
+pub trait CheckedCost: Sized {
+    /// checked_cost_add is the sum of the costs, or None if it can't be represented
+    fn checked_cost_add(self, other: Self) -> Option<Self>;
+
+    /// saturating_cost_add is the sum of the costs, or the largest cost if it can't be represented.
+    /// Cost models combining costs for a single jump use it, so an overflowing jump is as expensive
+    /// as a jump can be, and astar reports CostOverflow for any path taking it after another.
+    fn saturating_cost_add(self, other: Self) -> Self;
+}
+
+macro_rules! checked_integer_cost {
+    ($($t:ty),*) => {
+        $(impl CheckedCost for $t {
+            #[inline]
+            fn checked_cost_add(self, other: Self) -> Option<Self> {
+                self.checked_add(other)
+            }
+
+            #[inline]
+            fn saturating_cost_add(self, other: Self) -> Self {
+                self.saturating_add(other)
+            }
+        })*
+    };
+}
+
+checked_integer_cost!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize);
+
+macro_rules! checked_float_cost {
+    ($($t:ty),*) => {
+        $(impl CheckedCost for ordered_float::NotNan<$t> {
+            /// floats don't wrap, but overflow to infinity
+            #[inline]
+            fn checked_cost_add(self, other: Self) -> Option<Self> {
+                let sum = self.into_inner() + other.into_inner();
+                sum.is_finite().then(|| ordered_float::NotNan::new(sum).ok()).flatten()
+            }
+
+            /// the largest float is infinity, which checked_cost_add then refuses to add to
+            #[inline]
+            fn saturating_cost_add(self, other: Self) -> Self {
+                self + other
+            }
+        })*
+    };
+}
+
+checked_float_cost!(f32, f64);
+
 /// astar implements A* over a number of trait bounds and using mostly things managed outside of it
 /// This uses a number of trait bounds on things like Cost to be generic over integers / floats
+/// `neighbours` gives the cost of the edge to each neighbour of a node and the neighbour. It can
+/// return any iterable, so a lazy iterator over the map saves allocating a Vec per expansion.
 pub fn astar<
     Node: Copy,
     Open: OpenList<OpenItem<Node, Cost>>,
     Closed: ClosedList<Node, Cost>,
-    Cost: Ord + Copy + std::ops::Add<Output=Cost>,
+    Cost: Ord + Copy + CheckedCost,
     IsGoalFn: Fn(&Node) -> bool,
     HeuristicFn: Fn(&Node) -> Cost,
-    GetNeighboursFn: Fn(&Node) -> Vec<(Cost, Node)>
+    Neighbours: IntoIterator<Item = (Cost, Node)>,
+    GetNeighboursFn: Fn(&Node) -> Neighbours
 >(
     openlist: &mut Open,
     closed: &mut Closed,
@@ -71,6 +181,69 @@ pub fn astar<
     neighbours: GetNeighboursFn,
 ) -> Result<Node, AStarError>
 {
+    astar_bounded(openlist, closed, is_goal, heuristic, neighbours, None, &mut AStarStats::default())
+}
+
+/// astar_bounded is astar, but never extends a path beyond `max_cost`.
+/// A path costing exactly `max_cost` is still allowed. If paths were cut off by the bound and no
+/// path was found, the error is ExceedsMaxCost rather than PathNotFound.
+/// The work done is added to `stats`.
+/// The openlist has to be seeded with the starting point(s), otherwise the error is EmptyStart.
+pub fn astar_bounded<
+    Node: Copy,
+    Open: OpenList<OpenItem<Node, Cost>>,
+    Closed: ClosedList<Node, Cost>,
+    Cost: Ord + Copy + CheckedCost,
+    IsGoalFn: Fn(&Node) -> bool,
+    HeuristicFn: Fn(&Node) -> Cost,
+    Neighbours: IntoIterator<Item = (Cost, Node)>,
+    GetNeighboursFn: Fn(&Node) -> Neighbours
+>(
+    openlist: &mut Open,
+    closed: &mut Closed,
+    is_goal: IsGoalFn,
+    heuristic: HeuristicFn,
+    neighbours: GetNeighboursFn,
+    max_cost: Option<Cost>,
+    stats: &mut AStarStats,
+) -> Result<Node, AStarError>
+{
+    astar_observed(openlist, closed, is_goal, heuristic, neighbours, max_cost, stats, |_, _| {})
+}
+
+/// astar_observed is astar_bounded, calling `on_expand` with each node as it's expanded and its
+/// state in the closed list (so the cost of reaching it), in the order the search explores them.
+/// That's for watching a search, eg. to animate it; the other astar functions pass a closure that
+/// does nothing, which compiles away.
+#[allow(clippy::too_many_arguments)]
+pub fn astar_observed<
+    Node: Copy,
+    Open: OpenList<OpenItem<Node, Cost>>,
+    Closed: ClosedList<Node, Cost>,
+    Cost: Ord + Copy + CheckedCost,
+    IsGoalFn: Fn(&Node) -> bool,
+    HeuristicFn: Fn(&Node) -> Cost,
+    Neighbours: IntoIterator<Item = (Cost, Node)>,
+    GetNeighboursFn: Fn(&Node) -> Neighbours,
+    OnExpandFn: FnMut(&Node, &ClosedListState<Node, Cost>),
+>(
+    openlist: &mut Open,
+    closed: &mut Closed,
+    is_goal: IsGoalFn,
+    heuristic: HeuristicFn,
+    neighbours: GetNeighboursFn,
+    max_cost: Option<Cost>,
+    stats: &mut AStarStats,
+    mut on_expand: OnExpandFn,
+) -> Result<Node, AStarError>
+{
+    if openlist.is_empty() {
+        return Err(EmptyStart);
+    }
+
+    let mut exceeded_max_cost = false;
+    stats.queue_peak = stats.queue_peak.max(openlist.len());
+
     while let Some(item) = openlist.pop_min() {
         let current_node = item.node;
 
@@ -84,15 +257,23 @@ pub fn astar<
             StartingPoint(c) => *c,
             Unvisited => return Err(OpenItemNotInClosedList),
         };
+        stats.nodes_expanded += 1;
+        on_expand(&current_node, &closed[current_node]);
 
         for (neighbour_cost, neighbour) in neighbours(&current_node) {
-            let potential_path_cost = neighbour_cost + current_cost;
+            let potential_path_cost = neighbour_cost.checked_cost_add(current_cost).ok_or(CostOverflow)?;
+
+            if max_cost.is_some_and(|max| potential_path_cost > max) {
+                exceeded_max_cost = true;
+                continue;
+            }
 
             match closed[neighbour] {
                 PathFrom(_, existing_cost) if existing_cost <= potential_path_cost => continue,
                 StartingPoint(_) => continue,
-                PathFrom(_, _) => return Err(FoundHigherCostPath),
-                Unvisited => (),
+                // With weighted costs we can find a cheaper path to a node we already reached.
+                // Relax it: take the cheaper path and explore the node again from the openlist
+                PathFrom(_, _) | Unvisited => (),
             };
 
             // Set the cost of the neighbour to the total cost, and the origin as the current node
@@ -100,15 +281,163 @@ pub fn astar<
 
             // Add the neighbour to the openlist to be explored when it is the lowest total estimated distance
             openlist.push_open(OpenItem {
-                heuristic: potential_path_cost + heuristic(&neighbour),
+                heuristic: potential_path_cost.checked_cost_add(heuristic(&neighbour)).ok_or(CostOverflow)?,
                 node: neighbour,
             });
+            stats.nodes_pushed += 1;
+            stats.queue_peak = stats.queue_peak.max(openlist.len());
         }
     }
 
+    if exceeded_max_cost {
+        return Err(ExceedsMaxCost);
+    }
     Err(PathNotFound)
 }
 
+/// BidirectionalLists are the open and closed lists for one direction of bidirectional_dijkstra
+pub struct BidirectionalLists<'a, Open, Closed> {
+    pub open: &'a mut Open,
+    pub closed: &'a mut Closed,
+}
+
+/// bidirectional_dijkstra finds the cheapest path from `from` to `to` with two Dijkstra searches,
+/// one forwards from `from` and one backwards from `to` over `reverse_neighbours` (the nodes with an
+/// edge into a node, and the cost of that edge). Without a heuristic to guide it, a search explores
+/// a ball around its start, and two balls that meet in the middle are roughly half the size of one
+/// reaching all the way.
+///
+/// Meeting isn't enough to stop with weighted edges, since a cheaper path can still come through
+/// nodes neither search has finished with. Each search's latest cost is a lower bound on the rest
+/// of its queue, so once the two add up to at least the cheapest path through a meeting point,
+/// nothing left can beat it.
+///
+/// The lists start empty and are seeded here. The work done by both searches is added to `stats`.
+pub fn bidirectional_dijkstra<
+    Node: Copy + PartialEq,
+    Open: OpenList<OpenItem<Node, Cost>>,
+    Closed: ClosedList<Node, Cost>,
+    Cost: Ord + Copy + CheckedCost + num::Zero,
+    Neighbours: IntoIterator<Item = (Cost, Node)>,
+    NeighboursFn: Fn(&Node) -> Neighbours,
+    ReverseNeighbours: IntoIterator<Item = (Cost, Node)>,
+    ReverseNeighboursFn: Fn(&Node) -> ReverseNeighbours,
+>(
+    from: Node,
+    to: Node,
+    forward: BidirectionalLists<Open, Closed>,
+    backward: BidirectionalLists<Open, Closed>,
+    neighbours: NeighboursFn,
+    reverse_neighbours: ReverseNeighboursFn,
+    stats: &mut AStarStats,
+) -> Result<(Vec<Node>, Cost), AStarError>
+{
+    if from == to {
+        return Ok((vec![from], Cost::zero()));
+    }
+
+    let (mut forward, mut backward) = (forward, backward);
+    for (lists, start) in [(&mut forward, from), (&mut backward, to)] {
+        lists.open.push_open(OpenItem {
+            heuristic: Cost::zero(),
+            node: start,
+        });
+        lists.closed[start] = StartingPoint(Cost::zero());
+    }
+    stats.queue_peak = stats.queue_peak.max(2);
+
+    // the cheapest path through a node reached from both sides so far, and that node
+    let mut best: Option<(Cost, Node)> = None;
+    // the cost of the last node each side took from its queue
+    let mut latest = [Cost::zero(), Cost::zero()];
+
+    loop {
+        let bound = latest[0].checked_cost_add(latest[1]).ok_or(CostOverflow)?;
+        if best.is_some_and(|(cost, _)| bound >= cost) {
+            break;
+        }
+
+        // expand the side with less in its queue, to keep the two searches balanced
+        let (forward_len, backward_len) = (forward.open.len(), backward.open.len());
+        let expanded = if forward_len > 0 && (backward_len == 0 || forward_len <= backward_len) {
+            bidirectional_step(&mut forward, backward.closed, &neighbours, &mut best, stats)?.map(|c| (0, c))
+        } else {
+            bidirectional_step(&mut backward, forward.closed, &reverse_neighbours, &mut best, stats)?.map(|c| (1, c))
+        };
+        match expanded {
+            Some((side, cost)) => latest[side] = cost,
+            None => break,
+        }
+        stats.queue_peak = stats.queue_peak.max(forward.open.len() + backward.open.len());
+    }
+
+    let (cost, meeting) = best.ok_or(PathNotFound)?;
+    let mut path = forward.closed.unwind(meeting);
+    let mut rest = backward.closed.unwind(meeting);
+    rest.reverse();
+    path.extend(rest.into_iter().skip(1));
+    Ok((path, cost))
+}
+
+/// bidirectional_step takes the next node from one side's queue and relaxes its edges, noting any
+/// cheaper path through a node that the `other` side has reached. It returns the cost of the node,
+/// or None if the queue was empty.
+fn bidirectional_step<Node, Open, Closed, Cost, Neighbours, NeighboursFn>(
+    lists: &mut BidirectionalLists<Open, Closed>,
+    other: &Closed,
+    neighbours: &NeighboursFn,
+    best: &mut Option<(Cost, Node)>,
+    stats: &mut AStarStats,
+) -> Result<Option<Cost>, AStarError>
+where
+    Node: Copy,
+    Open: OpenList<OpenItem<Node, Cost>>,
+    Closed: ClosedList<Node, Cost>,
+    Cost: Ord + Copy + CheckedCost,
+    Neighbours: IntoIterator<Item = (Cost, Node)>,
+    NeighboursFn: Fn(&Node) -> Neighbours,
+{
+    let Some(item) = lists.open.pop_min() else {
+        return Ok(None);
+    };
+    let cost = match lists.closed[item.node] {
+        PathFrom(_, c) | StartingPoint(c) => c,
+        Unvisited => return Err(OpenItemNotInClosedList),
+    };
+    // a cheaper path to the node was found after this item was queued, and has been expanded already
+    if cost < item.heuristic {
+        return Ok(Some(item.heuristic));
+    }
+    stats.nodes_expanded += 1;
+
+    for (edge_cost, neighbour) in neighbours(&item.node) {
+        let potential_path_cost = cost.checked_cost_add(edge_cost).ok_or(CostOverflow)?;
+        match lists.closed[neighbour] {
+            PathFrom(_, existing_cost) if existing_cost <= potential_path_cost => continue,
+            StartingPoint(_) => continue,
+            PathFrom(_, _) | Unvisited => (),
+        };
+
+        lists.closed[neighbour] = PathFrom(item.node, potential_path_cost);
+        lists.open.push_open(OpenItem {
+            heuristic: potential_path_cost,
+            node: neighbour,
+        });
+        stats.nodes_pushed += 1;
+
+        let other_cost = match other[neighbour] {
+            PathFrom(_, c) | StartingPoint(c) => c,
+            Unvisited => continue,
+        };
+        let through = potential_path_cost.checked_cost_add(other_cost).ok_or(CostOverflow)?;
+        if best.is_none_or(|(b, _)| through < b) {
+            *best = Some((through, neighbour));
+        }
+    }
+
+    Ok(Some(cost))
+}
+
 // TODO: There are a a whole load of relations that have to be guaranteed here
 //       Need to double check that the required relationships apply with these implementations
 impl<Node: Eq, Cost> Eq for OpenItem<Node, Cost> {}
@@ -121,14 +450,137 @@ impl<Node: Eq, Cost> PartialEq for OpenItem<Node, Cost> {
 
 impl<Node: Eq, Cost: Ord> Ord for OpenItem<Node, Cost> {
     fn cmp(&self, other: &Self) -> Ordering {
-        self.partial_cmp(other).unwrap()
+        self.heuristic
+            .cmp(&other.heuristic)
+            .reverse() // reverse the ordering so that a priority queue is min first
     }
 }
 
 impl<Node: Eq, Cost: Ord> PartialOrd for OpenItem<Node, Cost> {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        self.heuristic
-            .partial_cmp(&other.heuristic)
-            .map(|x| x.reverse()) // reverse the ordering so that a priority queue is min first
+        Some(self.cmp(other))
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hashclosed::HashClosed;
+    use crate::simpleopen::SimpleOpenList;
+    use ordered_float::NotNan;
+
+    #[test]
+    fn checked_cost_add_refuses_to_wrap_around() {
+        assert_eq!(3u32.checked_cost_add(4), Some(7));
+        assert_eq!(u32::MAX.checked_cost_add(1), None);
+        assert_eq!(u32::MAX.saturating_cost_add(1), u32::MAX);
+        assert_eq!(i8::MIN.checked_cost_add(-1), None);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn checked_cost_add_of_floats_refuses_infinity() {
+        let big = NotNan::new(f32::MAX).unwrap();
+        assert_eq!(NotNan::new(1.5f32).unwrap().checked_cost_add(NotNan::new(2.0).unwrap()), NotNan::new(3.5).ok());
+        assert_eq!(big.checked_cost_add(big), None);
+        assert_eq!(big.saturating_cost_add(big).into_inner(), f32::INFINITY);
+    }
+
+    #[test]
+    fn astar_reports_a_path_cost_that_overflows() {
+        // a line 0 - 1 - 2 - 3 where each step costs more than a third of a u8
+        let mut open = SimpleOpenList::new();
+        let mut closed = HashClosed::new();
+        open.push_open(OpenItem { heuristic: 0u8, node: 0u32 });
+        closed[0] = StartingPoint(0);
+
+        let result = astar(&mut open, &mut closed, |n| *n == 3, |_| 0, |n| [(100, n + 1)]);
+        assert_eq!(result, Err(CostOverflow));
+    }
+
+    #[test]
+    fn astar_without_a_starting_point_is_empty_start() {
+        let mut open: SimpleOpenList<u32, u32> = SimpleOpenList::new();
+        let mut closed = HashClosed::new();
+        let result = astar(&mut open, &mut closed, |n| *n == 3, |_| 0, |n| [(1, n + 1)]);
+        assert_eq!(result, Err(EmptyStart));
+        assert_eq!(EmptyStart.to_string(), "the search was not given a starting point");
+    }
+
+    #[test]
+    fn astar_observed_sees_each_expansion_in_order_with_its_cost() {
+        // a line 0 - 1 - 2 - 3 - 4, searched from 0 to 3 with each step costing 2
+        let mut open = SimpleOpenList::new();
+        let mut closed = HashClosed::new();
+        open.push_open(OpenItem { heuristic: 0u32, node: 0u32 });
+        closed[0] = StartingPoint(0);
+        let mut stats = AStarStats::default();
+
+        let mut seen = Vec::new();
+        let goal = astar_observed(
+            &mut open,
+            &mut closed,
+            |n| *n == 3,
+            |_| 0,
+            |n| [(2, n + 1)].into_iter().chain((*n > 0).then(|| (2, n - 1))),
+            None,
+            &mut stats,
+            |n, state| {
+                let cost = match state {
+                    PathFrom(_, c) | StartingPoint(c) => *c,
+                    Unvisited => panic!("{n} expanded before it was reached"),
+                };
+                seen.push((*n, cost));
+            },
+        )
+        .unwrap();
+
+        assert_eq!(goal, 3);
+        assert_eq!(seen[..3], [(0, 0), (1, 2), (2, 4)]);
+        assert_eq!(seen.len(), stats.nodes_expanded);
+    }
+
+    #[test]
+    fn bidirectional_dijkstra_keeps_going_past_the_first_meeting() {
+        // 0 - 1 - 5 meets after a node from each side, but 0 - 2 - 3 - 4 - 5 is cheaper
+        let edges = [(0u32, 1u32, 1u32), (1, 5, 10), (0, 2, 2), (2, 3, 2), (3, 4, 2), (4, 5, 2)];
+        let neighbours = |n: &u32| {
+            let n = *n;
+            edges.iter().filter_map(move |&(a, b, c)| {
+                if a == n {
+                    Some((c, b))
+                } else {
+                    (b == n).then_some((c, a))
+                }
+            })
+        };
+        let (mut forward_open, mut backward_open) = (SimpleOpenList::new(), SimpleOpenList::new());
+        let (mut forward_closed, mut backward_closed) = (HashClosed::new(), HashClosed::new());
+        let mut stats = AStarStats::default();
+        let result = bidirectional_dijkstra(
+            0,
+            5,
+            BidirectionalLists { open: &mut forward_open, closed: &mut forward_closed },
+            BidirectionalLists { open: &mut backward_open, closed: &mut backward_closed },
+            neighbours,
+            neighbours,
+            &mut stats,
+        );
+        assert_eq!(result, Ok((vec![0, 2, 3, 4, 5], 8)));
+        assert!(stats.nodes_expanded > 0);
+    }
+
+    #[test]
+    fn bidirectional_dijkstra_without_a_path_is_path_not_found() {
+        let (mut forward_open, mut backward_open) = (SimpleOpenList::new(), SimpleOpenList::new());
+        let (mut forward_closed, mut backward_closed) = (HashClosed::new(), HashClosed::new());
+        let result = bidirectional_dijkstra(
+            0u32,
+            9,
+            BidirectionalLists { open: &mut forward_open, closed: &mut forward_closed },
+            BidirectionalLists { open: &mut backward_open, closed: &mut backward_closed },
+            |n| (*n < 3).then_some((1u32, n + 1)),
+            |n| (*n > 6).then_some((1u32, n - 1)),
+            &mut AStarStats::default(),
+        );
+        assert_eq!(result, Err(PathNotFound));
+    }
+}
@@ -0,0 +1,960 @@
+//! Breadth first searches over the map.
+//!
+//! When every jump costs the same, a BFS finds shortest routes without needing astar's open and
+//! closed lists, and visits systems in order of their distance in jumps.
+use crate::bitset::BitsetVisited;
+use crate::evemap::{Map, RawIndex, SolarSystemIndex};
+use crate::rng::SplitMix64;
+use rayon::prelude::*;
+use std::cmp::Reverse;
+use std::collections::VecDeque;
+
+/// DistanceTable is the number of jumps to every system from a single origin, along with the
+/// previous system on a shortest route to each. Keeping it around lets repeated queries from the
+/// same origin reconstruct routes without searching again.
+#[derive(Debug, Clone)]
+pub struct DistanceTable {
+    origin: SolarSystemIndex,
+    distances: Vec<Option<u32>>,
+    previous: Vec<Option<SolarSystemIndex>>,
+}
+
+impl DistanceTable {
+    pub fn origin(&self) -> SolarSystemIndex {
+        self.origin
+    }
+
+    /// get is the number of jumps from the origin to `i`, or None if it can't be reached
+    #[inline]
+    pub fn get(&self, i: &SolarSystemIndex) -> Option<u32> {
+        self.distances[usize::from(*i)]
+    }
+
+    /// iter yields every reachable system and its distance, in SolarSystemIndex order
+    pub fn iter(&self) -> impl Iterator<Item = (SolarSystemIndex, u32)> + '_ {
+        self.distances
+            .iter()
+            .enumerate()
+            .filter_map(|(i, d)| d.map(|d| (SolarSystemIndex(i as RawIndex), d)))
+    }
+}
+
+impl Map {
+    /// distances_from runs a full BFS from `origin` to find the jumps to every system
+    pub fn distances_from(&self, origin: &SolarSystemIndex) -> DistanceTable {
+        let mut distances = vec![None; self.system_count()];
+        let mut previous = vec![None; self.system_count()];
+        let mut queue = VecDeque::from([*origin]);
+        distances[usize::from(*origin)] = Some(0);
+
+        while let Some(current) = queue.pop_front() {
+            let next_distance = distances[usize::from(current)].map(|d: u32| d + 1);
+            for n in self.get_neighbours(&current) {
+                if distances[usize::from(n)].is_none() {
+                    distances[usize::from(n)] = next_distance;
+                    previous[usize::from(n)] = Some(current);
+                    queue.push_back(n);
+                }
+            }
+        }
+
+        DistanceTable {
+            origin: *origin,
+            distances,
+            previous,
+        }
+    }
+
+    /// eccentricity is the most jumps it takes to get from `i` to any system it can reach, or None
+    /// if it can't reach any other system
+    pub fn eccentricity(&self, i: &SolarSystemIndex) -> Option<u32> {
+        self.distances_from(i).iter().map(|(_, d)| d).max().filter(|d| *d > 0)
+    }
+
+    /// diameter is the longest shortest route in the largest connected component of the map:
+    /// the most jumps between any two of its systems, when taking the shortest route.
+    ///
+    /// This needs a BFS from every system in the component, so it's O(V·(V+E)). The searches run
+    /// in parallel, but on the full map it's still a lot slower than any single route.
+    pub fn diameter(&self) -> u32 {
+        self.connected_components()
+            .into_iter()
+            .max_by_key(|c| c.len())
+            .unwrap_or_default()
+            .par_iter()
+            .filter_map(|i| self.eccentricity(i))
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// max_route_length is the most jumps any shortest route in the map takes, across every
+    /// connected component (so it's at least the diameter). It's found the first time it's asked
+    /// for, as slowly as diameter, and kept until the jumps change.
+    ///
+    /// It's the yardstick for penalties meant to outweigh jumps: with a penalty of
+    /// `max_route_length() + 1`, eg. for `BorderCostModel::with_penalty`, a single penalised jump
+    /// costs more than any shortest route, so any way around it no longer than that is taken
+    /// instead. Detours can be longer still; for a bound none can exceed, use system_count.
+    pub fn max_route_length(&self) -> u32 {
+        *self.max_route_length.get_or_init(|| {
+            (0..self.system_count())
+                .into_par_iter()
+                .filter_map(|i| self.eccentricity(&SolarSystemIndex(i as RawIndex)))
+                .max()
+                .unwrap_or(0)
+        })
+    }
+
+    /// sample_distances is the jumps between `n` random pairs of different systems that can reach
+    /// each other, for a histogram of route lengths across the map. The pairs are picked with a
+    /// SplitMix64 seeded with `seed`, so the same seed gives the same sample.
+    ///
+    /// It's a sample, not every pair: it takes a BFS per pair (run in parallel), rather than a BFS
+    /// from every system like diameter. Jumps taken out with remove_edge can leave a pair with no
+    /// route after all, and then it's left out, so there may be fewer than `n` distances.
+    pub fn sample_distances(&self, n: usize, seed: u64) -> Vec<u32> {
+        let components: Vec<Vec<SolarSystemIndex>> =
+            self.connected_components().into_iter().filter(|c| c.len() > 1).collect();
+        let systems: Vec<(SolarSystemIndex, usize)> = components
+            .iter()
+            .enumerate()
+            .flat_map(|(c, systems)| systems.iter().map(move |i| (*i, c)))
+            .collect();
+        if systems.is_empty() {
+            return Vec::new();
+        }
+
+        let mut rng = SplitMix64::new(seed);
+        let pairs: Vec<(SolarSystemIndex, SolarSystemIndex)> = (0..n)
+            .map(|_| {
+                let (from, c) = systems[rng.below(systems.len())];
+                // pick from the rest of the component, so that `to` is never `from`
+                let others = &components[c];
+                let at = others.iter().position(|i| *i == from).unwrap_or_default();
+                let pick = rng.below(others.len() - 1);
+                (from, others[if pick < at { pick } else { pick + 1 }])
+            })
+            .collect();
+
+        pairs
+            .par_iter()
+            .filter_map(|(from, to)| self.jumps_between(from, to))
+            .collect()
+    }
+
+    /// betweenness_centrality scores each system by how many shortest routes pass through it, to
+    /// find the busiest transit systems. For every pair of systems (s, t), each system strictly
+    /// between them gets its share of the shortest routes from s to t. The scores aren't
+    /// normalised, and stargates go both ways, so each pair of systems counts twice. Systems are
+    /// returned busiest first, ties in SolarSystemIndex order.
+    ///
+    /// It's Brandes' algorithm on jumps: a BFS from every system, with the sources shared out over
+    /// rayon. That's O(V·E), much like diameter, so on the full map it takes a while.
+    pub fn betweenness_centrality(&self) -> Vec<(SolarSystemIndex, f64)> {
+        let n = self.system_count();
+        let scores = (0..n)
+            .into_par_iter()
+            .fold(
+                || vec![0.0; n],
+                |mut scores, source| {
+                    let source = SolarSystemIndex(source as RawIndex);
+                    // number of shortest routes from the source to each system, and their length
+                    let mut routes = vec![0.0f64; n];
+                    let mut distances: Vec<Option<u32>> = vec![None; n];
+                    let mut order = Vec::new();
+                    let mut queue = VecDeque::from([source]);
+                    routes[usize::from(source)] = 1.0;
+                    distances[usize::from(source)] = Some(0);
+
+                    while let Some(current) = queue.pop_front() {
+                        order.push(current);
+                        let next = distances[usize::from(current)].map(|d| d + 1);
+                        for w in self.get_neighbours(&current) {
+                            if distances[usize::from(w)].is_none() {
+                                distances[usize::from(w)] = next;
+                                queue.push_back(w);
+                            }
+                            if distances[usize::from(w)] == next {
+                                routes[usize::from(w)] += routes[usize::from(current)];
+                            }
+                        }
+                    }
+
+                    // back from the furthest systems, passing each one's dependency on to the
+                    // systems before it on shortest routes
+                    let mut dependency = vec![0.0f64; n];
+                    for w in order.iter().rev() {
+                        let before = distances[usize::from(*w)].and_then(|d| d.checked_sub(1));
+                        for v in self.get_predecessors(w) {
+                            if distances[usize::from(v)].is_some() && distances[usize::from(v)] == before {
+                                dependency[usize::from(v)] += routes[usize::from(v)] / routes[usize::from(*w)]
+                                    * (1.0 + dependency[usize::from(*w)]);
+                            }
+                        }
+                        if *w != source {
+                            scores[usize::from(*w)] += dependency[usize::from(*w)];
+                        }
+                    }
+                    scores
+                },
+            )
+            .reduce(
+                || vec![0.0; n],
+                |mut a, b| {
+                    a.iter_mut().zip(b).for_each(|(a, b)| *a += b);
+                    a
+                },
+            );
+
+        let mut ranked: Vec<(SolarSystemIndex, f64)> = scores
+            .into_iter()
+            .enumerate()
+            .map(|(i, score)| (SolarSystemIndex(i as RawIndex), score))
+            .collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        ranked
+    }
+
+    /// region_center is the most central system of a region for staging: the one with the least
+    /// eccentricity when only jumps within the region are taken. Ties go to the lowest
+    /// SolarSystemId. If the region doesn't hang together by itself, systems that reach more of it
+    /// come first. None if there are no systems in the region.
+    ///
+    /// Like diameter, it takes a BFS from every system in the region, run in parallel.
+    pub fn region_center(&self, region_id: u64) -> Option<SolarSystemIndex> {
+        let in_region = |i: &SolarSystemIndex| self.get_extended_solarsystem_info(i).region_id == region_id;
+        let systems: Vec<SolarSystemIndex> = (0..self.system_count())
+            .map(|i| SolarSystemIndex(i as RawIndex))
+            .filter(in_region)
+            .collect();
+
+        systems
+            .par_iter()
+            .map(|origin| {
+                let mut visited = BitsetVisited::new(self.system_count());
+                let mut queue = VecDeque::from([(*origin, 0u32)]);
+                visited.set(*origin);
+                let (mut reached, mut eccentricity) = (0, 0);
+                while let Some((current, d)) = queue.pop_front() {
+                    reached += 1;
+                    eccentricity = d;
+                    for n in self.get_neighbours(&current) {
+                        if !visited.is_set(n) && in_region(&n) {
+                            visited.set(n);
+                            queue.push_back((n, d + 1));
+                        }
+                    }
+                }
+                let id = self.get_extended_solarsystem_info(origin).solar_system_id;
+                (Reverse(reached), eccentricity, id, *origin)
+            })
+            .min()
+            .map(|(_, _, _, i)| i)
+    }
+
+    /// connected_components groups the systems that can reach each other, ignoring the direction
+    /// of jumps. Components are in order of their lowest SolarSystemIndex.
+    pub fn connected_components(&self) -> Vec<Vec<SolarSystemIndex>> {
+        let mut seen = BitsetVisited::new(self.system_count());
+        let mut components = Vec::new();
+
+        for start in 0..self.system_count() {
+            let start = SolarSystemIndex(start as RawIndex);
+            if seen.is_set(start) {
+                continue;
+            }
+            let mut component = Vec::new();
+            let mut queue = VecDeque::from([start]);
+            seen.set(start);
+            while let Some(current) = queue.pop_front() {
+                component.push(current);
+                for n in self.get_neighbours(&current).chain(self.get_predecessors(&current)) {
+                    if !seen.is_set(n) {
+                        seen.set(n);
+                        queue.push_back(n);
+                    }
+                }
+            }
+            components.push(component);
+        }
+
+        components
+    }
+
+    /// path_exists is a quick check, without searching, of whether there can be a route from `from`
+    /// to `to`: it's false when they're in different connected components.
+    ///
+    /// Stargates come in pairs, so for them being in the same component means there's a route,
+    /// but jumps taken out with remove_edge don't split components here (even when the labels are
+    /// redone, eg. by apply_patch). So false is certain, while true means a search is worth doing.
+    #[inline]
+    pub fn path_exists(&self, from: &SolarSystemIndex, to: &SolarSystemIndex) -> bool {
+        self.components[usize::from(*from)] == self.components[usize::from(*to)]
+    }
+
+    /// component_labels numbers each system's connected component, indexed by SolarSystemIndex.
+    /// The jumps remove_edge has taken out count as joining their systems, since restore_edge
+    /// can put them back without the labels being redone.
+    pub(crate) fn component_labels(&self) -> Vec<u32> {
+        let components = self.connected_components();
+        let mut labels = vec![0; self.system_count()];
+        for (label, component) in components.iter().enumerate() {
+            for i in component {
+                labels[usize::from(*i)] = label as u32;
+            }
+        }
+
+        // merge the components a removed jump would join, pointing each label at the one it's
+        // merged into (always a lower label, so following them ends at the lowest)
+        let mut merged_into: Vec<u32> = (0..components.len() as u32).collect();
+        let root = |merged_into: &[u32], mut label: u32| {
+            while merged_into[label as usize] != label {
+                label = merged_into[label as usize];
+            }
+            label
+        };
+        for (from, to) in &self.removed_edges {
+            let a = root(&merged_into, labels[usize::from(*from)]);
+            let b = root(&merged_into, labels[usize::from(*to)]);
+            merged_into[a.max(b) as usize] = a.min(b);
+        }
+        labels.iter().map(|label| root(&merged_into, *label)).collect()
+    }
+
+    /// rendezvous is the best system for pilots in `a` and `b` to meet: the one minimising the
+    /// jumps the further of them has to make, so they both arrive as soon as possible. Ties go to
+    /// the fewest jumps in total, then the lowest SolarSystemIndex. It returns the system and the
+    /// jumps from `a` and `b` to it, or None if no system can be reached from both.
+    pub fn rendezvous(&self, a: &SolarSystemIndex, b: &SolarSystemIndex) -> Option<(SolarSystemIndex, u32, u32)> {
+        let (from_a, from_b) = (self.distances_from(a), self.distances_from(b));
+        from_a
+            .iter()
+            .filter_map(|(i, jumps_a)| Some((i, jumps_a, from_b.get(&i)?)))
+            .min_by_key(|(i, jumps_a, jumps_b)| (*jumps_a.max(jumps_b), jumps_a + jumps_b, *i))
+    }
+
+    /// route_using_distances reconstructs the shortest route from the table's origin to `to`
+    /// from a table made by distances_from, without searching. None if `to` can't be reached.
+    pub fn route_using_distances(&self, table: &DistanceTable, to: &SolarSystemIndex) -> Option<Vec<SolarSystemIndex>> {
+        table.get(to)?;
+
+        let mut path = vec![*to];
+        while let Some(p) = table.previous[usize::from(*path.last()?)] {
+            path.push(p);
+        }
+        path.reverse();
+        Some(path)
+    }
+
+    /// at_exactly_jumps is the "ring" of systems exactly `n` jumps from `origin`, in SolarSystemIndex
+    /// order. A ring of 0 is just the origin, and beyond the edge of the map the ring is empty.
+    pub fn at_exactly_jumps(&self, origin: &SolarSystemIndex, n: u32) -> Vec<SolarSystemIndex> {
+        self.distances_from(origin)
+            .iter()
+            .filter(|(_, d)| *d == n)
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// nearest_safe finds the closest system (in jumps) to `from` with a security of at least
+    /// `min_sec`, and how many jumps away it is. If `from` is already safe enough, it's returned
+    /// with a distance of 0. Returns None if no reachable system is safe enough.
+    pub fn nearest_safe(&self, from: &SolarSystemIndex, min_sec: f32) -> Option<(SolarSystemIndex, u32)> {
+        let mut visited = BitsetVisited::new(self.system_count());
+        let mut queue = VecDeque::from([(*from, 0u32)]);
+        visited.set(*from);
+
+        while let Some((current, distance)) = queue.pop_front() {
+            if self.get_extended_solarsystem_info(&current).security_true() >= min_sec {
+                return Some((current, distance));
+            }
+
+            for n in self.get_neighbours(&current) {
+                if !visited.is_set(n) {
+                    visited.set(n);
+                    queue.push_back((n, distance + 1));
+                }
+            }
+        }
+
+        None
+    }
+
+    /// k_nearest finds the (up to) `k` of `candidates` closest to `from` in jumps, with their
+    /// distances, closest first. It's a single BFS that stops once it has found `k`, rather than a
+    /// route to every candidate. If fewer than `k` candidates can be reached, it returns those.
+    /// Candidates the same distance away come in the order the BFS reaches them.
+    pub fn k_nearest(
+        &self,
+        from: SolarSystemIndex,
+        candidates: &[SolarSystemIndex],
+        k: usize,
+    ) -> Vec<(SolarSystemIndex, u32)> {
+        let mut wanted = BitsetVisited::new(self.system_count());
+        for c in candidates {
+            wanted.set(*c);
+        }
+
+        let mut found = Vec::new();
+        let mut visited = BitsetVisited::new(self.system_count());
+        let mut queue = VecDeque::from([(from, 0u32)]);
+        visited.set(from);
+
+        while let Some((current, distance)) = queue.pop_front() {
+            if found.len() == k {
+                break;
+            }
+            if wanted.is_set(current) {
+                found.push((current, distance));
+            }
+
+            for n in self.get_neighbours(&current) {
+                if !visited.is_set(n) {
+                    visited.set(n);
+                    queue.push_back((n, distance + 1));
+                }
+            }
+        }
+
+        found
+    }
+
+    /// annotate_route_with_nearest pairs each system on `route` with the closest system in `set`
+    /// (eg. stations to refuel at) and the jumps to get there, or None if no system in the set can
+    /// be reached from it. Systems in the set are 0 jumps from themselves. When two are equally
+    /// close, one of them is picked, the same one each time.
+    ///
+    /// It's one BFS from the whole set at once, backwards through predecessors to get the jumps
+    /// from each system to the set, rather than a search from every system on the route.
+    pub fn annotate_route_with_nearest(
+        &self,
+        route: &[SolarSystemIndex],
+        set: &[SolarSystemIndex],
+    ) -> Vec<(SolarSystemIndex, Option<(SolarSystemIndex, u32)>)> {
+        let mut nearest: Vec<Option<(SolarSystemIndex, u32)>> = vec![None; self.system_count()];
+        let mut queue = VecDeque::new();
+        for s in set {
+            if nearest[usize::from(*s)].is_none() {
+                nearest[usize::from(*s)] = Some((*s, 0));
+                queue.push_back(*s);
+            }
+        }
+
+        while let Some(current) = queue.pop_front() {
+            let reached = nearest[usize::from(current)].map(|(member, d)| (member, d + 1));
+            for p in self.get_predecessors(&current) {
+                if nearest[usize::from(p)].is_none() {
+                    nearest[usize::from(p)] = reached;
+                    queue.push_back(p);
+                }
+            }
+        }
+
+        route.iter().map(|i| (*i, nearest[usize::from(*i)])).collect()
+    }
+
+    /// routes_to finds a shortest route to `destination` from each of `origins` (eg. a fleet
+    /// forming up), in the same order, or None for an origin that can't reach it. An origin that
+    /// is the destination gets a route of just that system.
+    ///
+    /// It's one BFS backwards through predecessors from the destination, remembering the next
+    /// system towards it from each one reached, rather than a search from every origin. Where
+    /// there are several shortest routes the one picked may differ from shortest_route's.
+    pub fn routes_to(
+        &self,
+        destination: SolarSystemIndex,
+        origins: &[SolarSystemIndex],
+    ) -> Vec<Option<Vec<SolarSystemIndex>>> {
+        let mut next: Vec<Option<SolarSystemIndex>> = vec![None; self.system_count()];
+        let mut visited = BitsetVisited::new(self.system_count());
+        let mut queue = VecDeque::from([destination]);
+        visited.set(destination);
+
+        while let Some(current) = queue.pop_front() {
+            for p in self.get_predecessors(&current) {
+                if !visited.is_set(p) {
+                    visited.set(p);
+                    next[usize::from(p)] = Some(current);
+                    queue.push_back(p);
+                }
+            }
+        }
+
+        origins
+            .iter()
+            .map(|origin| {
+                if !visited.is_set(*origin) {
+                    return None;
+                }
+                let mut path = vec![*origin];
+                while let Some(n) = next[usize::from(*path.last()?)] {
+                    path.push(n);
+                }
+                Some(path)
+            })
+            .collect()
+    }
+
+    /// jumps_between is the number of jumps on the shortest route from `from` to `to`, or None
+    /// if `to` can't be reached. Unlike astar this doesn't build the route itself.
+    pub fn jumps_between(&self, from: &SolarSystemIndex, to: &SolarSystemIndex) -> Option<u32> {
+        let mut visited = BitsetVisited::new(self.system_count());
+        let mut queue = VecDeque::from([(*from, 0u32)]);
+        visited.set(*from);
+
+        while let Some((current, distance)) = queue.pop_front() {
+            if current == *to {
+                return Some(distance);
+            }
+
+            for n in self.get_neighbours(&current) {
+                if !visited.is_set(n) {
+                    visited.set(n);
+                    queue.push_back((n, distance + 1));
+                }
+            }
+        }
+
+        None
+    }
+
+    /// reachable_within is true if `to` can be reached from `from` in at most `max` jumps.
+    ///
+    /// It's a bidirectional BFS: a frontier grows forwards from `from` and another backwards
+    /// (through predecessors) from `to`, expanding whichever is smaller a level at a time, until
+    /// they meet or their combined depth reaches `max`. When all you need is yes/no, this explores
+    /// far fewer systems than a full route.
+    pub fn reachable_within(&self, from: &SolarSystemIndex, to: &SolarSystemIndex, max: u32) -> bool {
+        if from == to {
+            return true;
+        }
+
+        let mut seen_forward = BitsetVisited::new(self.system_count());
+        let mut seen_backward = BitsetVisited::new(self.system_count());
+        seen_forward.set(*from);
+        seen_backward.set(*to);
+
+        let mut forward = vec![*from];
+        let mut backward = vec![*to];
+        let mut depth = 0;
+
+        while depth < max && !forward.is_empty() && !backward.is_empty() {
+            let expand_forward = forward.len() <= backward.len();
+            let (frontier, seen, other_seen) = if expand_forward {
+                (&mut forward, &mut seen_forward, &seen_backward)
+            } else {
+                (&mut backward, &mut seen_backward, &seen_forward)
+            };
+
+            // a call for each direction, so that expanding a system is monomorphised for its
+            // iterator rather than allocating a boxed one
+            let next = if expand_forward {
+                expand_level(frontier, |i| self.get_neighbours(i), seen, other_seen)
+            } else {
+                expand_level(frontier, |i| self.get_predecessors(i), seen, other_seen)
+            };
+            let Some(next) = next else {
+                return true;
+            };
+
+            *frontier = next;
+            depth += 1;
+        }
+
+        false
+    }
+
+    /// bfs_route finds the route with the fewest jumps from `from` to `to`, only passing through
+    /// systems for which `allowed` is true. `from` itself is always allowed.
+    pub(crate) fn bfs_route<F: Fn(&SolarSystemIndex) -> bool>(
+        &self,
+        from: &SolarSystemIndex,
+        to: &SolarSystemIndex,
+        allowed: F,
+    ) -> Option<Vec<SolarSystemIndex>> {
+        self.bfs_route_jumps(from, to, |_, n| allowed(n))
+    }
+
+    /// bfs_route_jumps is bfs_route, but `allowed` decides on each jump (from, to) rather than
+    /// on each system, so that single jumps can be ruled out
+    pub(crate) fn bfs_route_jumps<F: Fn(&SolarSystemIndex, &SolarSystemIndex) -> bool>(
+        &self,
+        from: &SolarSystemIndex,
+        to: &SolarSystemIndex,
+        allowed: F,
+    ) -> Option<Vec<SolarSystemIndex>> {
+        if from == to {
+            return Some(vec![*from]);
+        }
+
+        let mut previous: Vec<Option<SolarSystemIndex>> = vec![None; self.system_count()];
+        let mut visited = BitsetVisited::new(self.system_count());
+        let mut queue = VecDeque::from([*from]);
+        visited.set(*from);
+
+        while let Some(current) = queue.pop_front() {
+            if current == *to {
+                let mut path = vec![current];
+                while let Some(p) = previous[usize::from(*path.last()?)] {
+                    path.push(p);
+                }
+                path.reverse();
+                return Some(path);
+            }
+
+            for n in self.get_neighbours(&current) {
+                if !visited.is_set(n) && allowed(&current, &n) {
+                    visited.set(n);
+                    previous[usize::from(n)] = Some(current);
+                    queue.push_back(n);
+                }
+            }
+        }
+
+        None
+    }
+}
+
+/// expand_level is a level of reachable_within's search: the unseen systems `adjacent` to the
+/// `frontier`, marked in `seen`. It's None when one of them has been seen by the search from the
+/// other end, ie. the two searches have met.
+fn expand_level<I: Iterator<Item = SolarSystemIndex>>(
+    frontier: &[SolarSystemIndex],
+    adjacent: impl Fn(&SolarSystemIndex) -> I,
+    seen: &mut BitsetVisited,
+    other_seen: &BitsetVisited,
+) -> Option<Vec<SolarSystemIndex>> {
+    let mut next = Vec::new();
+    for current in frontier {
+        for n in adjacent(current) {
+            if other_seen.is_set(n) {
+                return None;
+            }
+            if !seen.is_set(n) {
+                seen.set(n);
+                next.push(n);
+            }
+        }
+    }
+    Some(next)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::expand_level;
+    use crate::bitset::BitsetVisited;
+    use crate::evemap::SolarSystemIndex;
+    use crate::fixtures::{indexes, line_map, map_from_edges, names, sde_map, system};
+
+    #[test]
+    fn nearest_safe_is_the_closest_system_secure_enough() {
+        let map = sde_map();
+        assert_eq!(map.nearest_safe(&system(&map, "Tama"), 0.5), Some((system(&map, "Perimeter"), 1)));
+        assert_eq!(map.nearest_safe(&system(&map, "Jita"), 0.5), Some((system(&map, "Jita"), 0)));
+    }
+
+    #[test]
+    fn nearest_safe_is_none_when_nothing_reachable_is_secure_enough() {
+        let map = sde_map();
+        let isolated = system(&map, "Isolated");
+        assert_eq!(map.nearest_safe(&isolated, 0.5), None);
+        assert_eq!(map.nearest_safe(&isolated, -1.0), Some((isolated, 0)));
+    }
+
+    #[test]
+    fn jumps_between_and_reachable_within_agree_with_astar() {
+        let map = sde_map();
+        let systems = indexes(&map);
+        for &from in &systems {
+            for &to in &systems {
+                let jumps = map.shortest_route(from, to).ok().map(|r| r.len() as u32 - 1);
+                assert_eq!(map.jumps_between(&from, &to), jumps);
+                for max in 0..6 {
+                    assert_eq!(map.reachable_within(&from, &to, max), jumps.is_some_and(|j| j <= max));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn reachable_within_follows_one_way_jumps_only_forwards() {
+        let map = {
+            let mut builder = crate::builder::MapBuilder::new();
+            builder.add_system(30_000_000, "A", 2, 1, 1.0).add_system(30_000_001, "B", 2, 1, 1.0);
+            builder.add_system(30_000_002, "C", 2, 1, 1.0);
+            builder.add_jump(30_000_000, 30_000_001, None).add_jump(30_000_001, 30_000_002, None);
+            builder.build().unwrap()
+        };
+        let (a, c) = (SolarSystemIndex(0), SolarSystemIndex(2));
+        assert!(map.reachable_within(&a, &c, 2));
+        assert!(!map.reachable_within(&a, &c, 1));
+        assert!(!map.reachable_within(&c, &a, 5));
+        assert_eq!(map.jumps_between(&c, &a), None);
+    }
+
+    #[test]
+    fn distances_from_counts_jumps_to_every_reachable_system() {
+        let map = sde_map();
+        let table = map.distances_from(&system(&map, "Jita"));
+        assert_eq!(table.origin(), system(&map, "Jita"));
+        assert_eq!(table.get(&system(&map, "Jita")), Some(0));
+        assert_eq!(table.get(&system(&map, "Amarr")), Some(2));
+        assert_eq!(table.get(&system(&map, "Niarja")), Some(3));
+        assert_eq!(table.get(&system(&map, "Isolated")), None);
+        assert_eq!(table.iter().count(), 10);
+    }
+
+    #[test]
+    fn at_exactly_jumps_is_a_ring_around_the_origin() {
+        let map = sde_map();
+        let jita = system(&map, "Jita");
+        assert_eq!(map.at_exactly_jumps(&jita, 0), [jita]);
+        let ring = map.at_exactly_jumps(&jita, 2);
+        let mut ring = names(&map, &ring);
+        ring.sort();
+        assert_eq!(ring, ["Amarr", "Madirmilire", "Tama"]);
+        assert!(map.at_exactly_jumps(&jita, 10).is_empty());
+    }
+
+    #[test]
+    fn route_using_distances_is_as_short_as_astar_for_every_destination() {
+        let map = sde_map();
+        let jita = system(&map, "Jita");
+        let table = map.distances_from(&jita);
+        for to in indexes(&map) {
+            let route = map.route_using_distances(&table, &to);
+            assert_eq!(route.as_ref().map(Vec::len), map.shortest_route(jita, to).ok().as_ref().map(Vec::len));
+            if let Some(route) = route {
+                assert_eq!((route[0], route[route.len() - 1]), (jita, to));
+                assert!(route.windows(2).all(|w| map.get_neighbours(&w[0]).any(|n| n == w[1])));
+            }
+        }
+        assert_eq!(map.route_using_distances(&table, &jita), Some(vec![jita]));
+    }
+
+    #[test]
+    fn eccentricity_is_the_furthest_reachable_system() {
+        let map = crate::fixtures::line_map(5);
+        assert_eq!(map.eccentricity(&SolarSystemIndex(0)), Some(4));
+        assert_eq!(map.eccentricity(&SolarSystemIndex(2)), Some(2));
+
+        let map = sde_map();
+        assert_eq!(map.eccentricity(&system(&map, "Sarum Prime")), Some(4));
+        assert_eq!(map.eccentricity(&system(&map, "Isolated")), None);
+    }
+
+    #[test]
+    fn diameter_is_of_the_largest_component() {
+        let map = sde_map();
+        assert_eq!(map.diameter(), 4);
+        assert_eq!(crate::fixtures::line_map(7).diameter(), 6);
+
+        // a star of 5 systems, and a longer but smaller line of 4
+        let systems: Vec<(&str, f64)> = ["A", "B", "C", "D", "E", "F", "G", "H", "I"].map(|n| (n, 1.0)).into();
+        let map = crate::fixtures::map_from_edges(&systems, &[(0, 1), (0, 2), (0, 3), (0, 4), (5, 6), (6, 7), (7, 8)]);
+        assert_eq!(map.diameter(), 2);
+        assert_eq!(crate::fixtures::line_map(1).diameter(), 0);
+    }
+
+    #[test]
+    fn connected_components_are_in_order_of_their_lowest_index() {
+        let map = sde_map();
+        let components = map.connected_components();
+        let isolated = system(&map, "Isolated");
+        assert_eq!(components.len(), 2);
+        assert_eq!(components[0].len(), 10);
+        assert_eq!(components[1], [isolated]);
+
+        let systems = [("A", 1.0), ("B", 1.0), ("C", 1.0), ("D", 1.0)];
+        let map = crate::fixtures::map_from_edges(&systems, &[(0, 2), (1, 3)]);
+        let mut components = map.connected_components();
+        components.iter_mut().for_each(|c| c.sort_unstable());
+        let (a, b, c, d) = (SolarSystemIndex(0), SolarSystemIndex(1), SolarSystemIndex(2), SolarSystemIndex(3));
+        assert_eq!(components, [[a, c], [b, d]]);
+    }
+
+    #[test]
+    fn path_exists_is_false_only_between_components() {
+        let map = sde_map();
+        let (jita, amarr, isolated) = (system(&map, "Jita"), system(&map, "Amarr"), system(&map, "Isolated"));
+        assert!(map.path_exists(&jita, &amarr));
+        assert!(map.path_exists(&isolated, &isolated));
+        assert!(!map.path_exists(&jita, &isolated));
+        assert!(!map.path_exists(&isolated, &amarr));
+        for (a, b) in indexes(&map).into_iter().flat_map(|a| indexes(&map).into_iter().map(move |b| (a, b))) {
+            assert_eq!(map.path_exists(&a, &b), map.jumps_between(&a, &b).is_some());
+        }
+    }
+
+    #[test]
+    fn path_exists_is_still_true_after_remove_edge_splits_the_map() {
+        let mut map = crate::fixtures::line_map(3);
+        let (a, c) = (SolarSystemIndex(0), SolarSystemIndex(2));
+        map.remove_edge(SolarSystemIndex(1), c);
+        assert!(map.path_exists(&a, &c));
+        assert_eq!(map.shortest_route(a, c), Err(crate::astar::AStarError::PathNotFound));
+    }
+
+    #[test]
+    fn rendezvous_is_where_the_further_pilot_arrives_soonest() {
+        let map = sde_map();
+        let (tama, amarr, jita) = (system(&map, "Tama"), system(&map, "Amarr"), system(&map, "Jita"));
+        // Niarja and Jita are both two jumps from each, and Niarja has the lower index
+        assert_eq!(map.rendezvous(&tama, &amarr), Some((system(&map, "Niarja"), 2, 2)));
+        // meeting at Perimeter is one jump for each, rather than two jumps for one of them
+        assert_eq!(map.rendezvous(&jita, &tama), Some((system(&map, "Perimeter"), 1, 1)));
+        assert_eq!(map.rendezvous(&jita, &jita), Some((jita, 0, 0)));
+        assert_eq!(map.rendezvous(&jita, &system(&map, "Isolated")), None);
+    }
+
+    #[test]
+    fn region_center_is_the_system_of_least_eccentricity() {
+        let map = sde_map();
+        let domain = map.get_extended_solarsystem_info(&system(&map, "Amarr")).region_id;
+        // Sarum Prime and Niarja are both within two jumps of all of Domain, Amarr only within three
+        assert_eq!(map.region_center(domain), Some(system(&map, "Sarum Prime")));
+        assert_eq!(map.region_center(1), None);
+
+        // S1 and S2 are both two jumps from everything: the lower SolarSystemId wins
+        assert_eq!(line_map(4).region_center(1), Some(SolarSystemIndex(1)));
+    }
+
+    #[test]
+    fn region_center_prefers_systems_reaching_more_of_the_region() {
+        // C is on its own, with an eccentricity of 0 but reaching only itself
+        let map = map_from_edges(&[("A", 1.0), ("B", 1.0), ("C", 1.0)], &[(0, 1)]);
+        assert_eq!(map.region_center(1), Some(SolarSystemIndex(0)));
+    }
+
+    #[test]
+    fn sample_distances_are_between_reachable_pairs_of_different_systems() {
+        let map = line_map(5);
+        let sample = map.sample_distances(200, 3);
+        assert_eq!(sample.len(), 200);
+        assert!(sample.iter().all(|d| (1..=4).contains(d)));
+        assert!(sample.contains(&4));
+        assert_eq!(map.sample_distances(200, 3), sample);
+
+        // the fixture's Isolated has no stargates, so it's never picked
+        let map = sde_map();
+        assert!(map.sample_distances(200, 5).iter().all(|d| (1..=map.diameter()).contains(d)));
+        let lonely = map_from_edges(&[("A", 1.0), ("B", 1.0)], &[]);
+        assert!(lonely.sample_distances(10, 1).is_empty());
+    }
+
+    #[test]
+    fn k_nearest_finds_the_closest_candidates_first() {
+        let map = line_map(6);
+        let s = |i| SolarSystemIndex(i);
+        let candidates = [s(5), s(0), s(3)];
+        assert_eq!(map.k_nearest(s(1), &candidates, 2), [(s(0), 1), (s(3), 2)]);
+        assert_eq!(map.k_nearest(s(1), &candidates, 3), [(s(0), 1), (s(3), 2), (s(5), 4)]);
+        assert_eq!(map.k_nearest(s(1), &candidates, 0), []);
+        // `from` is a candidate zero jumps away
+        assert_eq!(map.k_nearest(s(5), &candidates, 1), [(s(5), 0)]);
+    }
+
+    #[test]
+    fn k_nearest_returns_the_reachable_candidates_when_there_are_fewer_than_k() {
+        let map = sde_map();
+        let candidates = [system(&map, "Isolated"), system(&map, "Amarr")];
+        assert_eq!(map.k_nearest(system(&map, "Jita"), &candidates, 5), [(system(&map, "Amarr"), 2)]);
+    }
+
+    #[test]
+    fn betweenness_centrality_counts_the_routes_through_each_system() {
+        // on S0 - S1 - S2 - S3 - S4, 4 ordered pairs pass through S2 each way, 3 through S1 and S3
+        let ranked = line_map(5).betweenness_centrality();
+        let s = |i| SolarSystemIndex(i);
+        assert_eq!(ranked, [(s(2), 8.0), (s(1), 6.0), (s(3), 6.0), (s(0), 0.0), (s(4), 0.0)]);
+    }
+
+    #[test]
+    fn betweenness_centrality_shares_out_equally_short_routes() {
+        // around the square A - B - C - D - A, opposite corners have a route through each neighbour
+        let corners = [("A", 1.0), ("B", 1.0), ("C", 1.0), ("D", 1.0)];
+        let square = map_from_edges(&corners, &[(0, 1), (1, 2), (2, 3), (3, 0)]);
+        assert!(square.betweenness_centrality().iter().all(|(_, score)| *score == 1.0));
+    }
+
+    #[test]
+    fn annotate_route_with_nearest_pairs_each_system_with_the_closest_of_the_set() {
+        let map = line_map(6);
+        let s = |i| SolarSystemIndex(i);
+        let route = [s(0), s(1), s(2), s(4)];
+        let annotated = map.annotate_route_with_nearest(&route, &[s(5), s(1)]);
+        assert_eq!(
+            annotated,
+            [(s(0), Some((s(1), 1))), (s(1), Some((s(1), 0))), (s(2), Some((s(1), 1))), (s(4), Some((s(5), 1)))]
+        );
+    }
+
+    #[test]
+    fn annotate_route_with_nearest_is_none_where_the_set_cannot_be_reached() {
+        let map = sde_map();
+        let (jita, isolated) = (system(&map, "Jita"), system(&map, "Isolated"));
+        let annotated = map.annotate_route_with_nearest(&[jita, isolated], &[system(&map, "Amarr")]);
+        assert_eq!(annotated, [(jita, Some((system(&map, "Amarr"), 2))), (isolated, None)]);
+        assert!(map.annotate_route_with_nearest(&[jita], &[]).iter().all(|(_, n)| n.is_none()));
+    }
+
+    #[test]
+    fn routes_to_routes_each_origin_to_the_destination() {
+        let map = sde_map();
+        let amarr = system(&map, "Amarr");
+        let origins = [system(&map, "Jita"), amarr, system(&map, "Isolated"), system(&map, "Tama")];
+        let routes = map.routes_to(amarr, &origins);
+        assert_eq!(routes.len(), 4);
+        assert_eq!(names(&map, routes[0].as_ref().unwrap()), ["Jita", "Yulai", "Amarr"]);
+        assert_eq!(routes[1], Some(vec![amarr]));
+        assert_eq!(routes[2], None);
+        let tama = routes[3].as_ref().unwrap();
+        assert_eq!((tama[0], tama[tama.len() - 1]), (origins[3], amarr));
+        assert_eq!(tama.len() as u32 - 1, map.jumps_between(&origins[3], &amarr).unwrap());
+    }
+
+    #[test]
+    fn routes_to_follows_one_way_jumps_forwards() {
+        // A -> B is one way, B - C both ways
+        let mut builder = crate::builder::MapBuilder::new();
+        for (i, name) in ["A", "B", "C"].iter().enumerate() {
+            builder.add_system(30_000_000 + i as u64, name, 2, 1, 1.0);
+        }
+        builder.add_jump(30_000_000, 30_000_001, None).connect(30_000_001, 30_000_002);
+        let map = builder.build().unwrap();
+        let (a, b, c) = (SolarSystemIndex(0), SolarSystemIndex(1), SolarSystemIndex(2));
+        assert_eq!(map.routes_to(c, &[a]), [Some(vec![a, b, c])]);
+        assert_eq!(map.routes_to(a, &[c, b]), [None, None]);
+    }
+
+    #[test]
+    fn max_route_length_is_recomputed_when_the_jumps_change() {
+        let mut map = line_map(5);
+        assert_eq!(map.max_route_length(), 4);
+        // S0 - S1 and S2 - S3 - S4
+        assert!(map.remove_edge(SolarSystemIndex(1), SolarSystemIndex(2)));
+        assert_eq!(map.max_route_length(), 2);
+        assert!(map.restore_edge(SolarSystemIndex(1), SolarSystemIndex(2)));
+        assert_eq!(map.max_route_length(), 4);
+
+        let map = sde_map();
+        assert_eq!(map.max_route_length(), map.diameter());
+    }
+
+    #[test]
+    fn expand_level_marks_the_next_level_and_stops_when_the_searches_meet() {
+        let map = line_map(5);
+        let (mut seen, mut other_seen) = (BitsetVisited::new(5), BitsetVisited::new(5));
+        seen.set(SolarSystemIndex(2));
+        other_seen.set(SolarSystemIndex(4));
+
+        let next = expand_level(&[SolarSystemIndex(2)], |i| map.get_neighbours(i), &mut seen, &other_seen);
+        let mut next = next.unwrap();
+        next.sort();
+        assert_eq!(next, [SolarSystemIndex(1), SolarSystemIndex(3)]);
+        assert!(seen.is_set(SolarSystemIndex(3)));
+
+        let next = expand_level(&[SolarSystemIndex(3)], |i| map.get_predecessors(i), &mut seen, &other_seen);
+        assert_eq!(next, None);
+    }
+}
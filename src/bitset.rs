@@ -0,0 +1,56 @@
+//! BitsetVisited is a visited-or-not tracker for searches that don't need a closed list.
+use crate::evemap::SolarSystemIndex;
+
+/// BitsetVisited keeps one bit per system, packed 64 to a word. BFS passes only need to know
+/// whether a system was seen, and a bit each is an eighth of a Vec<bool>, so much more of it
+/// stays in cache while the search jumps around the map.
+#[derive(Debug, Clone)]
+pub struct BitsetVisited(Vec<u64>);
+
+impl BitsetVisited {
+    /// new is a tracker for `len` systems, with none of them visited
+    pub fn new(len: usize) -> Self {
+        BitsetVisited(vec![0; len.div_ceil(64)])
+    }
+
+    #[inline]
+    pub fn set(&mut self, i: SolarSystemIndex) {
+        let i = usize::from(i);
+        self.0[i / 64] |= 1 << (i % 64);
+    }
+
+    #[inline]
+    pub fn is_set(&self, i: SolarSystemIndex) -> bool {
+        let i = usize::from(i);
+        self.0[i / 64] & (1 << (i % 64)) != 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evemap::RawIndex;
+
+    #[test]
+    fn bits_are_set_independently_across_words() {
+        let mut visited = BitsetVisited::new(130);
+        let indexes = [0, 63, 64, 65, 127, 129].map(|i| SolarSystemIndex(i as RawIndex));
+        for i in indexes {
+            assert!(!visited.is_set(i));
+            visited.set(i);
+            assert!(visited.is_set(i));
+        }
+        let set = (0..130).filter(|i| visited.is_set(SolarSystemIndex(*i as RawIndex))).count();
+        assert_eq!(set, indexes.len());
+
+        visited.set(indexes[0]);
+        assert!(visited.is_set(indexes[0]));
+        assert!(!visited.is_set(SolarSystemIndex(1)));
+    }
+
+    #[test]
+    #[should_panic]
+    fn indexes_beyond_the_systems_it_was_made_for_panic() {
+        BitsetVisited::new(64).is_set(SolarSystemIndex(64));
+    }
+}
@@ -0,0 +1,355 @@
+//! MapBuilder assembles a Map from its parts: regions, constellations, systems and stargates.
+//!
+//! Map::new uses it after reading the SDE, but it's equally happy building a synthetic map for
+//! experiments without having the SDE around.
+use crate::evemap::{
+    Map, Neighbours, SolarSystemEx, SolarSystemId, SolarSystemIndex, SolarSystemMapItem, StargateData,
+    SystemGate,
+};
+use eyre::eyre;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::sync::OnceLock;
+
+/// FIRST_SYNTHETIC_STARGATE_ID is where ids for stargates created by MapBuilder::connect start.
+/// The SDE stargate ids are in the 50,000,000 range, so these shouldn't collide with them.
+const FIRST_SYNTHETIC_STARGATE_ID: u64 = 90_000_000;
+
+/// Jump is a one way jump added with add_jump, rather than from a pair of stargates
+#[derive(Debug)]
+struct Jump {
+    from: SolarSystemId,
+    to: SolarSystemId,
+    stargate_id: Option<u64>,
+    position: Option<[f64; 3]>,
+}
+
+/// WiringReport counts how the stargates read for a map were turned into jumps. A stargate whose
+/// destination gate wasn't read (eg. it's in a region left out of the map, or the SDE is partial)
+/// can't be wired, and its jump is silently missing from routes, so a count of unresolved
+/// destinations well above zero for a whole SDE points at missing data.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WiringReport {
+    pub stargates_seen: usize,
+    pub destinations_resolved: usize,
+    pub destinations_unresolved: usize,
+    /// jumps in the map as built, from stargates and otherwise
+    pub edges_wired: usize,
+}
+
+impl Display for WiringReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} stargates seen, {} destinations resolved, {} unresolved, {} jumps wired",
+            self.stargates_seen, self.destinations_resolved, self.destinations_unresolved, self.edges_wired
+        )
+    }
+}
+
+#[derive(Debug)]
+pub struct MapBuilder {
+    names: HashMap<String, Vec<u64>>,
+    systems: Vec<SolarSystemEx>,
+    stargates: Vec<StargateData>,
+    /// jumps between systems that aren't described by a pair of stargates
+    jumps: Vec<Jump>,
+    next_stargate_id: u64,
+    sde_version: Option<String>,
+}
+
+impl Default for MapBuilder {
+    fn default() -> Self {
+        MapBuilder {
+            names: HashMap::new(),
+            systems: Vec::new(),
+            stargates: Vec::new(),
+            jumps: Vec::new(),
+            next_stargate_id: FIRST_SYNTHETIC_STARGATE_ID,
+            sde_version: None,
+        }
+    }
+}
+
+impl MapBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// sde_version records which build of the SDE the map comes from
+    pub fn sde_version(&mut self, version: &str) -> &mut Self {
+        self.sde_version = Some(version.to_string());
+        self
+    }
+
+    /// add_region registers a region, so that its name can be looked up in the Map
+    pub fn add_region(&mut self, id: u64, name: &str) -> &mut Self {
+        self.names.entry(name.to_string()).or_default().push(id);
+        self
+    }
+
+    /// add_constellation registers a constellation, so that its name can be looked up in the Map
+    pub fn add_constellation(&mut self, id: u64, name: &str) -> &mut Self {
+        self.names.entry(name.to_string()).or_default().push(id);
+        self
+    }
+
+    /// add_system adds a solar system with its true security status
+    pub fn add_system(
+        &mut self,
+        id: u64,
+        name: &str,
+        constellation_id: u64,
+        region_id: u64,
+        security: f64,
+    ) -> &mut Self {
+        self.add_system_at(id, name, constellation_id, region_id, security, None)
+    }
+
+    /// add_system_at is add_system for a system at a known position in the universe (meters)
+    pub fn add_system_at(
+        &mut self,
+        id: u64,
+        name: &str,
+        constellation_id: u64,
+        region_id: u64,
+        security: f64,
+        position: Option<[f64; 3]>,
+    ) -> &mut Self {
+        self.names.entry(name.to_string()).or_default().push(id);
+        self.systems.push(SolarSystemEx {
+            name: name.to_string(),
+            solar_system_id: SolarSystemId(id),
+            constellation_id,
+            region_id,
+            security,
+            gates: Vec::new(),
+            position,
+        });
+        self
+    }
+
+    /// add_stargate adds a single stargate. Its destination gate should also be added before build,
+    /// or build leaves it out of the map (see WiringReport).
+    pub fn add_stargate(&mut self, stargate: StargateData) -> &mut Self {
+        self.stargates.push(stargate);
+        self
+    }
+
+    /// connect adds a pair of stargates linking two systems (by SolarSystemId value), creating
+    /// ids for the stargates.
+    pub fn connect(&mut self, a: u64, b: u64) -> &mut Self {
+        let (gate_a, gate_b) = (self.next_stargate_id, self.next_stargate_id + 1);
+        self.next_stargate_id += 2;
+
+        self.add_stargate(StargateData {
+            stargate_id: gate_a,
+            solar_system_id: SolarSystemId(a),
+            destination_stargate_id: gate_b,
+            position: None,
+        });
+        self.add_stargate(StargateData {
+            stargate_id: gate_b,
+            solar_system_id: SolarSystemId(b),
+            destination_stargate_id: gate_a,
+            position: None,
+        })
+    }
+
+    /// add_jump adds a one way jump from one system to another (by SolarSystemId value), without
+    /// needing stargates at either end. `position` is where the jump is made from within `from`.
+    pub fn add_jump(&mut self, from: u64, to: u64, position: Option<[f64; 3]>) -> &mut Self {
+        self.add_jump_through(from, to, None, position)
+    }
+
+    /// add_jump_through is add_jump, recording the stargate the jump is made through without
+    /// needing the stargate at the other end
+    pub fn add_jump_through(
+        &mut self,
+        from: u64,
+        to: u64,
+        stargate_id: Option<u64>,
+        position: Option<[f64; 3]>,
+    ) -> &mut Self {
+        self.jumps.push(Jump {
+            from: SolarSystemId(from),
+            to: SolarSystemId(to),
+            stargate_id,
+            position,
+        });
+        self
+    }
+
+    /// build creates the Map: it assigns a SolarSystemIndex to each system (in SolarSystemId order)
+    /// and resolves each stargate's destination gate to the system it's in, to get the neighbours
+    pub fn build(self) -> eyre::Result<Map> {
+        let MapBuilder {
+            names,
+            mut systems,
+            stargates,
+            jumps,
+            sde_version,
+            ..
+        } = self;
+
+        // since solarsystem ID is unique, don't need any stable sorting
+        systems.sort_unstable_by_key(|x| x.solar_system_id);
+
+        // build a lookup of the offset of a SolarSystemID
+        let system_id_to_index: HashMap<SolarSystemId, SolarSystemIndex> = systems
+            .iter()
+            .enumerate()
+            .map(|(i, ss)| Ok((ss.solar_system_id, SolarSystemIndex(i.try_into()?))))
+            .collect::<eyre::Result<_>>()?;
+
+        if system_id_to_index.len() != systems.len() {
+            return Err(eyre!("duplicate solar system ids added to the map"));
+        }
+
+        let stargate_to_system: HashMap<u64, SolarSystemIndex> = stargates
+            .iter()
+            .map(|g| {
+                let ss_idx = system_id_to_index.get(&g.solar_system_id).ok_or_else(|| {
+                    eyre!("stargate {} is in unknown system {}", g.stargate_id, g.solar_system_id)
+                })?;
+                Ok((g.stargate_id, *ss_idx))
+            })
+            .collect::<eyre::Result<_>>()?;
+
+        // a stargate whose destination gate wasn't added (eg. it's in a region left out of the map)
+        // has nowhere to go, so it's left out and counted in the WiringReport
+        let mut wiring = WiringReport {
+            stargates_seen: stargates.len(),
+            ..Default::default()
+        };
+        let stargates: Vec<StargateData> = stargates
+            .into_iter()
+            .filter(|g| {
+                let Some(destination) = stargate_to_system.get(&g.destination_stargate_id) else {
+                    wiring.destinations_unresolved += 1;
+                    return false;
+                };
+                systems[usize::from(stargate_to_system[&g.stargate_id])]
+                    .gates
+                    .push(SystemGate {
+                        destination: *destination,
+                        stargate_id: Some(g.stargate_id),
+                        position: g.position,
+                    });
+                true
+            })
+            .collect();
+        wiring.destinations_resolved = stargates.len();
+
+        for Jump { from, to, stargate_id, position } in jumps {
+            let from_idx = system_id_to_index.get(&from).ok_or_else(|| eyre!("jump from unknown system {from}"))?;
+            let destination = *system_id_to_index.get(&to).ok_or_else(|| eyre!("jump to unknown system {to}"))?;
+
+            systems[usize::from(*from_idx)].gates.push(SystemGate {
+                destination,
+                stargate_id,
+                position,
+            });
+        }
+
+        wiring.edges_wired = systems.iter().map(|ss| ss.gates.len()).sum();
+
+        let map_items: Vec<SolarSystemMapItem> = systems
+            .iter()
+            .map(|ss| SolarSystemMapItem {
+                solar_system_id: ss.solar_system_id,
+                neighbours: ss.gates.iter().map(|g| g.destination).collect::<Neighbours>(),
+            })
+            .collect();
+
+        // Stargates always come in pairs, so this mirrors the neighbours, but other edges need not
+        let mut predecessors = vec![Vec::new(); map_items.len()];
+        for (i, ss) in map_items.iter().enumerate() {
+            let ss_idx = SolarSystemIndex(i.try_into()?);
+            for n in ss.get_neighbours() {
+                predecessors[usize::from(n)].push(ss_idx);
+            }
+        }
+
+        let mut map = Map {
+            systems: map_items,
+            extended_systems: systems,
+            name_to_id: names,
+            system_id_to_index,
+            predecessors,
+            sde_version,
+            removed_edges: Default::default(),
+            components: Vec::new(),
+            stargates: stargates.into_iter().map(|g| (g.stargate_id, g)).collect(),
+            wiring,
+            max_route_length: OnceLock::new(),
+        };
+        map.components = map.component_labels();
+        Ok(map)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn two_systems() -> MapBuilder {
+        let mut builder = MapBuilder::new();
+        builder.add_region(1, "Region").add_constellation(2, "Constellation");
+        builder.add_system(30_000_002, "B", 2, 1, 0.4).add_system(30_000_001, "A", 2, 1, 1.0);
+        builder
+    }
+
+    #[test]
+    fn build_indexes_systems_in_id_order() {
+        let map = two_systems().build().unwrap();
+        assert_eq!(map.find_solarsystem("A").unwrap(), SolarSystemIndex(0));
+        assert_eq!(map.find_solarsystem("B").unwrap(), SolarSystemIndex(1));
+        assert!(map.find_solarsystem("Region").is_err());
+    }
+
+    #[test]
+    fn connect_adds_a_jump_both_ways() {
+        let mut builder = two_systems();
+        builder.connect(30_000_001, 30_000_002);
+        let map = builder.build().unwrap();
+        let (a, b) = (SolarSystemIndex(0), SolarSystemIndex(1));
+        assert_eq!(map.get_neighbours(&a).collect::<Vec<_>>(), [b]);
+        assert_eq!(map.get_neighbours(&b).collect::<Vec<_>>(), [a]);
+    }
+
+    #[test]
+    fn build_rejects_duplicate_system_ids() {
+        let mut builder = two_systems();
+        builder.add_system(30_000_001, "A again", 2, 1, 1.0);
+        assert!(builder.build().is_err());
+    }
+
+    #[test]
+    fn build_leaves_out_and_counts_a_stargate_to_a_missing_gate() {
+        let mut builder = two_systems();
+        builder.connect(30_000_001, 30_000_002);
+        // a gate out of A whose destination gate (in a system that was never added) is missing
+        builder.add_stargate(StargateData {
+            stargate_id: 50_000_001,
+            solar_system_id: SolarSystemId(30_000_001),
+            destination_stargate_id: 50_000_002,
+            position: None,
+        });
+        let map = builder.build().unwrap();
+
+        let report = map.wiring_report();
+        assert_eq!(
+            report,
+            WiringReport {
+                stargates_seen: 3,
+                destinations_resolved: 2,
+                destinations_unresolved: 1,
+                edges_wired: 2,
+            }
+        );
+        assert_eq!(report.to_string(), "3 stargates seen, 2 destinations resolved, 1 unresolved, 2 jumps wired");
+        assert_eq!(map.get_neighbours(&SolarSystemIndex(0)).count(), 1);
+        assert!(map.stargate(50_000_001).is_none());
+    }
+}
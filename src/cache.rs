@@ -0,0 +1,200 @@
+//! CachedRouter remembers the routes it has found, for services that answer the same queries
+//! over and over.
+use crate::astar::AStarError;
+use crate::cost::{BorderCostModel, ConnectivityCostModel, TimeCostModel};
+use crate::evemap::{Map, SolarSystemIndex};
+use ordered_float::OrderedFloat;
+use std::collections::HashMap;
+
+/// Mode is the kind of route to find, and so part of the cache key
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Mode {
+    /// fewest jumps
+    Shortest,
+    /// fewest jumps, with a penalty (in jumps) for crossing security class borders (BorderCostModel)
+    PreferSafe { penalty: u32 },
+    /// fewest jumps, favouring well connected systems (ConnectivityCostModel)
+    PreferConnected,
+    /// the route whose least secure system is as secure as possible
+    SafestBottleneck,
+    /// least estimated travel time (TimeCostModel) for a ship with this align time in seconds
+    OptimizeTime { align_time: OrderedFloat<f32> },
+}
+
+type RouteResult = Result<Vec<SolarSystemIndex>, AStarError>;
+
+/// CachedRouter owns a Map and keeps up to `capacity` of the most recently used routes (including
+/// failures to find one). When it's full, the least recently used route is forgotten.
+///
+/// Cached routes are only right for the map they were found on, so the cache is cleared whenever
+/// the map is borrowed mutably through map_mut, eg. to remove_edge.
+pub struct CachedRouter {
+    map: Map,
+    cache: HashMap<(SolarSystemIndex, SolarSystemIndex, Mode), (RouteResult, u64)>,
+    capacity: usize,
+    /// incremented on every lookup, to find the least recently used route
+    tick: u64,
+    hits: u64,
+    misses: u64,
+}
+
+impl CachedRouter {
+    pub fn new(map: Map, capacity: usize) -> Self {
+        CachedRouter {
+            map,
+            cache: HashMap::with_capacity(capacity),
+            capacity,
+            tick: 0,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    pub fn map(&self) -> &Map {
+        &self.map
+    }
+
+    /// map_mut gives access to change the map, which clears the cache
+    pub fn map_mut(&mut self) -> &mut Map {
+        self.clear();
+        &mut self.map
+    }
+
+    pub fn into_map(self) -> Map {
+        self.map
+    }
+
+    /// clear forgets all the cached routes. The hit and miss counts are kept.
+    pub fn clear(&mut self) {
+        self.cache.clear();
+    }
+
+    /// hits is the number of routes that were answered from the cache
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    /// misses is the number of routes that had to be searched for
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+
+    pub fn len(&self) -> usize {
+        self.cache.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cache.is_empty()
+    }
+
+    /// route finds the route from `from` to `to` in `mode`, or returns a copy of the one found before
+    pub fn route(&mut self, from: SolarSystemIndex, to: SolarSystemIndex, mode: Mode) -> RouteResult {
+        self.tick += 1;
+        let key = (from, to, mode);
+
+        if let Some((result, last_used)) = self.cache.get_mut(&key) {
+            *last_used = self.tick;
+            self.hits += 1;
+            return result.clone();
+        }
+
+        self.misses += 1;
+        let result = find(&self.map, from, to, mode);
+        if self.capacity > 0 {
+            if self.cache.len() >= self.capacity {
+                // a linear scan, but the capacity of a route cache is small next to a search
+                if let Some(oldest) = self.cache.iter().min_by_key(|(_, (_, t))| *t).map(|(k, _)| *k) {
+                    self.cache.remove(&oldest);
+                }
+            }
+            self.cache.insert(key, (result.clone(), self.tick));
+        }
+        result
+    }
+}
+
+fn find(map: &Map, from: SolarSystemIndex, to: SolarSystemIndex, mode: Mode) -> RouteResult {
+    match mode {
+        Mode::Shortest => map.shortest_route(from, to),
+        Mode::PreferSafe { penalty } => map
+            .route_with_model(from, to, &BorderCostModel::with_penalty(penalty), None)
+            .map(|(path, _)| path),
+        Mode::PreferConnected => map
+            .route_with_model(from, to, &ConnectivityCostModel::default(), None)
+            .map(|(path, _)| path),
+        Mode::SafestBottleneck => map.safest_bottleneck_route(from, to).ok_or(AStarError::PathNotFound),
+        Mode::OptimizeTime { align_time } => map
+            .route_with_model(from, to, &TimeCostModel::new(align_time.into_inner()), None)
+            .map(|(path, _)| path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{names, sde_map, system};
+
+    #[test]
+    fn route_is_searched_for_once_per_query_and_mode() {
+        let map = sde_map();
+        let (jita, amarr) = (system(&map, "Jita"), system(&map, "Amarr"));
+        let expected = map.shortest_route(jita, amarr);
+        let mut router = CachedRouter::new(map, 8);
+
+        assert_eq!(router.route(jita, amarr, Mode::Shortest), expected);
+        assert_eq!(router.route(jita, amarr, Mode::Shortest), expected);
+        assert_eq!((router.hits(), router.misses()), (1, 1));
+
+        router.route(jita, amarr, Mode::PreferConnected).unwrap();
+        router.route(amarr, jita, Mode::Shortest).unwrap();
+        assert_eq!((router.hits(), router.misses(), router.len()), (1, 3, 3));
+    }
+
+    #[test]
+    fn failures_are_cached_too() {
+        let map = sde_map();
+        let (jita, isolated) = (system(&map, "Jita"), system(&map, "Isolated"));
+        let mut router = CachedRouter::new(map, 8);
+        for _ in 0..2 {
+            assert_eq!(router.route(jita, isolated, Mode::SafestBottleneck), Err(AStarError::PathNotFound));
+        }
+        assert_eq!((router.hits(), router.misses()), (1, 1));
+    }
+
+    #[test]
+    fn the_least_recently_used_route_is_forgotten_when_full() {
+        let map = sde_map();
+        let (jita, amarr, tama) = (system(&map, "Jita"), system(&map, "Amarr"), system(&map, "Tama"));
+        let mut router = CachedRouter::new(map, 2);
+
+        router.route(jita, amarr, Mode::Shortest).unwrap();
+        router.route(jita, tama, Mode::Shortest).unwrap();
+        router.route(jita, amarr, Mode::Shortest).unwrap();
+        // Jita to Tama was used least recently, so it makes way
+        router.route(amarr, tama, Mode::Shortest).unwrap();
+        assert_eq!(router.len(), 2);
+
+        router.route(jita, amarr, Mode::Shortest).unwrap();
+        assert_eq!(router.hits(), 2);
+        router.route(jita, tama, Mode::Shortest).unwrap();
+        assert_eq!(router.hits(), 2);
+
+        let mut uncached = CachedRouter::new(router.into_map(), 0);
+        uncached.route(jita, amarr, Mode::Shortest).unwrap();
+        assert!(uncached.is_empty());
+    }
+
+    #[test]
+    fn changing_the_map_clears_the_cache() {
+        let map = sde_map();
+        let (jita, yulai, amarr) = (system(&map, "Jita"), system(&map, "Yulai"), system(&map, "Amarr"));
+        let mut router = CachedRouter::new(map, 8);
+        assert_eq!(router.route(jita, amarr, Mode::Shortest).unwrap().len(), 3);
+
+        router.map_mut().remove_edge(jita, yulai);
+        assert!(router.is_empty());
+        let route = router.route(jita, amarr, Mode::Shortest).unwrap();
+        assert_eq!(names(router.map(), &route), ["Jita", "Perimeter", "Madirmilire", "Niarja", "Sarum Prime", "Amarr"]);
+        assert_eq!(router.misses(), 2);
+    }
+}
@@ -0,0 +1,912 @@
+//! Cost models turn a jump from one system to a neighbour into a Cost that astar can minimise.
+//! Counting jumps needs nothing more than a constant, but other costs need to look at the map.
+use crate::astar::CheckedCost;
+use crate::evemap::{Map, SolarSystemIndex};
+use crate::security::SecurityClass;
+use eyre::eyre;
+use ordered_float::NotNan;
+use std::collections::HashMap;
+use std::ops::Add;
+
+/// AU is an astronomical unit in meters. Warp speeds are given in AU/s, while positions are in meters.
+const AU: f64 = 149_597_870_700.0;
+
+/// CostModel is the cost of each jump for one way of choosing routes, computed from the map as
+/// the search reaches the jump. The router takes any CostModel, including a `&dyn CostModel`, in
+/// place of an edge cost closure (see Map::route_with_model).
+pub trait CostModel {
+    type Cost;
+
+    /// edge_cost is the cost of a single jump from `from` to its neighbour `to`
+    fn edge_cost(&self, map: &Map, from: &SolarSystemIndex, to: &SolarSystemIndex) -> Self::Cost;
+}
+
+/// JumpsCostModel counts jumps, so the cheapest route is the shortest
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JumpsCostModel;
+
+impl CostModel for JumpsCostModel {
+    type Cost = u32;
+
+    fn edge_cost(&self, _map: &Map, _from: &SolarSystemIndex, _to: &SolarSystemIndex) -> u32 {
+        1
+    }
+}
+
+/// TimeCostModel estimates the seconds taken by each jump as the time to align, warp to the
+/// gate and then activate it.
+///
+/// We don't know which gate a ship arrived at, so the warp is measured from the system's star
+/// (the origin of in-system positions) to the outgoing gate. Warp acceleration is ignored.
+/// If the SDE didn't provide a position for the gate, `fallback_warp_time` is used instead.
+#[derive(Debug, Clone)]
+pub struct TimeCostModel {
+    /// seconds for the ship to align before it can enter warp
+    pub align_time: f32,
+    /// warp speed in AU/s
+    pub warp_speed: f32,
+    /// seconds taken to activate the gate and get through the session change on the other side
+    pub gate_time: f32,
+    /// seconds of warp assumed when the gate position is unavailable
+    pub fallback_warp_time: f32,
+}
+
+impl Default for TimeCostModel {
+    fn default() -> Self {
+        TimeCostModel {
+            align_time: 5.0,
+            warp_speed: 3.0,
+            gate_time: 10.0,
+            fallback_warp_time: 20.0,
+        }
+    }
+}
+
+impl TimeCostModel {
+    pub fn new(align_time: f32) -> Self {
+        TimeCostModel {
+            align_time,
+            ..Default::default()
+        }
+    }
+}
+
+impl CostModel for TimeCostModel {
+    type Cost = NotNan<f32>;
+
+    /// edge_cost is the estimated seconds for a single jump from `from` to its neighbour `to`
+    fn edge_cost(&self, map: &Map, from: &SolarSystemIndex, to: &SolarSystemIndex) -> NotNan<f32> {
+        let warp_time = map
+            .get_extended_solarsystem_info(from)
+            .gates
+            .iter()
+            .find(|g| g.destination == *to)
+            .and_then(|g| g.position)
+            .map(|[x, y, z]| ((x * x + y * y + z * z).sqrt() / (self.warp_speed as f64 * AU)) as f32)
+            .unwrap_or(self.fallback_warp_time);
+
+        NotNan::new(self.align_time + warp_time + self.gate_time)
+            .expect("time cost model configured with a NaN")
+    }
+}
+
+/// GateToGateTimeModel is TimeCostModel, but measures the warp across each system from the gate the
+/// ship arrived at to the gate it leaves by. That's a better estimate, and it makes the cost of a
+/// jump depend on the direction the route takes through the system, so the fastest route from A to
+/// B needn't be the fastest from B to A reversed.
+///
+/// The cost of a jump depends on the jump before it, so this isn't a CostModel: route with
+/// Map::route_gate_to_gate, which searches on (system, previous system) pairs. In the starting
+/// system the ship is taken to be at the star, like TimeCostModel, and fallback_warp_time is
+/// used where either gate has no position.
+#[derive(Debug, Clone, Default)]
+pub struct GateToGateTimeModel {
+    pub time: TimeCostModel,
+}
+
+impl GateToGateTimeModel {
+    pub fn new(align_time: f32) -> Self {
+        GateToGateTimeModel {
+            time: TimeCostModel::new(align_time),
+        }
+    }
+
+    /// edge_cost is the estimated seconds for a jump from `from` to its neighbour `to`, having
+    /// arrived in `from` from `previous`, or started there if that's None
+    pub fn edge_cost(
+        &self,
+        map: &Map,
+        previous: Option<&SolarSystemIndex>,
+        from: &SolarSystemIndex,
+        to: &SolarSystemIndex,
+    ) -> NotNan<f32> {
+        let gates = &map.get_extended_solarsystem_info(from).gates;
+        let gate_to = |system: &SolarSystemIndex| {
+            gates.iter().find(|g| g.destination == *system).and_then(|g| g.position)
+        };
+        let entry = match previous {
+            Some(previous) => gate_to(previous),
+            None => Some([0.0; 3]),
+        };
+
+        let warp_time = entry
+            .zip(gate_to(to))
+            .map(|(a, b)| {
+                let [dx, dy, dz] = [a[0] - b[0], a[1] - b[1], a[2] - b[2]];
+                ((dx * dx + dy * dy + dz * dz).sqrt() / (self.time.warp_speed as f64 * AU)) as f32
+            })
+            .unwrap_or(self.time.fallback_warp_time);
+
+        NotNan::new(self.time.align_time + warp_time + self.time.gate_time)
+            .expect("time cost model configured with a NaN")
+    }
+}
+
+/// BorderCostModel counts jumps, but adds a penalty to jumps that cross from one security class
+/// to another (eg. high-sec into low-sec). The border systems are where the risk concentrates for
+/// haulers, so this favours routes that stay within one class, even if they are a little longer.
+///
+/// Penalties apply in both directions and are in jump-equivalents.
+#[derive(Debug, Clone)]
+pub struct BorderCostModel {
+    /// penalty for a jump between high-sec and low-sec
+    pub high_low: u32,
+    /// penalty for a jump between low-sec and null-sec
+    pub low_null: u32,
+    /// penalty for a jump directly between high-sec and null-sec
+    pub high_null: u32,
+}
+
+impl Default for BorderCostModel {
+    fn default() -> Self {
+        BorderCostModel {
+            high_low: 2,
+            low_null: 2,
+            high_null: 4,
+        }
+    }
+}
+
+impl BorderCostModel {
+    /// with_penalty penalises every border by `penalty` jumps, and high-sec straight into
+    /// null-sec (two classes apart) by twice that
+    pub fn with_penalty(penalty: u32) -> Self {
+        BorderCostModel {
+            high_low: penalty,
+            low_null: penalty,
+            high_null: penalty.saturating_mul(2),
+        }
+    }
+
+    /// with_derived_penalty is with_penalty(map.max_route_length() + 1): crossing a border costs
+    /// more than any shortest route in `map`, so a route stays in its security class whenever a
+    /// detour no longer than that keeps it there
+    pub fn with_derived_penalty(map: &Map) -> Self {
+        Self::with_penalty(map.max_route_length().saturating_add(1))
+    }
+}
+
+impl CostModel for BorderCostModel {
+    type Cost = u32;
+
+    /// edge_cost is one jump, plus the penalty for the security class border it crosses (if any)
+    fn edge_cost(&self, map: &Map, from: &SolarSystemIndex, to: &SolarSystemIndex) -> u32 {
+        use SecurityClass::*;
+
+        let from_class = map.get_extended_solarsystem_info(from).security_class();
+        let to_class = map.get_extended_solarsystem_info(to).security_class();
+
+        1u32.saturating_add(match (from_class, to_class) {
+            (High, Low) | (Low, High) => self.high_low,
+            (Low, Null) | (Null, Low) => self.low_null,
+            (High, Null) | (Null, High) => self.high_null,
+            _ => 0,
+        })
+    }
+}
+
+/// ConstellationCostModel counts jumps, adding `penalty` for each jump from one constellation into
+/// another. Logistics that work constellation by constellation prefer routes that cross fewer of
+/// their borders, even when that's a jump or so longer. Region borders are constellation borders
+/// too, so they're penalised the same.
+#[derive(Debug, Clone)]
+pub struct ConstellationCostModel {
+    /// penalty for a jump between constellations, in jump-equivalents
+    pub penalty: u32,
+}
+
+impl Default for ConstellationCostModel {
+    fn default() -> Self {
+        ConstellationCostModel { penalty: 1 }
+    }
+}
+
+impl CostModel for ConstellationCostModel {
+    type Cost = u32;
+
+    /// edge_cost is one jump, plus the penalty if it leaves the constellation
+    fn edge_cost(&self, map: &Map, from: &SolarSystemIndex, to: &SolarSystemIndex) -> u32 {
+        let from_constellation = map.get_extended_solarsystem_info(from).constellation_id;
+        let to_constellation = map.get_extended_solarsystem_info(to).constellation_id;
+
+        if from_constellation == to_constellation {
+            1
+        } else {
+            1u32.saturating_add(self.penalty)
+        }
+    }
+}
+
+/// RegionPreferenceCostModel keeps routes in friendly regions where it can. Regions are listed in
+/// order of preference: jumping into the first costs a jump, into each next one a jump more than
+/// the one before, and into any region not on the list `outside_penalty` jumps more than that.
+///
+/// It's a soft constraint: a route still leaves the preferred regions when going around them
+/// would cost more than the penalties.
+#[derive(Debug, Clone)]
+pub struct RegionPreferenceCostModel {
+    /// region ids, most preferred first
+    pub preferred: Vec<u64>,
+    /// penalty for jumping into a region that isn't preferred, in jump-equivalents
+    pub outside_penalty: u32,
+}
+
+impl RegionPreferenceCostModel {
+    /// new prefers the regions in the order given, with anywhere else a step down from the last
+    pub fn new(preferred: &[u64]) -> Self {
+        RegionPreferenceCostModel {
+            preferred: preferred.to_vec(),
+            outside_penalty: preferred.len() as u32,
+        }
+    }
+}
+
+impl CostModel for RegionPreferenceCostModel {
+    type Cost = u32;
+
+    /// edge_cost is one jump, plus the penalty for the region of `to`
+    fn edge_cost(&self, map: &Map, _from: &SolarSystemIndex, to: &SolarSystemIndex) -> u32 {
+        let region_id = map.get_extended_solarsystem_info(to).region_id;
+        let penalty = match self.preferred.iter().position(|r| *r == region_id) {
+            Some(rank) => rank as u32,
+            None => self.outside_penalty,
+        };
+        1u32.saturating_add(penalty)
+    }
+}
+
+/// EdgeWeightCostModel costs each jump by a table of weights kept outside the SDE, eg. from intel
+/// about where the gate camps are. A jump with no weight in the table costs `default_weight`.
+/// Weights are per direction: a weight on a jump from A to B doesn't apply from B to A.
+#[derive(Debug, Clone)]
+pub struct EdgeWeightCostModel {
+    pub weights: HashMap<(SolarSystemIndex, SolarSystemIndex), NotNan<f32>>,
+    pub default_weight: NotNan<f32>,
+}
+
+impl EdgeWeightCostModel {
+    pub fn new(weights: HashMap<(SolarSystemIndex, SolarSystemIndex), NotNan<f32>>) -> Self {
+        EdgeWeightCostModel {
+            weights,
+            default_weight: NotNan::new(1.0).expect("1 is a number"),
+        }
+    }
+
+    /// from_csv reads weights as lines of `from_name,to_name,weight`, eg. `Jita,Perimeter,10`.
+    /// Blank lines are skipped, and a later weight for the same jump replaces an earlier one. Each
+    /// pair of names has to be a jump in the map, and weights can't be negative, which would
+    /// break the search.
+    pub fn from_csv(map: &Map, csv: &str) -> eyre::Result<Self> {
+        let mut weights = HashMap::new();
+        for (n, line) in csv.lines().enumerate().filter(|(_, l)| !l.trim().is_empty()) {
+            let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+            let [from_name, to_name, weight] = fields[..] else {
+                return Err(eyre!("line {}: expected from,to,weight but found \"{line}\"", n + 1));
+            };
+
+            let (from, to) = (map.find_solarsystem(from_name)?, map.find_solarsystem(to_name)?);
+            if !map.get_neighbours(&from).any(|i| i == to) {
+                return Err(eyre!("line {}: there's no jump from {from_name} to {to_name}", n + 1));
+            }
+            let weight = weight
+                .parse::<f32>()
+                .ok()
+                .and_then(|w| NotNan::new(w).ok())
+                .filter(|w| w.into_inner() >= 0.0)
+                .ok_or_else(|| eyre!("line {}: weight \"{weight}\" isn't a number of at least 0", n + 1))?;
+            weights.insert((from, to), weight);
+        }
+        Ok(EdgeWeightCostModel::new(weights))
+    }
+}
+
+impl CostModel for EdgeWeightCostModel {
+    type Cost = NotNan<f32>;
+
+    /// edge_cost is the weight of the jump in the table, or default_weight if it isn't there
+    fn edge_cost(&self, _map: &Map, from: &SolarSystemIndex, to: &SolarSystemIndex) -> NotNan<f32> {
+        self.weights.get(&(*from, *to)).copied().unwrap_or(self.default_weight)
+    }
+}
+
+/// ConnectivityCostModel prefers routes through well connected systems, which have more ways out
+/// if a gate is camped. Each jump costs `jump_cost`, less a discount for each stargate out of the
+/// system jumped into, up to `max_neighbours` of them.
+///
+/// The discounts are small next to a jump, so they mostly pick between routes of the same length,
+/// but enough of them can outweigh an extra jump. Since a jump can cost less than `jump_cost`, a
+/// heuristic counting jumps has to use min_edge_cost per jump to stay admissible; astar tolerates
+/// one that isn't, but the route may not be the cheapest.
+#[derive(Debug, Clone)]
+pub struct ConnectivityCostModel {
+    pub jump_cost: u32,
+    /// discount per stargate out of the destination system
+    pub discount_per_neighbour: u32,
+    /// stargates beyond this many don't add to the discount
+    pub max_neighbours: u32,
+}
+
+impl Default for ConnectivityCostModel {
+    fn default() -> Self {
+        ConnectivityCostModel {
+            jump_cost: 100,
+            discount_per_neighbour: 2,
+            max_neighbours: 5,
+        }
+    }
+}
+
+impl CostModel for ConnectivityCostModel {
+    type Cost = u32;
+
+    /// edge_cost is a jump, less the discount for how well connected `to` is
+    fn edge_cost(&self, map: &Map, _from: &SolarSystemIndex, to: &SolarSystemIndex) -> u32 {
+        let neighbours = map.get_system(to).neighbours.len() as u32;
+        self.jump_cost
+            .saturating_sub(neighbours.min(self.max_neighbours).saturating_mul(self.discount_per_neighbour))
+    }
+}
+
+impl ConnectivityCostModel {
+    /// min_edge_cost is the least a single jump can cost, into a system with max_neighbours or more
+    pub fn min_edge_cost(&self) -> u32 {
+        self.jump_cost
+            .saturating_sub(self.max_neighbours.saturating_mul(self.discount_per_neighbour))
+    }
+}
+
+/// DangerCostModel avoids systems known to be dangerous, eg. from recent kills. The map has no
+/// idea what's dangerous, so the caller gives each system a danger score, which is added to the
+/// cost of jumping into it. Systems without a score cost just the jump.
+#[derive(Debug, Clone)]
+pub struct DangerCostModel {
+    pub jump_cost: u32,
+    pub danger: HashMap<SolarSystemIndex, u32>,
+}
+
+impl DangerCostModel {
+    /// new scores each jump as 1, plus the danger of the system jumped into
+    pub fn new(danger: HashMap<SolarSystemIndex, u32>) -> Self {
+        DangerCostModel { jump_cost: 1, danger }
+    }
+}
+
+impl CostModel for DangerCostModel {
+    type Cost = u32;
+
+    fn edge_cost(&self, _map: &Map, _from: &SolarSystemIndex, to: &SolarSystemIndex) -> u32 {
+        self.jump_cost
+            .saturating_add(self.danger.get(to).copied().unwrap_or(0))
+    }
+}
+
+/// ExposureCostModel minimises the total time spent at risk: for each system passed through, its
+/// danger times the seconds spent crossing it, summed over the route. Unlike DangerCostModel, a
+/// dangerous system costs more the longer it takes to get through, so a quick hop through a
+/// dangerous system can beat a long crossing of a merely risky one.
+///
+/// A jump from `from` to `to` costs the time to cross `from` to the gate, as TimeCostModel
+/// estimates it, times the danger of `from`. So the starting system counts, but the destination,
+/// where the route ends on arrival, doesn't. Every system's danger is `base_danger` plus its
+/// score, so that time in systems with no score still counts for something and routes don't
+/// wander.
+#[derive(Debug, Clone)]
+pub struct ExposureCostModel {
+    /// estimates the seconds spent in each system
+    pub time: TimeCostModel,
+    /// danger of every system, on top of its score
+    pub base_danger: f32,
+    pub danger: HashMap<SolarSystemIndex, f32>,
+}
+
+impl ExposureCostModel {
+    /// new weighs each second in a system by 1, plus the system's danger
+    pub fn new(danger: HashMap<SolarSystemIndex, f32>) -> Self {
+        ExposureCostModel {
+            time: TimeCostModel::default(),
+            base_danger: 1.0,
+            danger,
+        }
+    }
+}
+
+impl CostModel for ExposureCostModel {
+    type Cost = NotNan<f32>;
+
+    /// edge_cost is the seconds spent crossing `from` to the gate into `to`, weighted by the
+    /// danger of `from`
+    fn edge_cost(&self, map: &Map, from: &SolarSystemIndex, to: &SolarSystemIndex) -> NotNan<f32> {
+        let danger = NotNan::new(self.base_danger + self.danger.get(from).copied().unwrap_or(0.0))
+            .expect("exposure cost model configured with a NaN");
+        danger * self.time.edge_cost(map, from, to)
+    }
+}
+
+/// SumCostModel stacks two cost models with the same Cost, costing each jump as the sum of both,
+/// eg. `SumCostModel(BorderCostModel::default(), DangerCostModel::new(kills))`. Both models
+/// usually count the jump itself, so subtract a jump's worth from one of them (eg. a
+/// DangerCostModel with a jump_cost of 0) unless jumps should weigh double. Sums can be nested
+/// to stack more than two.
+#[derive(Debug, Clone)]
+pub struct SumCostModel<A, B>(pub A, pub B);
+
+impl<A, B> CostModel for SumCostModel<A, B>
+where
+    A: CostModel,
+    B: CostModel<Cost = A::Cost>,
+    A::Cost: CheckedCost,
+{
+    type Cost = A::Cost;
+
+    /// edge_cost is the sum of both models' costs, saturating rather than wrapping around
+    fn edge_cost(&self, map: &Map, from: &SolarSystemIndex, to: &SolarSystemIndex) -> A::Cost {
+        self.0.edge_cost(map, from, to).saturating_cost_add(self.1.edge_cost(map, from, to))
+    }
+}
+
+/// Lexicographic is a Cost in two parts, compared in order: the first decides, and the second only
+/// breaks ties. Costing jumps as (1, danger), eg. with a LexicographicCostModel of JumpsCostModel
+/// and a DangerCostModel with a jump_cost of 0, finds the shortest route and, of those, the
+/// safest. Unlike SumCostModel, there's no exchange rate between the parts to tune.
+///
+/// Adding is component by component, so each part adds up along the route by itself. A heuristic
+/// has to be a lower bound on both parts; Lexicographic(h, 0) with h a bound on the first is.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Lexicographic<A, B>(pub A, pub B);
+
+impl<A: CheckedCost, B: CheckedCost> Add for Lexicographic<A, B> {
+    type Output = Self;
+
+    /// add saturates each part, like SumCostModel; astar adds with checked_cost_add
+    fn add(self, other: Self) -> Self {
+        Lexicographic(self.0.saturating_cost_add(other.0), self.1.saturating_cost_add(other.1))
+    }
+}
+
+impl<A: num::Zero + CheckedCost, B: num::Zero + CheckedCost> num::Zero for Lexicographic<A, B> {
+    fn zero() -> Self {
+        Lexicographic(A::zero(), B::zero())
+    }
+
+    fn is_zero(&self) -> bool {
+        self.0.is_zero() && self.1.is_zero()
+    }
+}
+
+impl<A: CheckedCost, B: CheckedCost> CheckedCost for Lexicographic<A, B> {
+    /// checked_cost_add overflows if either part does
+    fn checked_cost_add(self, other: Self) -> Option<Self> {
+        Some(Lexicographic(self.0.checked_cost_add(other.0)?, self.1.checked_cost_add(other.1)?))
+    }
+
+    fn saturating_cost_add(self, other: Self) -> Self {
+        self + other
+    }
+}
+
+/// LexicographicCostModel costs each jump as a Lexicographic of two models' costs: routes are as
+/// cheap as possible by the first model, and as cheap as possible by the second of those.
+#[derive(Debug, Clone)]
+pub struct LexicographicCostModel<A, B>(pub A, pub B);
+
+impl<A: CostModel, B: CostModel> CostModel for LexicographicCostModel<A, B> {
+    type Cost = Lexicographic<A::Cost, B::Cost>;
+
+    fn edge_cost(&self, map: &Map, from: &SolarSystemIndex, to: &SolarSystemIndex) -> Self::Cost {
+        Lexicographic(self.0.edge_cost(map, from, to), self.1.edge_cost(map, from, to))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::MapBuilder;
+    use crate::fixtures::{map_from_edges, names};
+
+    /// square_map is A - B - D, two jumps through gates 100 AU from the star, and A - C - E - D,
+    /// three jumps through gates at the star. System `i` of "ABCDE" has index `i`.
+    fn square_map() -> Map {
+        let mut builder = MapBuilder::new();
+        builder.add_region(1, "Region").add_constellation(2, "Constellation");
+        for (i, name) in ["A", "B", "C", "D", "E"].iter().enumerate() {
+            builder.add_system(30_000_000 + i as u64, name, 2, 1, 1.0);
+        }
+        let far = Some([100.0 * AU, 0.0, 0.0]);
+        for (a, b, position) in [(0, 1, far), (1, 3, far), (0, 2, None), (2, 4, None), (4, 3, None)] {
+            let position = position.or(Some([0.0; 3]));
+            builder.add_jump(30_000_000 + a, 30_000_000 + b, position);
+            builder.add_jump(30_000_000 + b, 30_000_000 + a, position);
+        }
+        builder.build().unwrap()
+    }
+
+    #[test]
+    fn time_cost_model_counts_align_warp_and_gate() {
+        let map = square_map();
+        let model = TimeCostModel::new(7.0);
+        let (a, b, c) = (SolarSystemIndex(0), SolarSystemIndex(1), SolarSystemIndex(2));
+        // 100 AU at 3 AU/s
+        assert!((model.edge_cost(&map, &a, &b).into_inner() - (7.0 + 100.0 / 3.0 + 10.0)).abs() < 0.01);
+        assert_eq!(model.edge_cost(&map, &a, &c).into_inner(), 17.0);
+    }
+
+    #[test]
+    fn time_cost_model_falls_back_without_gate_positions() {
+        let mut builder = MapBuilder::new();
+        builder.add_system(30_000_000, "A", 2, 1, 1.0).add_system(30_000_001, "B", 2, 1, 1.0);
+        builder.connect(30_000_000, 30_000_001);
+        let map = builder.build().unwrap();
+        let (a, b) = (SolarSystemIndex(0), SolarSystemIndex(1));
+        assert_eq!(TimeCostModel::default().edge_cost(&map, &a, &b).into_inner(), 5.0 + 20.0 + 10.0);
+    }
+
+    #[test]
+    fn time_optimal_and_jump_optimal_routes_differ() {
+        let map = square_map();
+        let (a, d) = (SolarSystemIndex(0), SolarSystemIndex(3));
+
+        let (jumps, _) = map.route_with_model(a, d, &JumpsCostModel, None).unwrap();
+        assert_eq!(jumps, [a, SolarSystemIndex(1), d]);
+
+        let (fastest, seconds) = map.route_with_model(a, d, &TimeCostModel::default(), None).unwrap();
+        assert_eq!(fastest, [a, SolarSystemIndex(2), SolarSystemIndex(4), d]);
+        assert_eq!(seconds.into_inner(), 3.0 * 15.0);
+    }
+
+    #[test]
+    fn border_cost_model_penalises_each_class_crossing() {
+        let map = map_from_edges(&[("H", 0.9), ("L", 0.3), ("N", -0.2)], &[(0, 1), (1, 2), (0, 2)]);
+        let (h, l, n) = (SolarSystemIndex(0), SolarSystemIndex(1), SolarSystemIndex(2));
+        let model = BorderCostModel { high_low: 2, low_null: 3, high_null: 5 };
+        assert_eq!(model.edge_cost(&map, &h, &h), 1);
+        assert_eq!(model.edge_cost(&map, &h, &l), 3);
+        assert_eq!(model.edge_cost(&map, &l, &h), 3);
+        assert_eq!(model.edge_cost(&map, &n, &l), 4);
+        assert_eq!(model.edge_cost(&map, &h, &n), 6);
+    }
+
+    #[test]
+    fn border_cost_model_goes_around_a_low_sec_shortcut() {
+        // A - B - D is through low-sec B, A - C - E - D is a jump longer but stays in high-sec
+        let map = map_from_edges(
+            &[("A", 1.0), ("B", 0.3), ("C", 0.9), ("D", 1.0), ("E", 0.8)],
+            &[(0, 1), (1, 3), (0, 2), (2, 4), (4, 3)],
+        );
+        let (a, d) = (SolarSystemIndex(0), SolarSystemIndex(3));
+        let (route, cost) = map.route_with_model(a, d, &BorderCostModel::default(), None).unwrap();
+        assert_eq!(names(&map, &route), ["A", "C", "E", "D"]);
+        assert_eq!(cost, 3);
+
+        let (route, _) = map.route_with_model(a, d, &BorderCostModel::with_penalty(0), None).unwrap();
+        assert_eq!(names(&map, &route), ["A", "B", "D"]);
+    }
+
+    #[test]
+    fn connectivity_cost_model_discounts_each_stargate_out_up_to_max_neighbours() {
+        // B has one jump out and C has six
+        let systems = [("A", 1.0), ("B", 1.0), ("C", 1.0), ("D", 1.0), ("E", 1.0), ("F", 1.0), ("G", 1.0), ("H", 1.0)];
+        let map = map_from_edges(&systems, &[(0, 1), (0, 2), (2, 3), (2, 4), (2, 5), (2, 6), (2, 7)]);
+        let model = ConnectivityCostModel::default();
+        let (a, b, c) = (SolarSystemIndex(0), SolarSystemIndex(1), SolarSystemIndex(2));
+
+        assert_eq!((map.get_system(&b).neighbours.len(), map.get_system(&c).neighbours.len()), (1, 6));
+        assert_eq!(model.edge_cost(&map, &a, &b), 100 - 2);
+        assert_eq!(model.edge_cost(&map, &a, &c), 100 - 5 * 2);
+        assert_eq!(model.min_edge_cost(), 90);
+    }
+
+    #[test]
+    fn connectivity_cost_model_picks_the_better_connected_of_equal_routes() {
+        // A - B - D and A - C - D, with C also joined to E and F
+        let systems = [("A", 1.0), ("B", 1.0), ("C", 1.0), ("D", 1.0), ("E", 1.0), ("F", 1.0)];
+        let map = map_from_edges(&systems, &[(0, 1), (1, 3), (0, 2), (2, 3), (2, 4), (2, 5)]);
+        let (a, d) = (SolarSystemIndex(0), SolarSystemIndex(3));
+        assert_eq!(names(&map, &map.shortest_route(a, d).unwrap()), ["A", "B", "D"]);
+
+        let (route, cost) = map.route_with_model(a, d, &ConnectivityCostModel::default(), None).unwrap();
+        assert_eq!(names(&map, &route), ["A", "C", "D"]);
+        assert_eq!(cost, (100 - 4 * 2) + (100 - 2 * 2));
+    }
+
+    #[test]
+    fn cost_models_saturate_rather_than_wrap_around() {
+        let map = map_from_edges(&[("H", 0.9), ("L", 0.3), ("N", -0.2)], &[(0, 1), (1, 2), (0, 2)]);
+        let (h, l, n) = (SolarSystemIndex(0), SolarSystemIndex(1), SolarSystemIndex(2));
+
+        let border = BorderCostModel::with_penalty(u32::MAX);
+        assert_eq!(border.edge_cost(&map, &h, &l), u32::MAX);
+        assert_eq!(border.edge_cost(&map, &h, &n), u32::MAX);
+        assert_eq!(border.edge_cost(&map, &h, &h), 1);
+
+        let connectivity = ConnectivityCostModel {
+            discount_per_neighbour: u32::MAX,
+            ..Default::default()
+        };
+        assert_eq!(connectivity.edge_cost(&map, &h, &l), 0);
+        assert_eq!(connectivity.min_edge_cost(), 0);
+
+        let sum = SumCostModel(BorderCostModel::with_penalty(u32::MAX - 1), ConstellationCostModel { penalty: 0 });
+        assert_eq!(sum.edge_cost(&map, &l, &n), u32::MAX);
+        assert_eq!(Lexicographic(u32::MAX, 1u32) + Lexicographic(1, 2), Lexicographic(u32::MAX, 3));
+    }
+
+    #[test]
+    fn an_overflowing_penalty_is_a_cost_overflow_not_a_cheap_route() {
+        let map = map_from_edges(&[("H", 0.9), ("L", 0.3), ("H2", 0.9)], &[(0, 1), (1, 2)]);
+        let (h, h2) = (SolarSystemIndex(0), SolarSystemIndex(2));
+        let result = map.route_with_model(h, h2, &BorderCostModel::with_penalty(u32::MAX), None);
+        assert_eq!(result, Err(crate::astar::AStarError::CostOverflow));
+    }
+
+    #[test]
+    fn with_penalty_decides_how_long_a_detour_a_border_is_worth() {
+        assert_eq!(BorderCostModel::with_penalty(3).high_null, 6);
+
+        // A - B - D crosses into low-sec and back out, A - C - E - F - G - D is five high-sec jumps
+        let map = map_from_edges(
+            &[("A", 1.0), ("B", 0.3), ("C", 0.9), ("D", 1.0), ("E", 0.9), ("F", 0.9), ("G", 0.9)],
+            &[(0, 1), (1, 3), (0, 2), (2, 4), (4, 5), (5, 6), (6, 3)],
+        );
+        let (a, d) = (SolarSystemIndex(0), SolarSystemIndex(3));
+        let (route, cost) = map.route_with_model(a, d, &BorderCostModel::with_penalty(1), None).unwrap();
+        assert_eq!((names(&map, &route), cost), (vec!["A", "B", "D"], 4));
+        let (route, cost) = map.route_with_model(a, d, &BorderCostModel::with_penalty(2), None).unwrap();
+        assert_eq!((names(&map, &route), cost), (vec!["A", "C", "E", "F", "G", "D"], 5));
+    }
+
+    #[test]
+    fn with_derived_penalty_goes_around_a_low_sec_shortcut() {
+        // the same ring as above: A - B - D through low-sec, or five high-sec jumps round the other way
+        let map = map_from_edges(
+            &[("A", 1.0), ("B", 0.3), ("C", 0.9), ("D", 1.0), ("E", 0.9), ("F", 0.9), ("G", 0.9)],
+            &[(0, 1), (1, 3), (0, 2), (2, 4), (4, 5), (5, 6), (6, 3)],
+        );
+        let (a, d) = (SolarSystemIndex(0), SolarSystemIndex(3));
+        let derived = BorderCostModel::with_derived_penalty(&map);
+        assert_eq!(derived.high_low, map.max_route_length() + 1);
+
+        let (route, _) = map.route_with_model(a, d, &BorderCostModel::with_penalty(1), None).unwrap();
+        assert_eq!(names(&map, &route), ["A", "B", "D"]);
+        let (route, cost) = map.route_with_model(a, d, &derived, None).unwrap();
+        assert_eq!((names(&map, &route), cost), (vec!["A", "C", "E", "F", "G", "D"], 5));
+
+        // with no detour, the route still goes through low-sec
+        let line = map_from_edges(&[("A", 1.0), ("B", 0.3), ("C", 1.0)], &[(0, 1), (1, 2)]);
+        let derived = BorderCostModel::with_derived_penalty(&line);
+        let (route, _) = line.route_with_model(a, SolarSystemIndex(2), &derived, None).unwrap();
+        assert_eq!(names(&line, &route), ["A", "B", "C"]);
+    }
+
+    #[test]
+    fn jumps_cost_model_routes_like_shortest_route() {
+        let map = crate::fixtures::sde_map();
+        let (tama, amarr) = (crate::fixtures::system(&map, "Tama"), crate::fixtures::system(&map, "Amarr"));
+        let (route, cost) = map.route_with_model(tama, amarr, &JumpsCostModel, None).unwrap();
+        assert_eq!(route, map.shortest_route(tama, amarr).unwrap());
+        assert_eq!(cost, 4);
+
+        // a model can be chosen at runtime, too
+        let model: &dyn CostModel<Cost = u32> = &JumpsCostModel;
+        assert_eq!(map.route_with_model(tama, amarr, model, Some(3)), Err(crate::astar::AStarError::ExceedsMaxCost));
+    }
+
+    #[test]
+    fn danger_cost_model_detours_around_dangerous_systems() {
+        // A - B - D is short but B is dangerous, A - C - E - D is a jump longer
+        let map = map_from_edges(
+            &[("A", 1.0), ("B", 1.0), ("C", 1.0), ("D", 1.0), ("E", 1.0)],
+            &[(0, 1), (1, 3), (0, 2), (2, 4), (4, 3)],
+        );
+        let (a, b, d) = (SolarSystemIndex(0), SolarSystemIndex(1), SolarSystemIndex(3));
+        let model = DangerCostModel::new(HashMap::from([(b, 5)]));
+        assert_eq!(model.edge_cost(&map, &a, &b), 6);
+        assert_eq!(model.edge_cost(&map, &b, &d), 1);
+
+        let (route, cost) = map.route_with_model(a, d, &model, None).unwrap();
+        assert_eq!((names(&map, &route), cost), (vec!["A", "C", "E", "D"], 3));
+
+        let model = DangerCostModel::new(HashMap::from([(SolarSystemIndex(4), 5)]));
+        let (route, cost) = map.route_with_model(a, d, &model, None).unwrap();
+        assert_eq!((names(&map, &route), cost), (vec!["A", "B", "D"], 2));
+    }
+
+    #[test]
+    fn sum_cost_model_adds_both_costs_of_each_jump() {
+        let map = map_from_edges(&[("H", 0.9), ("L", 0.3)], &[(0, 1)]);
+        let (h, l) = (SolarSystemIndex(0), SolarSystemIndex(1));
+        let danger = DangerCostModel {
+            jump_cost: 0,
+            danger: HashMap::from([(l, 10)]),
+        };
+        let model = SumCostModel(BorderCostModel::default(), danger);
+        assert_eq!(model.edge_cost(&map, &h, &l), 1 + 2 + 10);
+        assert_eq!(model.edge_cost(&map, &l, &h), 1 + 2);
+    }
+
+    #[test]
+    fn constellation_cost_model_stays_in_the_constellation_when_it_is_worth_it() {
+        // A - C - D is through C in another constellation, A - B - E - D stays in A's
+        let mut builder = MapBuilder::new();
+        builder.add_region(1, "Region").add_constellation(2, "Home").add_constellation(4, "Away");
+        for (i, name) in ["A", "B", "C", "D", "E"].iter().enumerate() {
+            let constellation = if *name == "C" { 4 } else { 2 };
+            builder.add_system(30_000_000 + i as u64, name, constellation, 1, 1.0);
+        }
+        for (a, b) in [(0, 2), (2, 3), (0, 1), (1, 4), (4, 3)] {
+            builder.connect(30_000_000 + a, 30_000_000 + b);
+        }
+        let map = builder.build().unwrap();
+        let (a, b, c, d) = (SolarSystemIndex(0), SolarSystemIndex(1), SolarSystemIndex(2), SolarSystemIndex(3));
+
+        let model = ConstellationCostModel::default();
+        assert_eq!((model.edge_cost(&map, &a, &b), model.edge_cost(&map, &a, &c)), (1, 2));
+        let (route, cost) = map.route_with_model(a, d, &model, None).unwrap();
+        assert_eq!((names(&map, &route), cost), (vec!["A", "B", "E", "D"], 3));
+
+        let (route, cost) = map.route_with_model(a, d, &ConstellationCostModel { penalty: 0 }, None).unwrap();
+        assert_eq!((names(&map, &route), cost), (vec!["A", "C", "D"], 2));
+    }
+
+    #[test]
+    fn region_preference_costs_a_jump_more_per_rank() {
+        let map = crate::fixtures::sde_map();
+        let system = |name| crate::fixtures::system(&map, name);
+        let region = |name| map.get_extended_solarsystem_info(&system(name)).region_id;
+        let mut model = RegionPreferenceCostModel::new(&[region("Jita"), region("Amarr")]);
+        assert_eq!(model.outside_penalty, 2);
+        assert_eq!(model.edge_cost(&map, &system("Jita"), &system("Perimeter")), 1);
+        assert_eq!(model.edge_cost(&map, &system("Perimeter"), &system("Madirmilire")), 2);
+        assert_eq!(model.edge_cost(&map, &system("Jita"), &system("Yulai")), 3);
+
+        // with leaving costing enough, the route stays in The Forge and Domain
+        model.outside_penalty = 10;
+        let (route, cost) = map.route_with_model(system("Jita"), system("Amarr"), &model, None).unwrap();
+        assert_eq!(names(&map, &route), ["Jita", "Perimeter", "Madirmilire", "Niarja", "Sarum Prime", "Amarr"]);
+        assert_eq!(cost, 9);
+    }
+
+    #[test]
+    fn exposure_cost_model_weighs_the_time_in_each_system_by_its_danger() {
+        let map = square_map();
+        let (a, c, d, e) = (SolarSystemIndex(0), SolarSystemIndex(2), SolarSystemIndex(3), SolarSystemIndex(4));
+        let model = ExposureCostModel::new(HashMap::from([(c, 2.0), (d, 100.0)]));
+        // 15s to cross each system with its gate at the star
+        assert_eq!(model.edge_cost(&map, &a, &c).into_inner(), 15.0);
+        assert_eq!(model.edge_cost(&map, &c, &e).into_inner(), 45.0);
+        // the destination's danger doesn't count: the route ends on arrival
+        assert_eq!(model.edge_cost(&map, &e, &d).into_inner(), 15.0);
+    }
+
+    #[test]
+    fn exposure_cost_model_takes_the_long_warps_around_a_dangerous_system() {
+        // A - C - E - D takes 45s and A - B - D about 97s, until C is dangerous enough
+        let map = square_map();
+        let (a, c, d) = (SolarSystemIndex(0), SolarSystemIndex(2), SolarSystemIndex(3));
+        let route = |danger| {
+            let model = ExposureCostModel::new(HashMap::from([(c, danger)]));
+            names(&map, &map.route_with_model(a, d, &model, None).unwrap().0).join(" ")
+        };
+        assert_eq!(route(3.0), "A C E D");
+        assert_eq!(route(5.0), "A B D");
+    }
+
+    #[test]
+    fn lexicographic_costs_compare_the_first_part_first() {
+        assert!(Lexicographic(1u32, 100u32) < Lexicographic(2, 0));
+        assert!(Lexicographic(2u32, 1u32) < Lexicographic(2, 3));
+        assert_eq!(Lexicographic(1u32, 2u32) + Lexicographic(3, 4), Lexicographic(4, 6));
+        assert_eq!(Lexicographic(1u32, u32::MAX).checked_cost_add(Lexicographic(1, 1)), None);
+        assert_eq!(Lexicographic(1u32, u32::MAX) + Lexicographic(1, 1), Lexicographic(2, u32::MAX));
+    }
+
+    #[test]
+    fn lexicographic_cost_model_breaks_ties_in_jumps_by_danger() {
+        // Jita - Perimeter - Tama and Jita - EC-P8R - Tama are both two jumps
+        let map = crate::fixtures::sde_map();
+        let system = |name| crate::fixtures::system(&map, name);
+        let (jita, tama) = (system("Jita"), system("Tama"));
+        let route = |danger: HashMap<SolarSystemIndex, u32>| {
+            let model = LexicographicCostModel(JumpsCostModel, DangerCostModel::new(danger));
+            let (route, cost) = map.route_with_model(jita, tama, &model, None).unwrap();
+            (names(&map, &route), cost)
+        };
+        let (via_ec, via_perimeter) = (vec!["Jita", "EC-P8R", "Tama"], vec!["Jita", "Perimeter", "Tama"]);
+        assert_eq!(route(HashMap::from([(system("Perimeter"), 5)])), (via_ec, Lexicographic(2, 2)));
+        assert_eq!(route(HashMap::from([(system("EC-P8R"), 5)])), (via_perimeter, Lexicographic(2, 2)));
+        // but never a longer route, however dangerous
+        let (_, cost) = route(HashMap::from([(system("Perimeter"), 50), (system("EC-P8R"), 50)]));
+        assert_eq!(cost.0, 2);
+    }
+
+    /// diamond_map is A - B - D and A - C - D, with the gates in B and C together, A's gate to B and
+    /// D's gate to C at the star, and A's gate to C and D's gate to B 100 AU out. System `i` of
+    /// "ABCD" has index `i`.
+    fn diamond_map() -> Map {
+        let mut builder = MapBuilder::new();
+        builder.add_region(1, "Region").add_constellation(2, "Constellation");
+        for (i, name) in ["A", "B", "C", "D"].iter().enumerate() {
+            builder.add_system(30_000_000 + i as u64, name, 2, 1, 1.0);
+        }
+        let (star, far) = (Some([0.0; 3]), Some([100.0 * AU, 0.0, 0.0]));
+        let jumps = [(0, 1, star), (0, 2, far), (3, 1, far), (3, 2, star)];
+        let back = [(1, 0, star), (1, 3, star), (2, 0, star), (2, 3, star)];
+        for (from, to, position) in jumps.into_iter().chain(back) {
+            builder.add_jump(30_000_000 + from, 30_000_000 + to, position);
+        }
+        builder.build().unwrap()
+    }
+
+    #[test]
+    fn gate_to_gate_time_model_warps_from_the_gate_arrived_at() {
+        let map = diamond_map();
+        let model = GateToGateTimeModel::new(5.0);
+        let (a, b, c, d) = (SolarSystemIndex(0), SolarSystemIndex(1), SolarSystemIndex(2), SolarSystemIndex(3));
+        // starting at A's star, its gate to B is no warp at all
+        assert_eq!(model.edge_cost(&map, None, &a, &b).into_inner(), 15.0);
+        assert!((model.edge_cost(&map, None, &a, &c).into_inner() - (15.0 + 100.0 / 3.0)).abs() < 0.01);
+        // B's gates are together, whichever way the ship goes through
+        assert_eq!(model.edge_cost(&map, Some(&a), &b, &d).into_inner(), 15.0);
+        assert_eq!(model.edge_cost(&map, Some(&d), &b, &a).into_inner(), 15.0);
+    }
+
+    #[test]
+    fn gate_to_gate_routes_depend_on_direction() {
+        let map = diamond_map();
+        let model = GateToGateTimeModel::new(5.0);
+        let (a, d) = (SolarSystemIndex(0), SolarSystemIndex(3));
+        let (there, cost) = map.route_gate_to_gate(a, d, &model).unwrap();
+        assert_eq!(names(&map, &there), ["A", "B", "D"]);
+        assert_eq!(cost.into_inner(), 30.0);
+        let (back, cost) = map.route_gate_to_gate(d, a, &model).unwrap();
+        assert_eq!(names(&map, &back), ["D", "C", "A"]);
+        assert_eq!(cost.into_inner(), 30.0);
+        assert_eq!(map.route_gate_to_gate(a, a, &model).unwrap(), (vec![a], NotNan::default()));
+    }
+
+    #[test]
+    fn edge_weights_from_csv_are_per_direction() {
+        let map = crate::fixtures::sde_map();
+        let system = |name| crate::fixtures::system(&map, name);
+        let csv = "Jita,Yulai,10\n\n Yulai , Amarr , 2.5 \nJita,Yulai,20\n";
+        let model = EdgeWeightCostModel::from_csv(&map, csv).unwrap();
+        assert_eq!(model.edge_cost(&map, &system("Jita"), &system("Yulai")).into_inner(), 20.0);
+        assert_eq!(model.edge_cost(&map, &system("Yulai"), &system("Jita")).into_inner(), 1.0);
+        assert_eq!(model.edge_cost(&map, &system("Yulai"), &system("Amarr")).into_inner(), 2.5);
+
+        // around the weighted gate: five jumps weighing 1 beat 20 + 2.5
+        let (route, cost) = map.route_with_model(system("Jita"), system("Amarr"), &model, None).unwrap();
+        assert_eq!(names(&map, &route), ["Jita", "Perimeter", "Madirmilire", "Niarja", "Sarum Prime", "Amarr"]);
+        assert_eq!(cost.into_inner(), 5.0);
+    }
+
+    #[test]
+    fn edge_weights_from_csv_reject_bad_lines() {
+        let map = crate::fixtures::sde_map();
+        let error = |csv| EdgeWeightCostModel::from_csv(&map, csv).unwrap_err().to_string();
+        assert_eq!(error("Jita,Yulai,1\nJita,Yulai"), "line 2: expected from,to,weight but found \"Jita,Yulai\"");
+        assert_eq!(error("Jita,Amarr,1"), "line 1: there's no jump from Jita to Amarr");
+        assert_eq!(error("Jita,Yulai,-1"), "line 1: weight \"-1\" isn't a number of at least 0");
+        assert_eq!(error("Jita,Yulai,NaN"), "line 1: weight \"NaN\" isn't a number of at least 0");
+        assert_eq!(error("Jita,Nowhere,1"), "unknown solar system Nowhere");
+    }
+}
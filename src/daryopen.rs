@@ -0,0 +1,154 @@
+use crate::astar;
+
+/// DaryOpenList is an astar::OpenList kept in a d-ary heap: each item has up to `D` children
+/// rather than a binary heap's two. The heap is shallower, so a push (which walks up it) touches
+/// fewer items, while a pop (which walks down, comparing all the children at each level) touches
+/// more. A* pushes an item for every neighbour it improves but pops only one per expansion, so a
+/// wider heap usually comes out ahead, and its children sit next to each other in memory.
+///
+/// Like SimpleOpenList, it doesn't check for an existing item for the same node: A* skips the
+/// stale ones when they're popped.
+pub struct DaryOpenList<N, Cost, const D: usize = 4> {
+    heap: Vec<astar::OpenItem<N, Cost>>,
+}
+
+impl<N, Cost, const D: usize> DaryOpenList<N, Cost, D>
+where
+    astar::OpenItem<N, Cost>: Ord,
+{
+    pub fn new() -> Self {
+        Self::with_capacity(0)
+    }
+
+    /// with_capacity is an empty open list with room for `capacity` items before it reallocates
+    pub fn with_capacity(capacity: usize) -> Self {
+        assert!(D >= 2, "a d-ary heap needs at least two children per item");
+        Self {
+            heap: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// clear empties the list but keeps its allocation
+    pub fn clear(&mut self) {
+        self.heap.clear();
+    }
+
+    /// sift_up moves the item at `i` towards the root until its parent comes before it
+    fn sift_up(&mut self, mut i: usize) {
+        while i > 0 {
+            let parent = (i - 1) / D;
+            if self.heap[i] <= self.heap[parent] {
+                break;
+            }
+            self.heap.swap(i, parent);
+            i = parent;
+        }
+    }
+
+    /// sift_down moves the item at `i` away from the root until it comes before all its children
+    fn sift_down(&mut self, mut i: usize) {
+        loop {
+            let first_child = i * D + 1;
+            if first_child >= self.heap.len() {
+                break;
+            }
+            let last_child = (first_child + D).min(self.heap.len());
+            let mut best = first_child;
+            for child in first_child + 1..last_child {
+                if self.heap[child] > self.heap[best] {
+                    best = child;
+                }
+            }
+            if self.heap[best] <= self.heap[i] {
+                break;
+            }
+            self.heap.swap(i, best);
+            i = best;
+        }
+    }
+}
+
+impl<N, Cost, const D: usize> Default for DaryOpenList<N, Cost, D>
+where
+    astar::OpenItem<N, Cost>: Ord,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<N, Cost, const D: usize> astar::OpenList<astar::OpenItem<N, Cost>> for DaryOpenList<N, Cost, D>
+where
+    astar::OpenItem<N, Cost>: Ord,
+{
+    fn is_empty(&self) -> bool {
+        self.heap.is_empty()
+    }
+
+    fn len(&self) -> usize {
+        self.heap.len()
+    }
+
+    fn push_open(&mut self, e: astar::OpenItem<N, Cost>) {
+        self.heap.push(e);
+        self.sift_up(self.heap.len() - 1);
+    }
+
+    fn pop_min(&mut self) -> Option<astar::OpenItem<N, Cost>> {
+        if self.heap.is_empty() {
+            return None;
+        }
+        let item = self.heap.swap_remove(0);
+        if !self.heap.is_empty() {
+            self.sift_down(0);
+        }
+        Some(item)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::astar::{OpenItem, OpenList};
+    use crate::rng::SplitMix64;
+    use crate::simpleopen::SimpleOpenList;
+
+    /// drain pops everything from `open`, returning the costs in the order they came out
+    fn drain(open: &mut impl OpenList<OpenItem<u32, u32>>) -> Vec<u32> {
+        std::iter::from_fn(|| open.pop_min().map(|item| item.heuristic)).collect()
+    }
+
+    #[test]
+    fn pops_cheapest_first_like_simple_open_list() {
+        let mut rng = SplitMix64::new(7);
+        let mut dary: DaryOpenList<u32, u32> = DaryOpenList::new();
+        let mut ternary: DaryOpenList<u32, u32, 3> = DaryOpenList::new();
+        let mut simple = SimpleOpenList::new();
+        for node in 0..500 {
+            let heuristic = rng.below(100) as u32;
+            dary.push_open(OpenItem { heuristic, node });
+            ternary.push_open(OpenItem { heuristic, node });
+            simple.push_open(OpenItem { heuristic, node });
+        }
+        assert_eq!(dary.len(), 500);
+
+        let expected = drain(&mut simple);
+        assert!(expected.windows(2).all(|w| w[0] <= w[1]));
+        assert_eq!(drain(&mut dary), expected);
+        assert_eq!(drain(&mut ternary), expected);
+        assert!(dary.is_empty());
+    }
+
+    #[test]
+    fn pops_interleaved_with_pushes() {
+        let mut open: DaryOpenList<u32, u32> = DaryOpenList::new();
+        for heuristic in [5, 3, 8] {
+            open.push_open(OpenItem { heuristic, node: 0 });
+        }
+        assert_eq!(open.pop_min().map(|i| i.heuristic), Some(3));
+        open.push_open(OpenItem { heuristic: 1, node: 0 });
+        open.push_open(OpenItem { heuristic: 6, node: 0 });
+        assert_eq!(drain(&mut open), [1, 5, 6, 8]);
+        assert!(open.pop_min().is_none());
+    }
+}
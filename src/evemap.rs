@@ -1,11 +1,15 @@
 use eyre::{eyre, WrapErr};
 use rayon::prelude::*;
 use sde::SdeZipReader;
-use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
 use std::fmt::Display;
 use std::io;
+use std::sync::OnceLock;
 
+use crate::builder::{MapBuilder, WiringReport};
 use crate::sde;
+use crate::security::SecurityClass;
 
 /// NUM_IN_PLACE_JUMPS is used by the Neighbours type which has enum variants for an in place array
 /// as well as a dynamically grown vector. The in place jump array serves for many systems at a count of <=3
@@ -13,20 +17,42 @@ use crate::sde;
 /// Note: it's _probably_ entirely irrelevant as an optimization and should probably be removed
 const NUM_IN_PLACE_JUMPS: usize = 3;
 
+/// MAJOR_HUBS are the five main trade hubs, where most haulers are headed. Use Map::find_hubs for
+/// a different list.
+pub const MAJOR_HUBS: [&str; 5] = ["Jita", "Amarr", "Dodixie", "Rens", "Hek"];
+
 /// Map is a wrapper around a number of structures that allow you to work with an Eve Map
 pub struct Map {
     /// systems is a packed vector of solarsystems including the minimal SolarSystemMapItem
     /// this is basically only the SolarSystemId, and the list of neighbours
     /// it is intended to be indexed by a SolarSystemIndex
-    systems: Vec<SolarSystemMapItem>,
+    pub(crate) systems: Vec<SolarSystemMapItem>,
     /// extended_systems is a pair to systems, but includes more information not strictly required
     /// to expand and explore neighbours
-    extended_systems: Vec<SolarSystemEx>,
-    /// lookup for a system name to an ID. Because it's not strictly limited to Systems, could yield an ID
-    /// for something that would not be contained in system_id_to_index
-    name_to_id: HashMap<String, u64>,
+    pub(crate) extended_systems: Vec<SolarSystemEx>,
+    /// lookup for a system name to IDs. Because it's not strictly limited to Systems, could yield an ID
+    /// for something that would not be contained in system_id_to_index.
+    /// Names aren't guaranteed to be unique, so all the IDs with a name are kept
+    pub(crate) name_to_id: HashMap<String, Vec<u64>>,
     /// lookup to convert a SolarSystemId to a SolarSystemIndex for direct lookups in the vec
-    system_id_to_index: HashMap<SolarSystemId, SolarSystemIndex>,
+    pub(crate) system_id_to_index: HashMap<SolarSystemId, SolarSystemIndex>,
+    /// reverse adjacency: for each system (by SolarSystemIndex), the systems that have a jump into it
+    pub(crate) predecessors: Vec<Vec<SolarSystemIndex>>,
+    /// build number of the SDE the map was read from, if it said
+    pub(crate) sde_version: Option<String>,
+    /// jumps taken out of the map by remove_edge, so that they can be restored
+    pub(crate) removed_edges: HashSet<(SolarSystemIndex, SolarSystemIndex)>,
+    /// label of each system's connected component (see connected_components), for path_exists.
+    /// Removing jumps can only split components, so labels are computed when the map is built
+    /// and only need redoing when jumps are added. Jumps in removed_edges still join components.
+    pub(crate) components: Vec<u32>,
+    /// the stargates the map was built from, by stargate id. Jumps added without a pair of
+    /// stargates (eg. by a patch or from a MapSnapshot) have none here.
+    pub(crate) stargates: HashMap<u64, StargateData>,
+    /// how the stargates were wired into jumps when the map was built
+    pub(crate) wiring: WiringReport,
+    /// max_route_length, once it's been asked for. Changing the jumps clears it.
+    pub(crate) max_route_length: OnceLock<u32>,
 }
 
 impl<'a> IntoIterator for &'a Map {
@@ -42,8 +68,16 @@ impl<'a> IntoIterator for &'a Map {
 /// It is intended to only be ever created with the invariant that the lookup id is valid for the
 /// systems and extended_systems vecs, allowing unchecked lookups.
 ///
-#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Copy, Clone)]
-pub struct SolarSystemIndex(u16);
+#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Copy, Clone, Hash)]
+pub struct SolarSystemIndex(pub(crate) RawIndex);
+
+/// RawIndex is the integer inside a SolarSystemIndex. A u16 is plenty for the ~8000 systems of
+/// Eve and keeps the neighbour lists small; the wide-index feature makes it a u32, for merged maps
+/// (eg. Eve with wormholes and custom overlays) of more than 65536 systems.
+#[cfg(not(feature = "wide-index"))]
+pub type RawIndex = u16;
+#[cfg(feature = "wide-index")]
+pub type RawIndex = u32;
 
 impl From<SolarSystemIndex> for usize {
     #[inline]
@@ -58,14 +92,27 @@ impl From<SolarSystemIndex> for usize {
 ///
 /// As such assume that SolarSystemId is an artifact of reading, writing and communicating with users,
 /// NOT a node identifier as used by A*
-#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Copy, Clone, Hash)]
-pub struct SolarSystemId(u64);
+#[derive(Debug, Ord, PartialOrd, Eq, PartialEq, Copy, Clone, Hash, serde::Serialize, serde::Deserialize)]
+pub struct SolarSystemId(pub(crate) u64);
 
 #[derive(Debug, Clone)]
 pub struct StargateData {
     pub stargate_id: u64,
     pub solar_system_id: SolarSystemId,
     pub destination_stargate_id: u64,
+    /// position of the gate within its solar system (meters), if the SDE included it
+    pub position: Option<[f64; 3]>,
+}
+
+/// SystemGate is a stargate out of a system, resolved to the system on the other side.
+/// It keeps the in-system details that Neighbours doesn't need for plain jump counting.
+#[derive(Debug, Clone)]
+pub struct SystemGate {
+    pub destination: SolarSystemIndex,
+    /// the stargate to activate to make the jump, None for jumps that aren't through a stargate
+    pub stargate_id: Option<u64>,
+    /// position of the gate within the system (meters), relative to the star
+    pub position: Option<[f64; 3]>,
 }
 
 /// Neighbours is a structure that's either an in-place array, or a
@@ -81,11 +128,53 @@ pub enum Neighbours {
 #[derive(Debug)]
 pub struct SolarSystemMapItem {
     pub solar_system_id: SolarSystemId,
-    pub neighbours: once_cell::unsync::OnceCell<Neighbours>,
+    /// filled in when the map is built. This used to be a OnceCell, but nothing ever set it later,
+    /// and holding it directly keeps Map Send + Sync without paying for a sync cell
+    pub neighbours: Neighbours,
+}
+
+// A Map is shared read only between threads (see route_many), so it has to stay Send + Sync
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Map>();
+};
+
+/// AmbiguousName is a name shared by more than one solar system
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AmbiguousName {
+    pub name: String,
+    /// each of the systems with the name, and the region ID that it is in
+    pub candidates: Vec<(SolarSystemId, u64)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NameLookupError {
+    /// there's no solar system with the name
+    NotFound(String),
+    /// more than one solar system has the name
+    Ambiguous(AmbiguousName),
+}
+
+impl Display for NameLookupError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NameLookupError::NotFound(name) => write!(f, "unknown solar system {name}"),
+            NameLookupError::Ambiguous(a) => {
+                write!(f, "more than one solar system is named {}:", a.name)?;
+                for (id, region) in &a.candidates {
+                    write!(f, " {id} (region {region})")?;
+                }
+                Ok(())
+            }
+        }
+    }
 }
 
+impl std::error::Error for NameLookupError {}
+
 /// SolarSystemEx is a larger object with more information in it than SolarSystemMapItem
 ///
+#[derive(Debug, Clone)]
 pub struct SolarSystemEx {
     /// solar system name in Eve Online, eg. Yulai
     pub name: String,
@@ -93,134 +182,305 @@ pub struct SolarSystemEx {
     pub solar_system_id: SolarSystemId,
     pub constellation_id: u64,
     pub region_id: u64,
+    /// true security status of the system, as parsed from the SDE. See security_true/security_rounded
+    pub(crate) security: f64,
+    /// the stargates out of this system, in the same order as the neighbours
+    pub gates: Vec<SystemGate>,
+    /// position of the system in the universe (meters), if the SDE included it
+    pub position: Option<[f64; 3]>,
 }
 
 impl Map {
-    // TODO: Split this up into functions
     /// this function takes a ZIP containing an SDE and converts it into a Map
     /// this requires creating a number of internal lookups (eg. what system is a given gateID in?)
-    /// in order to build our map, indexes and neighbours
+    /// in order to build our map, indexes and neighbours, the bulk of which is left to MapBuilder
     pub fn new<T: io::Read + Send>(reader: &mut SdeZipReader<T>) -> Result<Map, eyre::Error> {
-        let mut stargates_by_system =
-            Vec::<(SolarSystemId, Vec<StargateData>)>::with_capacity(6000);
-        let mut stellar_items = Vec::<(u64, String, MapType)>::with_capacity(6000);
+        Map::read(reader, |_| true)
+    }
+
+    /// new_filtered is Map::new, but only keeps the systems in the named regions. Jumps out of
+    /// those regions are pruned, so routes that leave the regions (even to come back) won't be
+    /// found. For region scoped tools this saves a lot of the build time and memory.
+    pub fn new_filtered<T: io::Read + Send>(
+        reader: &mut SdeZipReader<T>,
+        regions: &[&str],
+    ) -> Result<Map, eyre::Error> {
+        Map::read(reader, |region| regions.contains(&region))
+    }
+
+    /// new_in_pool is Map::new, parsing on the threads of `pool` rather than rayon's global pool,
+    /// for applications that manage their own threads. For a number of threads, build the pool
+    /// with `rayon::ThreadPoolBuilder::new().num_threads(n)`.
+    pub fn new_in_pool<T: io::Read + Send>(
+        reader: &mut SdeZipReader<T>,
+        pool: &rayon::ThreadPool,
+    ) -> Result<Map, eyre::Error> {
+        pool.install(|| Map::new(reader))
+    }
+
+    /// new_from_sources builds a single Map from several sources of SDE files, eg. the official
+    /// SDE and an overlay of test systems or a private jump network. Each source yields
+    /// (path, contents) pairs like SdeZipReader does, with the same `sde/fsd/universe/eve/...`
+    /// paths, and other files are skipped.
+    ///
+    /// Sources are merged in order. When a region, constellation or system id appears in more than
+    /// one source, the last source's file replaces the earlier ones entirely, so an overriding
+    /// system brings all of its stargates with it. Stargates lead wherever their destination gate
+    /// is, in any source, so an overlay system can have a gate into a base system; for a jump
+    /// back, the overlay has to override the base system with the extra gate too.
+    ///
+    /// Plain iterators carry no names table or SDE version, so systems are named by their paths
+    /// and the map has no sde_version.
+    pub fn new_from_sources(sources: &mut [&mut dyn Iterator<Item = (String, Vec<u8>)>]) -> Result<Map, eyre::Error> {
+        // the sources don't have to be Send, so read them in turn and parse in parallel after.
+        // Files stay in source order, which is what decides the winner of an id
+        let files: Vec<(String, Vec<u8>)> = sources
+            .iter_mut()
+            .flatten()
+            .filter(|(filename, _)| filename.starts_with("sde/fsd/universe/eve") && filename.ends_with(".staticdata"))
+            .collect();
 
+        let parsed = files
+            .par_iter()
+            .map(|(filename, content)| parse(filename, content))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // a later file with an id takes the place of the first one
+        let mut merged: Vec<(IdNameType, SolarSystemStargates)> = Vec::with_capacity(parsed.len());
+        let mut position_of_id = HashMap::new();
+        for item in parsed.into_iter().flatten() {
+            match position_of_id.entry(item.0 .0) {
+                Entry::Occupied(at) => merged[*at.get()] = item,
+                Entry::Vacant(at) => {
+                    at.insert(merged.len());
+                    merged.push(item);
+                }
+            }
+        }
+
+        Map::from_parsed(merged, None, None, None)
+    }
+
+    fn read<T: io::Read + Send, F: Fn(&str) -> bool + Sync>(
+        reader: &mut SdeZipReader<T>,
+        keep_region: F,
+    ) -> Result<Map, eyre::Error> {
         // Read all the stellar items from the SDE (Region/Constellation/System)
         // pipe in parallel to parsing function (using rayon) and collect the result
+        // The path is sde/fsd/universe/eve/<region>/..., so other regions can be skipped without parsing
         let p = reader
             .par_bridge() // rayon parallel iterator bridge
+            .filter(|(filename, _)| filename.split('/').nth(4).is_some_and(&keep_region))
             .map(|(filename, file_content)| parse(filename.as_str(), file_content.as_slice()))
             .collect::<Result<Vec<_>, _>>()?;
 
-        // flatten() unwraps the Option<>s in the reader
-        for (new_stellar_item, maybe_stargates) in p.iter().flatten() {
-            stellar_items.push(new_stellar_item.to_owned());
+        // The hierarchy has to use the path names, but if the SDE's name table was read
+        // then it has the official name for each item, and perhaps a translation of it
+        let names = reader.names();
+        let localized = reader.language().and_then(|l| reader.localized_names(l));
+        Map::from_parsed(p.into_iter().flatten().collect(), names, localized, reader.version())
+    }
+
+    /// from_parsed builds the map from the parsed universe files, naming items from `localized`
+    /// or else `names` if given, and otherwise by their paths
+    fn from_parsed(
+        p: Vec<(IdNameType, SolarSystemStargates)>,
+        names: Option<&HashMap<u64, String>>,
+        localized: Option<&HashMap<u64, String>>,
+        version: Option<&str>,
+    ) -> Result<Map, eyre::Error> {
+        let mut stargates_by_system =
+            Vec::<(SolarSystemId, Vec<StargateData>)>::with_capacity(6000);
+        let mut stellar_items = Vec::<(u64, String, MapType)>::with_capacity(6000);
+
+        for (new_stellar_item, maybe_stargates) in p {
+            stellar_items.push(new_stellar_item);
             if let Some(stargates) = maybe_stargates {
-                stargates_by_system.push((*stargates).clone());
+                stargates_by_system.push(stargates);
             }
         }
 
-        // stellar items and stargates_by_system are all we care about now
-        drop(p);
-
         // Used for name lookups, since the hierarchy is based on filename
         // and the filenames have the name of the region/constellation/system
-        // NB: Strictly speaking the names should come from a translation table
-        // for multiple languages, but the disk structure represents the names
-        let stellar_item_name_to_id: HashMap<String, u64> = stellar_items
+        // NB: These can differ from the official names, which are in the SDE's name table (see below)
+        // Regions and constellations are kept apart, since a name can be used by more than one kind of item
+        let region_name_to_id: HashMap<&str, u64> = stellar_items
             .iter()
-            .map(|(id, name, _)| (name.clone(), *id))
+            .filter(|(_, _, t)| matches!(t, MapType::Region))
+            .map(|(id, name, _)| (name.as_str(), *id))
             .collect();
-
-        let system_count: usize = stellar_items
+        let constellation_name_to_id: HashMap<&str, u64> = stellar_items
             .iter()
-            .filter(|(_, _, t)| t.is_solarsystem())
-            .count();
-
-        let mut solarsystems: Vec<SolarSystemMapItem> = Vec::with_capacity(system_count);
-        let mut solarsystems_ex: Vec<SolarSystemEx> = Vec::with_capacity(system_count);
+            .filter(|(_, _, t)| matches!(t, MapType::Constellation { .. }))
+            .map(|(id, name, _)| (name.as_str(), *id))
+            .collect();
 
-        for x in &stellar_items {
-            if let (
-                id,
-                name,
+        let mut builder = MapBuilder::new();
+        for (id, path_name, t) in &stellar_items {
+            let name = localized
+                .and_then(|n| n.get(id))
+                .or_else(|| names.and_then(|n| n.get(id)))
+                .unwrap_or(path_name);
+            match t {
+                MapType::Region => builder.add_region(*id, name),
+                MapType::Constellation { .. } => builder.add_constellation(*id, name),
                 MapType::SolarSystem {
                     region,
                     constellation,
-                },
-            ) = x
-            {
-                solarsystems.push(SolarSystemMapItem {
-                    solar_system_id: SolarSystemId(*id),
-                    neighbours: Default::default(),
-                });
-
-                solarsystems_ex.push(SolarSystemEx {
-                    name: name.clone(),
-                    solar_system_id: SolarSystemId(*id),
-                    constellation_id: *stellar_item_name_to_id
+                    security,
+                    center,
+                } => {
+                    let constellation_id = *constellation_name_to_id
                         .get(constellation.as_str())
                         .ok_or_else(|| {
                             eyre!(
                                 "constellation {} not found for system {}",
                                 constellation,
-                                name
+                                path_name
                             )
-                        })?,
-                    region_id: *stellar_item_name_to_id
+                        })?;
+                    let region_id = *region_name_to_id
                         .get(region.as_str())
-                        .ok_or_else(|| eyre!("region {} not found for system {}", region, name))?,
-                });
+                        .ok_or_else(|| eyre!("region {} not found for system {}", region, path_name))?;
+
+                    builder.add_system_at(*id, name, constellation_id, region_id, *security, *center)
+                }
+            };
+        }
+
+        // stargates leading to a region that was filtered out have nowhere to go, and build leaves
+        // them out, counting them in the map's WiringReport
+        for (_, stargates) in stargates_by_system {
+            for g in stargates {
+                builder.add_stargate(g);
             }
         }
 
-        // since solarsystem ID is unique, don't need any stable sorting
-        solarsystems.sort_unstable_by_key(|x| x.solar_system_id.0);
-        solarsystems_ex.sort_unstable_by_key(|x| x.solar_system_id.0);
+        if let Some(version) = version {
+            builder.sde_version(version);
+        }
 
-        // build a lookup of the offset of a SolarSystemID
-        let solarsystem_lookup: HashMap<SolarSystemId, SolarSystemIndex> = solarsystems
-            .iter()
-            .enumerate()
-            .map(|(i, ss)| Ok((ss.solar_system_id, SolarSystemIndex(i.try_into()?))))
-            .collect::<eyre::Result<_>>()?;
+        builder.build()
+    }
+}
 
-        //
-        let stargate_id_to_system_id: HashMap<u64, SolarSystemIndex> = stargates_by_system
-            .iter()
-            .flat_map(|(ssid, gates)| {
-                let ss_idx = solarsystem_lookup[ssid];
-                gates.iter().map(move |g| (g.stargate_id, ss_idx))
-            })
-            .collect();
+impl Map {
+    /// from_zip_bytes builds a Map from an SDE zip that is already in memory
+    /// eg. a small fixture pulled in with `include_bytes!`, saving the SdeZipReader boilerplate
+    pub fn from_zip_bytes(bytes: &[u8]) -> Result<Map, eyre::Error> {
+        let mut reader = SdeZipReader::new(io::Cursor::new(bytes));
+        Map::new(&mut reader)
+    }
 
-        // Finally, build and set the neighours for each solarsystem
-        for (ssid, stargates) in &stargates_by_system {
-            let ss_idx = solarsystem_lookup[ssid];
+    /// sde_version is the build of the SDE this map was read from, if the SDE included it
+    pub fn sde_version(&self) -> Option<&str> {
+        self.sde_version.as_deref()
+    }
 
-            let neighbours = stargates
-                .iter()
-                .map(|g| stargate_id_to_system_id[&g.destination_stargate_id])
-                .collect::<Neighbours>();
+    /// wiring_report counts the stargates read for the map, how many of their destinations could
+    /// be found, and the jumps that made. It describes the map as built, before any patch or
+    /// remove_edge.
+    pub fn wiring_report(&self) -> WiringReport {
+        self.wiring
+    }
 
-            if let Some(ss) = solarsystems.get_mut(ss_idx.0 as usize) {
-                ss.neighbours.set(neighbours).or_else(|_| Err(eyre!("unable to set neighbours on {ssid}")))?;
-            }
+    /// system_count is the number of solar systems in the map, and so one past the largest SolarSystemIndex
+    #[inline]
+    pub fn system_count(&self) -> usize {
+        self.systems.len()
+    }
+
+    /// index_from_u16 turns a raw index, eg. one deserialized or from another program, into a
+    /// SolarSystemIndex for this map. It's None unless the index is below system_count, which is
+    /// what makes the unchecked lookups on a SolarSystemIndex safe.
+    #[inline]
+    pub fn index_from_u16(&self, raw: u16) -> Option<SolarSystemIndex> {
+        self.index_from_raw(raw as RawIndex)
+    }
+
+    /// index_from_raw is index_from_u16 for a RawIndex, which reaches every system of a map built
+    /// with the wide-index feature
+    #[inline]
+    pub fn index_from_raw(&self, raw: RawIndex) -> Option<SolarSystemIndex> {
+        ((raw as usize) < self.system_count()).then_some(SolarSystemIndex(raw))
+    }
+
+    /// get_solarsystem_id_by_name looks up a solar system by name. Regions and constellations of
+    /// the same name are ignored, but if more than one solar system has the name, this returns
+    /// all of them in an AmbiguousName error rather than picking one.
+    pub fn get_solarsystem_id_by_name(&self, name: &str) -> Result<SolarSystemId, NameLookupError> {
+        let candidates: Vec<SolarSystemId> = self
+            .name_to_id
+            .get(name)
+            .into_iter()
+            .flatten()
+            .map(|id| SolarSystemId(*id))
+            .filter(|id| self.system_id_to_index.contains_key(id))
+            .collect();
+
+        match candidates.as_slice() {
+            [] => Err(NameLookupError::NotFound(name.to_string())),
+            [id] => Ok(*id),
+            _ => Err(NameLookupError::Ambiguous(AmbiguousName {
+                name: name.to_string(),
+                candidates: candidates
+                    .iter()
+                    .map(|id| (*id, self.get_extended_solarsystem_info(&self.system_id_to_index[id]).region_id))
+                    .collect(),
+            })),
         }
+    }
+
+    /// systems_with_prefix finds the solar systems whose names start with `prefix`, ignoring case,
+    /// for autocompleting names. They're sorted by name and at most `limit` are returned.
+    pub fn systems_with_prefix(&self, prefix: &str, limit: usize) -> Vec<(SolarSystemId, &str)> {
+        let prefix = prefix.to_lowercase();
+        let mut matches: Vec<(SolarSystemId, &str)> = self
+            .name_to_id
+            .iter()
+            .filter(|(name, _)| name.to_lowercase().starts_with(&prefix))
+            .flat_map(|(name, ids)| ids.iter().map(move |id| (SolarSystemId(*id), name.as_str())))
+            .filter(|(id, _)| self.system_id_to_index.contains_key(id))
+            .collect();
 
-        Ok(Map {
-            systems: solarsystems,
-            extended_systems: solarsystems_ex,
-            name_to_id: stellar_item_name_to_id,
-            system_id_to_index: solarsystem_lookup,
-        })
+        matches.sort_unstable_by(|a, b| a.1.cmp(b.1).then(a.0.cmp(&b.0)));
+        matches.truncate(limit);
+        matches
     }
-}
 
-impl Map {
+    /// find_solarsystem looks up a system by name, straight to its SolarSystemIndex
     #[inline]
-    pub fn get_solarsystem_id_by_name(&self, name: &str) -> Option<SolarSystemId> {
-        self.name_to_id.get(name).map(|i| SolarSystemId(*i))
+    pub fn find_solarsystem(&self, name: &str) -> Result<SolarSystemIndex, NameLookupError> {
+        let id = self.get_solarsystem_id_by_name(name)?;
+        Ok(self.system_id_to_index[&id])
+    }
+
+    /// system_info_by_name is the details of the system called `name`, in one call. None if there's
+    /// no such system, or if more than one system has the name (see get_solarsystem_id_by_name).
+    pub fn system_info_by_name(&self, name: &str) -> Option<&SolarSystemEx> {
+        let i = self.find_solarsystem(name).ok()?;
+        Some(self.get_extended_solarsystem_info(&i))
+    }
+
+    /// major_hubs are the systems of the five main trade hubs in MAJOR_HUBS, in that order
+    pub fn major_hubs(&self) -> Result<Vec<SolarSystemIndex>, NameLookupError> {
+        self.find_hubs(&MAJOR_HUBS)
+    }
+
+    /// find_hubs looks up each of `names`, for a list of hubs other than MAJOR_HUBS. A hub that
+    /// doesn't resolve is an error rather than being left out, since it usually means the map
+    /// isn't from the standard SDE. Routing to the nearest hub is then Map::k_nearest with k = 1.
+    pub fn find_hubs(&self, names: &[&str]) -> Result<Vec<SolarSystemIndex>, NameLookupError> {
+        names.iter().map(|name| self.find_solarsystem(name)).collect()
+    }
+
+    /// name_of is the name of a region, constellation or system by its id. Names are kept for
+    /// lookups the other way, so this searches them all; it's for the odd message, not for loops.
+    pub fn name_of(&self, id: u64) -> Option<&str> {
+        self.name_to_id
+            .iter()
+            .find(|(_, ids)| ids.contains(&id))
+            .map(|(name, _)| name.as_str())
     }
 
     #[inline]
@@ -235,10 +495,140 @@ impl Map {
     }
 
     #[inline]
-    pub fn get_neighbours(&self, i: &SolarSystemIndex) -> Box<dyn Iterator<Item=SolarSystemIndex> + '_> {
+    pub fn get_neighbours(&self, i: &SolarSystemIndex) -> NeighbourIter<'_> {
         self.get_system(i).get_neighbours()
     }
 
+    /// get_neighbours_filtered is get_neighbours_sorted without the systems for which `avoid` is
+    /// true. The predicate is applied lazily as the neighbours are iterated, so expanding a system
+    /// while avoiding some doesn't allocate.
+    #[inline]
+    pub fn get_neighbours_filtered<'a, AvoidFn>(
+        &'a self,
+        i: &SolarSystemIndex,
+        avoid: AvoidFn,
+    ) -> impl Iterator<Item = SolarSystemIndex> + 'a
+    where
+        AvoidFn: Fn(&SolarSystemIndex) -> bool + 'a,
+    {
+        self.get_neighbours_sorted(i).filter(move |n| !avoid(n))
+    }
+
+    /// get_neighbours_sorted is get_neighbours in SolarSystemIndex (and so SolarSystemId) order,
+    /// rather than the order the stargates happened to be read in. Searches expanding neighbours
+    /// in this order give the same result for the same SDE every time.
+    #[inline]
+    pub fn get_neighbours_sorted(&self, i: &SolarSystemIndex) -> SortedNeighbourIter<'_> {
+        SortedNeighbourIter {
+            neighbours: self.get_neighbours(i),
+            last: None,
+            repeats: 0,
+        }
+    }
+
+    /// neighbours_as_ids is the adjacency of a system entirely in SolarSystemIds, for consumers
+    /// that don't know about SolarSystemIndex: the systems `id` has a jump to, in id order. None
+    /// if there's no system `id` in the map.
+    pub fn neighbours_as_ids(&self, id: &SolarSystemId) -> Option<Vec<SolarSystemId>> {
+        let i = self.system_id_to_index.get(id)?;
+        Some(self.get_neighbours_by_id(i).map(|(id, _)| id).collect())
+    }
+
+    /// get_neighbours_by_id is get_neighbours along with each neighbour's SolarSystemId, in id order.
+    /// For a freshly built map that's the order of get_neighbours_sorted too, but a patch appends
+    /// systems out of id order. Exporters use this so the same jumps are written the same way.
+    pub fn get_neighbours_by_id(&self, i: &SolarSystemIndex) -> impl Iterator<Item = (SolarSystemId, SolarSystemIndex)> {
+        let mut neighbours: Vec<(SolarSystemId, SolarSystemIndex)> =
+            self.get_neighbours(i).map(|n| (self.get_system(&n).solar_system_id, n)).collect();
+        neighbours.sort_unstable();
+        neighbours.into_iter()
+    }
+
+    /// systems_exceeding_inplace are the systems with too many jumps for the in place array of
+    /// NUM_IN_PLACE_JUMPS, whose neighbours are in a separately allocated Vec, and how many jumps
+    /// they have. It's there to measure how often the in place array helps.
+    pub fn systems_exceeding_inplace(&self) -> Vec<(SolarSystemIndex, usize)> {
+        self.systems
+            .iter()
+            .enumerate()
+            .filter(|(_, ss)| matches!(ss.neighbours, Neighbours::Vec(_)))
+            .map(|(i, ss)| (SolarSystemIndex(i as RawIndex), ss.neighbours.len()))
+            .collect()
+    }
+
+    /// stargate is the stargate with the id `id`: the system it's in, and the gate it leads to
+    pub fn stargate(&self, id: u64) -> Option<&StargateData> {
+        self.stargates.get(&id)
+    }
+
+    /// destination_through_gate is the system on the far side of the stargate `stargate_id` in
+    /// `system`, eg. to follow a "jump to gate" from the game's logs. None if the system has no
+    /// such gate. Gates taken out with remove_edge still lead where they did.
+    pub fn destination_through_gate(&self, system: SolarSystemIndex, stargate_id: u64) -> Option<SolarSystemIndex> {
+        self.get_extended_solarsystem_info(&system)
+            .gates
+            .iter()
+            .find(|g| g.stargate_id == Some(stargate_id))
+            .map(|g| g.destination)
+    }
+
+    /// get_predecessors is the reverse of get_neighbours: the systems that have a jump into `i`
+    #[inline]
+    pub fn get_predecessors(&self, i: &SolarSystemIndex) -> impl Iterator<Item = SolarSystemIndex> + '_ {
+        self.predecessors[usize::from(*i)].iter().copied()
+    }
+
+    /// region_border_systems are the systems in the region with a jump to another region, ie. the
+    /// ways in and out of the region, in SolarSystemIndex order
+    pub fn region_border_systems(&self, region_id: u64) -> Vec<SolarSystemIndex> {
+        let region_of = |i: &SolarSystemIndex| self.get_extended_solarsystem_info(i).region_id;
+        (0..self.system_count())
+            .map(|i| SolarSystemIndex(i as RawIndex))
+            .filter(|i| region_of(i) == region_id)
+            .filter(|i| self.get_neighbours(i).any(|n| region_of(&n) != region_id))
+            .collect()
+    }
+
+    /// region_adjacency is the pairs of regions with a jump from a system in the first into one in
+    /// the second, sorted and without repeats. Stargates come in pairs, so each pair of adjacent
+    /// regions is normally there both ways round.
+    pub fn region_adjacency(&self) -> Vec<(u64, u64)> {
+        let region_of = |i: &SolarSystemIndex| self.get_extended_solarsystem_info(i).region_id;
+        let mut pairs: Vec<(u64, u64)> = (0..self.system_count())
+            .map(|i| SolarSystemIndex(i as RawIndex))
+            .flat_map(|i| self.get_neighbours(&i).map(move |n| (region_of(&i), region_of(&n))))
+            .filter(|(a, b)| a != b)
+            .collect();
+        pairs.sort_unstable();
+        pairs.dedup();
+        pairs
+    }
+
+    /// adjacency_matrix builds a dense matrix for the given systems, where `m[i][j]` is true if
+    /// there's a jump from `systems[i]` to `systems[j]`. Systems outside the subset are ignored.
+    /// This is O(n²) in memory, so is intended for small subsets (eg. a constellation) to hand
+    /// to external solvers, not for the whole map.
+    pub fn adjacency_matrix(&self, systems: &[SolarSystemIndex]) -> Vec<Vec<bool>> {
+        let matrix_index: HashMap<SolarSystemIndex, usize> = systems
+            .iter()
+            .enumerate()
+            .map(|(i, s)| (*s, i))
+            .collect();
+
+        systems
+            .iter()
+            .map(|s| {
+                let mut row = vec![false; systems.len()];
+                for n in self.get_neighbours(s) {
+                    if let Some(j) = matrix_index.get(&n) {
+                        row[*j] = true;
+                    }
+                }
+                row
+            })
+            .collect()
+    }
+
     #[inline]
     pub fn get_extended_solarsystem_info(&self, system_index: &SolarSystemIndex) -> &SolarSystemEx {
         let a = &self.extended_systems;
@@ -252,6 +642,7 @@ type IdNameType = (u64, String, MapType);
 #[derive(serde::Serialize, serde::Deserialize)]
 struct Gate {
     destination: u64,
+    position: Option<[f64; 3]>,
 }
 
 /// This is the union of all the fields that we're interested in from all the different universe yaml files
@@ -266,6 +657,11 @@ struct UnionSystemData {
     #[serde(rename = "regionID")]
     region_id: Option<u64>,
 
+    security: Option<f64>,
+
+    /// the position of the system within the universe
+    center: Option<[f64; 3]>,
+
     stargates: Option<HashMap<u64, Gate>>
 }
 
@@ -315,6 +711,8 @@ pub fn parse(
             StelarItemType::SolarSystem => SolarSystem {
                 constellation: path_item(&path, 2).context("unable to get constellation parent of SolarSystem")?,
                 region: path_item(&path, 3).context("unable to region parent of SolarSystem")?,
+                security: yaml_value.security.ok_or(eyre!("file did not contain \"security\" field"))?,
+                center: yaml_value.center,
             },
             StelarItemType::Constellation => Constellation {
                 region: path_item(&path, 2).context("unable to get region parent of constellation")?,
@@ -336,6 +734,7 @@ pub fn parse(
                 stargate_id,
                 solar_system_id: ssid,
                 destination_stargate_id: gate.destination,
+                position: gate.position,
             })
         }
     }
@@ -350,11 +749,132 @@ impl Display for SolarSystemId {
 }
 
 impl SolarSystemMapItem {
-    pub fn get_neighbours(&self) -> Box<dyn Iterator<Item = SolarSystemIndex> + '_> {
-        match self.neighbours.get() {
-            Some(Neighbours::Vec(v)) => Box::new(v.iter().copied()),
-            Some(Neighbours::InPlace(a)) => Box::new(a.iter().filter_map(|n| *n)),
-            None => Box::new(std::iter::empty()),
+    #[inline]
+    pub fn get_neighbours(&self) -> NeighbourIter<'_> {
+        match &self.neighbours {
+            Neighbours::Vec(v) => NeighbourIter::Vec(v.iter()),
+            Neighbours::InPlace(a) => NeighbourIter::InPlace(a.iter()),
+        }
+    }
+}
+
+/// NeighbourIter iterates over either variant of Neighbours. Returning it rather than a boxed
+/// iterator saves a heap allocation and dynamic dispatch every time a system is expanded.
+#[derive(Debug, Clone)]
+pub enum NeighbourIter<'a> {
+    InPlace(std::slice::Iter<'a, Option<SolarSystemIndex>>),
+    Vec(std::slice::Iter<'a, SolarSystemIndex>),
+}
+
+impl Iterator for NeighbourIter<'_> {
+    type Item = SolarSystemIndex;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            NeighbourIter::InPlace(i) => i.find_map(|n| *n),
+            NeighbourIter::Vec(i) => i.next().copied(),
+        }
+    }
+}
+
+/// SortedNeighbourIter yields the neighbours in SolarSystemIndex order without sorting them into a
+/// Vec, which would allocate for every system expanded. Each step scans the neighbours for the
+/// next smallest, which is quadratic, but a system has a handful of stargates at most.
+#[derive(Debug, Clone)]
+pub struct SortedNeighbourIter<'a> {
+    neighbours: NeighbourIter<'a>,
+    /// the neighbour yielded last
+    last: Option<SolarSystemIndex>,
+    /// how many times `last` has been yielded, in case there's more than one jump to it
+    repeats: usize,
+}
+
+impl Iterator for SortedNeighbourIter<'_> {
+    type Item = SolarSystemIndex;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(last) = self.last {
+            if self.neighbours.clone().filter(|n| *n == last).count() > self.repeats {
+                self.repeats += 1;
+                return Some(last);
+            }
+        }
+
+        let next = self.neighbours.clone().filter(|n| self.last.is_none_or(|last| *n > last)).min()?;
+        self.last = Some(next);
+        self.repeats = 1;
+        Some(next)
+    }
+}
+
+impl SolarSystemEx {
+    /// security_true is the security status as the game mechanics see it.
+    /// This is what decides if a system is high-sec, and what SecurityClass uses.
+    #[inline]
+    pub fn security_true(&self) -> f32 {
+        self.security as f32
+    }
+
+    /// security_rounded is the security status as displayed in game: rounded half up to one
+    /// decimal place, with negative values displayed as 0.0.
+    /// NB: This means that a true 0.45 displays as 0.5 but is low-sec, so don't use it for routing.
+    #[inline]
+    pub fn security_rounded(&self) -> f32 {
+        ((self.security.max(0.0) * 10.0).round() / 10.0) as f32
+    }
+
+    /// security_class is the high/low/null-sec class of the system, from the true security
+    #[inline]
+    pub fn security_class(&self) -> SecurityClass {
+        SecurityClass::from_security(self.security_true())
+    }
+}
+
+impl Neighbours {
+    /// len is the number of jumps out of the system
+    pub fn len(&self) -> usize {
+        match self {
+            Neighbours::InPlace(a) => a.iter().flatten().count(),
+            Neighbours::Vec(v) => v.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// remove takes `n` out of the neighbours, returning false if it wasn't there
+    pub fn remove(&mut self, n: SolarSystemIndex) -> bool {
+        match self {
+            Neighbours::InPlace(a) => match a.iter_mut().find(|x| **x == Some(n)) {
+                Some(slot) => {
+                    *slot = None;
+                    true
+                }
+                None => false,
+            },
+            Neighbours::Vec(v) => {
+                let len = v.len();
+                v.retain(|x| *x != n);
+                v.len() != len
+            }
+        }
+    }
+
+    /// insert adds `n` to the neighbours, moving to a Vec if the in place array is full
+    pub fn insert(&mut self, n: SolarSystemIndex) {
+        match self {
+            Neighbours::InPlace(a) => match a.iter_mut().find(|x| x.is_none()) {
+                Some(slot) => *slot = Some(n),
+                None => {
+                    let mut v: Vec<SolarSystemIndex> = a.iter().filter_map(|x| *x).collect();
+                    v.push(n);
+                    *self = Neighbours::Vec(v);
+                }
+            },
+            Neighbours::Vec(v) => v.push(n),
         }
     }
 }
@@ -390,6 +910,8 @@ pub enum MapType {
     SolarSystem {
         region: String,
         constellation: String,
+        security: f64,
+        center: Option<[f64; 3]>,
     },
 }
 
@@ -397,4 +919,368 @@ impl MapType {
     pub fn is_solarsystem(&self) -> bool {
         matches!(self, MapType::SolarSystem {..})
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{self, sde_map, system};
+
+    #[test]
+    fn from_zip_bytes_builds_the_embedded_fixture() {
+        let map = Map::from_zip_bytes(fixtures::SDE_ZIP).unwrap();
+        assert_eq!(map.system_count(), 11);
+        let jita = map.get_extended_solarsystem_info(&system(&map, "Jita"));
+        assert_eq!(jita.solar_system_id, SolarSystemId(30000008));
+        assert_eq!(map.get_neighbours(&system(&map, "Jita")).count(), 3);
+    }
+
+    #[test]
+    fn from_zip_bytes_finds_no_systems_in_bytes_that_are_not_a_zip() {
+        // SdeZipReader stops at the first entry it can't read, like the end of a truncated download
+        let map = Map::from_zip_bytes(b"not a zip").unwrap();
+        assert_eq!(map.system_count(), 0);
+    }
+
+    #[test]
+    fn from_zip_bytes_routes_like_the_fixture_describes() {
+        let map = sde_map();
+        let route = map.shortest_route(system(&map, "Jita"), system(&map, "Amarr")).unwrap();
+        assert_eq!(fixtures::names(&map, &route), ["Jita", "Yulai", "Amarr"]);
+    }
+
+    #[test]
+    fn get_predecessors_mirror_the_neighbours_of_stargate_pairs() {
+        let map = sde_map();
+        for i in 0..map.system_count() {
+            let i = SolarSystemIndex(i as RawIndex);
+            let mut neighbours: Vec<_> = map.get_neighbours(&i).collect();
+            let mut predecessors: Vec<_> = map.get_predecessors(&i).collect();
+            neighbours.sort();
+            predecessors.sort();
+            assert_eq!(neighbours, predecessors);
+        }
+    }
+
+    #[test]
+    fn get_predecessors_follow_one_way_jumps_backwards() {
+        let mut builder = crate::builder::MapBuilder::new();
+        builder.add_system(30_000_000, "A", 2, 1, 1.0).add_system(30_000_001, "B", 2, 1, 1.0);
+        builder.add_jump(30_000_000, 30_000_001, None);
+        let map = builder.build().unwrap();
+        let (a, b) = (SolarSystemIndex(0), SolarSystemIndex(1));
+        assert_eq!(map.get_predecessors(&b).collect::<Vec<_>>(), [a]);
+        assert_eq!(map.get_predecessors(&a).count(), 0);
+    }
+
+    #[test]
+    fn adjacency_matrix_marks_the_jumps_within_the_subset() {
+        let map = sde_map();
+        let subset = [system(&map, "Jita"), system(&map, "Perimeter"), system(&map, "Amarr")];
+        // Jita's jump to Yulai is outside the subset, so dropped
+        assert_eq!(
+            map.adjacency_matrix(&subset),
+            [[false, true, false], [true, false, false], [false, false, false]]
+        );
+        assert!(map.adjacency_matrix(&[]).is_empty());
+    }
+
+    #[test]
+    fn security_rounded_is_for_display_and_security_class_uses_the_true_value() {
+        let map = sde_map();
+        let ashab = map.get_extended_solarsystem_info(&system(&map, "Ashab"));
+        assert_eq!(ashab.security_true(), 0.45);
+        assert_eq!(ashab.security_rounded(), 0.5);
+        assert_eq!(ashab.security_class(), SecurityClass::Low);
+
+        let ec_p8r = map.get_extended_solarsystem_info(&system(&map, "EC-P8R"));
+        assert_eq!(ec_p8r.security_rounded(), 0.0);
+        assert_eq!(ec_p8r.security_class(), SecurityClass::Null);
+    }
+
+    #[test]
+    fn get_neighbours_sorted_ignores_the_order_gates_were_read_in() {
+        let systems = [("A", 1.0), ("B", 1.0), ("C", 1.0), ("D", 1.0)];
+        let forwards = fixtures::map_from_edges(&systems, &[(0, 1), (0, 2), (1, 3), (2, 3)]);
+        let backwards = fixtures::map_from_edges(&systems, &[(2, 3), (1, 3), (0, 2), (0, 1)]);
+        let (a, b, c, d) = (SolarSystemIndex(0), SolarSystemIndex(1), SolarSystemIndex(2), SolarSystemIndex(3));
+
+        assert_eq!(backwards.get_neighbours(&a).collect::<Vec<_>>(), [c, b]);
+        assert_eq!(backwards.get_neighbours_sorted(&a).collect::<Vec<_>>(), [b, c]);
+        // with two routes of equal length, both maps take the same one
+        assert_eq!(forwards.shortest_route(a, d).unwrap(), backwards.shortest_route(a, d).unwrap());
+    }
+
+    #[test]
+    fn get_solarsystem_id_by_name_reports_every_system_sharing_a_name() {
+        let mut builder = crate::builder::MapBuilder::new();
+        builder.add_region(1, "West").add_region(3, "East").add_constellation(2, "Solo");
+        builder.add_system(30_000_001, "Twin", 2, 1, 1.0).add_system(30_000_002, "Twin", 2, 3, 1.0);
+        builder.add_system(30_000_003, "Solo", 2, 1, 1.0);
+        let map = builder.build().unwrap();
+
+        let ambiguous = NameLookupError::Ambiguous(AmbiguousName {
+            name: "Twin".to_string(),
+            candidates: vec![(SolarSystemId(30_000_001), 1), (SolarSystemId(30_000_002), 3)],
+        });
+        assert_eq!(map.get_solarsystem_id_by_name("Twin"), Err(ambiguous.clone()));
+        assert_eq!(
+            ambiguous.to_string(),
+            "more than one solar system is named Twin: 30000001 (region 1) 30000002 (region 3)"
+        );
+        // the constellation called Solo isn't a solar system, so doesn't make the name ambiguous
+        assert_eq!(map.get_solarsystem_id_by_name("Solo"), Ok(SolarSystemId(30_000_003)));
+        assert_eq!(map.get_solarsystem_id_by_name("West"), Err(NameLookupError::NotFound("West".to_string())));
+    }
+
+    #[test]
+    fn new_filtered_keeps_only_the_named_regions() {
+        let mut reader = SdeZipReader::new(io::Cursor::new(fixtures::SDE_ZIP));
+        let map = Map::new_filtered(&mut reader, &["Domain"]).unwrap();
+        assert_eq!(map.system_count(), 5);
+        assert!(map.find_solarsystem("Jita").is_err());
+
+        // Ashab's gate to Tama in The Citadel is pruned
+        let ashab = system(&map, "Ashab");
+        assert_eq!(map.get_neighbours(&ashab).collect::<Vec<_>>(), [system(&map, "Niarja")]);
+        let route = map.shortest_route(system(&map, "Amarr"), ashab).unwrap();
+        assert_eq!(fixtures::names(&map, &route), ["Amarr", "Sarum Prime", "Niarja", "Ashab"]);
+    }
+
+    #[test]
+    fn map_is_shared_between_threads() {
+        let map = sde_map();
+        let (jita, amarr) = (system(&map, "Jita"), system(&map, "Amarr"));
+        let expected = map.shortest_route(jita, amarr).unwrap();
+        std::thread::scope(|scope| {
+            let searches: Vec<_> = (0..4).map(|_| scope.spawn(|| map.shortest_route(jita, amarr))).collect();
+            for search in searches {
+                assert_eq!(search.join().unwrap().unwrap(), expected);
+            }
+        });
+    }
+
+    #[test]
+    fn get_neighbours_sorted_is_get_neighbours_sorted_keeping_repeated_jumps() {
+        // A has more jumps than fit in place, read out of order, and two stargates to D
+        let systems = [("A", 1.0), ("B", 1.0), ("C", 1.0), ("D", 1.0), ("E", 1.0)];
+        let map = fixtures::map_from_edges(&systems, &[(0, 4), (0, 3), (0, 1), (0, 3), (0, 2)]);
+
+        for i in fixtures::indexes(&map) {
+            let mut expected: Vec<_> = map.get_neighbours(&i).collect();
+            expected.sort_unstable();
+            assert_eq!(map.get_neighbours_sorted(&i).collect::<Vec<_>>(), expected);
+        }
+        let d = SolarSystemIndex(3);
+        assert_eq!(map.get_neighbours_sorted(&SolarSystemIndex(0)).filter(|n| *n == d).count(), 2);
+    }
+
+    #[test]
+    fn systems_with_prefix_ignores_case_and_sorts_by_name() {
+        let map = sde_map();
+        let names = |prefix, limit| -> Vec<String> {
+            map.systems_with_prefix(prefix, limit).iter().map(|(_, n)| n.to_string()).collect()
+        };
+        assert_eq!(names("a", 10), ["Amarr", "Ashab"]);
+        assert_eq!(names("SARUM", 10), ["Sarum Prime"]);
+        assert_eq!(names("", 3), ["Amarr", "Ashab", "EC-P8R"]);
+        assert!(names("Zz", 10).is_empty());
+        assert_eq!(map.systems_with_prefix("jit", 1), [(SolarSystemId(30_000_008), "Jita")]);
+    }
+
+    #[test]
+    fn region_border_systems_are_the_ways_out_of_the_region() {
+        let map = sde_map();
+        let domain = map.get_extended_solarsystem_info(&system(&map, "Amarr")).region_id;
+        assert_eq!(fixtures::names(&map, &map.region_border_systems(domain)), ["Amarr", "Madirmilire", "Ashab"]);
+
+        let pure_blind = map.get_extended_solarsystem_info(&system(&map, "Isolated")).region_id;
+        assert_eq!(fixtures::names(&map, &map.region_border_systems(pure_blind)), ["EC-P8R"]);
+        assert!(map.region_border_systems(0).is_empty());
+    }
+
+    #[test]
+    fn new_from_sources_lets_a_later_source_override_and_extend_an_earlier_one() {
+        let system_file = |id: u64, gates: &str| {
+            format!("solarSystemID: {id}\nsecurity: -0.3\ncenter:\n- 0\n- 0\n- 0\nstargates:{gates}\n").into_bytes()
+        };
+        let path = |name: &str| format!("sde/fsd/universe/eve/Pure Blind/Cold/{name}/solarsystem.staticdata");
+        // Isolated gets a gate to the new Overlay system, which has one back and one into Jita
+        let overlay = vec![
+            (path("Isolated"), system_file(30_000_011, "\n  60000001:\n    destination: 60000002\n")),
+            (
+                path("Overlay"),
+                system_file(
+                    31_000_000,
+                    "\n  60000002:\n    destination: 60000001\n  60000003:\n    destination: 50000016\n",
+                ),
+            ),
+            ("sde/other.yaml".to_string(), b"ignored: true".to_vec()),
+        ];
+
+        let mut base = crate::sde::SdeZipReader::new(io::Cursor::new(fixtures::SDE_ZIP));
+        let mut overlay = overlay.into_iter();
+        let map = Map::new_from_sources(&mut [&mut base, &mut overlay]).unwrap();
+
+        assert_eq!(map.system_count(), 12);
+        assert_eq!(map.sde_version(), None);
+        let (isolated, jita) = (system(&map, "Isolated"), system(&map, "Jita"));
+        let route = map.shortest_route(isolated, jita).unwrap();
+        assert_eq!(fixtures::names(&map, &route), ["Isolated", "Overlay", "Jita"]);
+        // Jita wasn't overridden, so there's no gate back
+        assert_eq!(map.shortest_route(jita, isolated), Err(crate::astar::AStarError::PathNotFound));
+    }
+
+    #[test]
+    fn destination_through_gate_follows_a_stargate_of_the_system() {
+        let mut map = sde_map();
+        let (jita, yulai, amarr) = (system(&map, "Jita"), system(&map, "Yulai"), system(&map, "Amarr"));
+        assert_eq!(map.destination_through_gate(jita, 50_000_017), Some(yulai));
+        assert_eq!(map.destination_through_gate(yulai, 50_000_024), Some(amarr));
+        // a gate of another system
+        assert_eq!(map.destination_through_gate(jita, 50_000_024), None);
+
+        map.remove_edge(jita, yulai);
+        assert_eq!(map.destination_through_gate(jita, 50_000_017), Some(yulai));
+    }
+
+    #[test]
+    fn systems_exceeding_inplace_are_those_with_more_jumps_than_fit() {
+        assert!(sde_map().systems_exceeding_inplace().is_empty());
+
+        let systems = [("Hub", 1.0), ("A", 1.0), ("B", 1.0), ("C", 1.0), ("D", 1.0)];
+        let map = fixtures::map_from_edges(&systems, &[(0, 1), (0, 2), (0, 3), (0, 4), (1, 2)]);
+        assert_eq!(map.systems_exceeding_inplace(), [(SolarSystemIndex(0), NUM_IN_PLACE_JUMPS + 1)]);
+    }
+
+    #[test]
+    fn system_info_by_name_and_name_of() {
+        let map = sde_map();
+        let ashab = map.system_info_by_name("Ashab").unwrap();
+        assert_eq!(ashab.solar_system_id, SolarSystemId(30_000_005));
+        assert_eq!(map.name_of(ashab.region_id), Some("Domain"));
+        assert_eq!(map.name_of(ashab.constellation_id), Some("Kador"));
+        assert_eq!(map.name_of(30_000_005), Some("Ashab"));
+        assert_eq!(map.name_of(1), None);
+        assert!(map.system_info_by_name("Nowhere").is_none());
+        // a region's name isn't a system
+        assert!(map.system_info_by_name("Domain").is_none());
+        let twins = crate::fixtures::map_from_edges(&[("Twin", 1.0), ("Twin", 0.5)], &[]);
+        assert!(twins.system_info_by_name("Twin").is_none());
+    }
+
+    #[test]
+    fn index_from_u16_is_checked_against_the_system_count() {
+        let map = sde_map();
+        assert_eq!(map.index_from_u16(0), Some(SolarSystemIndex(0)));
+        assert_eq!(map.index_from_u16(10), Some(SolarSystemIndex(10)));
+        assert_eq!(map.index_from_u16(11), None);
+        assert_eq!(map.index_from_u16(u16::MAX), None);
+    }
+
+    #[test]
+    fn major_hubs_are_looked_up_in_order() {
+        let systems: Vec<(&str, f64)> = ["Rens", "Hek", "Jita", "Dodixie", "Amarr"].iter().map(|n| (*n, 1.0)).collect();
+        let map = fixtures::map_from_edges(&systems, &[]);
+        let hubs = map.major_hubs().unwrap();
+        assert_eq!(fixtures::names(&map, &hubs), MAJOR_HUBS);
+    }
+
+    #[test]
+    fn find_hubs_fails_on_the_first_hub_not_on_the_map() {
+        let map = sde_map();
+        assert_eq!(map.find_hubs(&["Amarr", "Jita"]).unwrap(), [system(&map, "Amarr"), system(&map, "Jita")]);
+        // the fixture has Jita and Amarr, but none of the other hubs
+        assert!(matches!(map.major_hubs(), Err(NameLookupError::NotFound(name)) if name == "Dodixie"));
+    }
+
+    #[test]
+    fn new_in_pool_builds_the_same_map_on_a_single_thread() {
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+        let mut reader = crate::sde::SdeZipReader::new(io::Cursor::new(fixtures::SDE_ZIP));
+        let map = Map::new_in_pool(&mut reader, &pool).unwrap();
+        assert_eq!(map.system_count(), 11);
+        assert_eq!(map.snapshot(), sde_map().snapshot());
+    }
+
+    #[test]
+    fn stargate_looks_up_a_gate_by_id() {
+        let map = sde_map();
+        let gate = map.stargate(50_000_017).unwrap();
+        assert_eq!(gate.solar_system_id, SolarSystemId(30_000_008));
+        assert_eq!(gate.destination_stargate_id, 50_000_018);
+        assert_eq!(map.stargate(50_000_018).unwrap().destination_stargate_id, 50_000_017);
+        assert!(map.stargate(1).is_none());
+    }
+
+    #[test]
+    fn neighbours_as_ids_are_in_id_order() {
+        let map = sde_map();
+        let ids = |ids: &[u64]| ids.iter().map(|i| SolarSystemId(*i)).collect::<Vec<_>>();
+        // Jita's stargates lead to Perimeter, Yulai and EC-P8R
+        assert_eq!(map.neighbours_as_ids(&SolarSystemId(30_000_008)), Some(ids(&[30_000_007, 30_000_009, 30_000_010])));
+        assert_eq!(map.neighbours_as_ids(&SolarSystemId(30_000_011)), Some(vec![]));
+        assert_eq!(map.neighbours_as_ids(&SolarSystemId(1)), None);
+    }
+
+    #[test]
+    fn wiring_report_counts_the_gates_out_of_the_regions_kept() {
+        let map = sde_map();
+        let report = map.wiring_report();
+        assert_eq!((report.stargates_seen, report.destinations_unresolved), (24, 0));
+        assert_eq!((report.destinations_resolved, report.edges_wired), (24, 24));
+
+        // Jita and Perimeter's gates to each other resolve, their other four lead out of The Forge
+        let mut reader = crate::sde::SdeZipReader::new(io::Cursor::new(fixtures::SDE_ZIP));
+        let forge = Map::new_filtered(&mut reader, &["The Forge"]).unwrap();
+        let report = forge.wiring_report();
+        assert_eq!((report.stargates_seen, report.destinations_unresolved), (6, 4));
+        assert_eq!((report.destinations_resolved, report.edges_wired), (2, 2));
+    }
+
+    #[test]
+    fn region_adjacency_lists_regions_joined_by_a_jump_both_ways_round() {
+        let map = sde_map();
+        let region = |name| map.get_extended_solarsystem_info(&system(&map, name)).region_id;
+        let (domain, forge, genesis) = (region("Amarr"), region("Jita"), region("Yulai"));
+        let adjacency = map.region_adjacency();
+        // Forge - Genesis - Domain - Forge, and The Citadel to Domain, The Forge and Pure Blind
+        assert_eq!(adjacency.len(), 14);
+        assert!(adjacency.windows(2).all(|w| w[0] < w[1]));
+        assert!(adjacency.iter().all(|(a, b)| a != b && adjacency.contains(&(*b, *a))));
+        for pair in [(forge, genesis), (genesis, domain), (domain, forge)] {
+            assert!(adjacency.contains(&pair), "{pair:?}");
+        }
+        assert!(fixtures::line_map(3).region_adjacency().is_empty());
+    }
+
+    #[test]
+    fn get_neighbours_filtered_is_get_neighbours_sorted_without_the_avoided() {
+        let map = sde_map();
+        let (jita, yulai) = (system(&map, "Jita"), system(&map, "Yulai"));
+        let sorted: Vec<_> = map.get_neighbours_sorted(&jita).collect();
+        let filtered: Vec<_> = map.get_neighbours_filtered(&jita, |n| *n == yulai).collect();
+        assert_eq!(filtered, sorted.iter().copied().filter(|n| *n != yulai).collect::<Vec<_>>());
+        assert_eq!(filtered.len(), 2);
+        assert_eq!(map.get_neighbours_filtered(&jita, |_| false).collect::<Vec<_>>(), sorted);
+        assert_eq!(map.get_neighbours_filtered(&jita, |_| true).count(), 0);
+    }
+
+    #[cfg(feature = "wide-index")]
+    #[test]
+    fn wide_index_routes_a_map_of_more_than_65536_systems() {
+        let count = 70_000;
+        let map = fixtures::line_map(count);
+        assert_eq!(map.system_count(), count);
+
+        let last = map.index_from_raw((count - 1) as RawIndex).unwrap();
+        assert_eq!(map.get_extended_solarsystem_info(&last).name, format!("S{}", count - 1));
+        assert_eq!(map.index_from_u16(u16::MAX), Some(SolarSystemIndex(u16::MAX as RawIndex)));
+
+        let first = system(&map, "S0");
+        let route = map.shortest_route(first, last).unwrap();
+        assert_eq!(route.len(), count);
+        assert_eq!(route.last(), Some(&last));
+        assert_eq!(map.jumps_between(&first, &last), Some(count as u32 - 1));
+    }
+}
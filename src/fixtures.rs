@@ -0,0 +1,65 @@
+//! Maps for the unit tests: the small SDE fixture that examples/route.rs reads too, and synthetic
+//! maps built with MapBuilder.
+//!
+//! The fixture has 11 systems (security in brackets) in 5 regions:
+//!
+//! ```text
+//! Amarr (1.0) - Sarum Prime (0.9) - Niarja (0.5) - Madirmilire (0.6) - Perimeter (0.95) - Jita (0.9)
+//! Niarja - Ashab (0.45) - Tama (0.3) - Perimeter      Tama - EC-P8R (-0.1) - Jita
+//! Jita - Yulai (1.0) - Amarr                          Isolated (-0.3), with no stargates
+//! ```
+//!
+//! Amarr, Sarum Prime and Niarja are in Domain's Throne Worlds, Madirmilire and Ashab in Domain's
+//! Kador, Jita and Perimeter in The Forge, Yulai in Genesis, Tama in The Citadel, and EC-P8R and
+//! Isolated in Pure Blind.
+use crate::builder::MapBuilder;
+use crate::evemap::{Map, RawIndex, SolarSystemIndex};
+
+/// SDE_ZIP is the fixture SDE, embedded so tests don't depend on the working directory
+pub(crate) const SDE_ZIP: &[u8] = include_bytes!("../examples/fixtures/sde.zip");
+
+/// FIRST_ID is the SolarSystemId of the first system of a map_from_edges map
+pub(crate) const FIRST_ID: u64 = 30_000_000;
+
+/// sde_map is the map of the fixture SDE
+pub(crate) fn sde_map() -> Map {
+    Map::from_zip_bytes(SDE_ZIP).expect("the fixture SDE builds")
+}
+
+/// system is the index of the system called `name`, which the test expects to exist
+pub(crate) fn system(map: &Map, name: &str) -> SolarSystemIndex {
+    map.find_solarsystem(name).unwrap_or_else(|e| panic!("no system {name}: {e}"))
+}
+
+/// indexes are all the systems of the map, in SolarSystemIndex order
+pub(crate) fn indexes(map: &Map) -> Vec<SolarSystemIndex> {
+    (0..map.system_count()).map(|i| SolarSystemIndex(i as RawIndex)).collect()
+}
+
+/// names are the names of the systems on a route, to compare routes readably
+pub(crate) fn names<'a>(map: &'a Map, route: &[SolarSystemIndex]) -> Vec<&'a str> {
+    route.iter().map(|i| map.get_extended_solarsystem_info(i).name.as_str()).collect()
+}
+
+/// map_from_edges builds a map of the named systems, each with its security, all in constellation
+/// 2 of region 1, joined by a pair of stargates for each (a, b) of indexes into `systems`. System
+/// `i` has the SolarSystemId FIRST_ID + i, so for a fresh map its SolarSystemIndex is `i` too.
+pub(crate) fn map_from_edges(systems: &[(&str, f64)], edges: &[(usize, usize)]) -> Map {
+    let mut builder = MapBuilder::new();
+    builder.add_region(1, "Region").add_constellation(2, "Constellation");
+    for (i, (name, security)) in systems.iter().enumerate() {
+        builder.add_system(FIRST_ID + i as u64, name, 2, 1, *security);
+    }
+    for (a, b) in edges {
+        builder.connect(FIRST_ID + *a as u64, FIRST_ID + *b as u64);
+    }
+    builder.build().expect("a synthetic map builds")
+}
+
+/// line_map is map_from_edges for `n` high-sec systems S0 to S{n-1}, each joined to the next
+pub(crate) fn line_map(n: usize) -> Map {
+    let names: Vec<String> = (0..n).map(|i| format!("S{i}")).collect();
+    let systems: Vec<(&str, f64)> = names.iter().map(|n| (n.as_str(), 1.0)).collect();
+    let edges: Vec<(usize, usize)> = (1..n).map(|i| (i - 1, i)).collect();
+    map_from_edges(&systems, &edges)
+}
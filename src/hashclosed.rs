@@ -0,0 +1,77 @@
+use crate::astar::{ClosedList, ClosedListState};
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// HashClosed is a closed list backed by a HashMap, so it only holds the nodes a search touches.
+/// Unlike SimpleClosed it doesn't need contiguous node ids or to know the node count up front,
+/// which suits small searches and generic graphs. Nodes that aren't in it are Unvisited.
+pub struct HashClosed<Node, Cost> {
+    states: HashMap<Node, ClosedListState<Node, Cost>>,
+    // Index has to return a reference, so absent nodes get a reference to this
+    unvisited: ClosedListState<Node, Cost>,
+}
+
+impl<Node: Eq + Hash, Cost> HashClosed<Node, Cost> {
+    pub fn new() -> Self {
+        HashClosed {
+            states: HashMap::new(),
+            unvisited: ClosedListState::Unvisited,
+        }
+    }
+}
+
+impl<Node: Eq + Hash, Cost> Default for HashClosed<Node, Cost> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Node: Eq + Hash, Cost> std::ops::Index<Node> for HashClosed<Node, Cost> {
+    type Output = ClosedListState<Node, Cost>;
+
+    fn index(&self, index: Node) -> &Self::Output {
+        self.states.get(&index).unwrap_or(&self.unvisited)
+    }
+}
+
+impl<Node: Eq + Hash, Cost> std::ops::IndexMut<Node> for HashClosed<Node, Cost> {
+    fn index_mut(&mut self, index: Node) -> &mut Self::Output {
+        self.states.entry(index).or_insert(ClosedListState::Unvisited)
+    }
+}
+
+// Implement the super-trait
+impl<Node: Copy + Eq + Hash, Cost> ClosedList<Node, Cost> for HashClosed<Node, Cost> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::astar::{self, OpenItem, OpenList};
+    use crate::simpleopen::SimpleOpenList;
+
+    #[test]
+    fn nodes_never_indexed_are_unvisited() {
+        let mut closed: HashClosed<u64, u32> = HashClosed::new();
+        assert!(matches!(closed[7], ClosedListState::Unvisited));
+        closed[7] = ClosedListState::StartingPoint(0);
+        assert!(matches!(closed[7], ClosedListState::StartingPoint(0)));
+        assert!(matches!(closed[u64::MAX], ClosedListState::Unvisited));
+    }
+
+    #[test]
+    fn astar_searches_a_graph_without_contiguous_node_ids() {
+        // from n you can go to n - 1, n + 1 or 2n, and the fewest steps from 1 to 10 are 4
+        let mut open = SimpleOpenList::new();
+        let mut closed = HashClosed::new();
+        open.push_open(OpenItem { heuristic: 0u32, node: 1i64 });
+        closed[1] = ClosedListState::StartingPoint(0);
+
+        let goal = astar::astar(&mut open, &mut closed, |n| *n == 10, |_| 0, |n| {
+            vec![(1, n - 1), (1, n + 1), (1, n * 2)]
+        })
+        .unwrap();
+
+        assert_eq!(closed.unwind(goal), [1, 2, 4, 5, 10]);
+        assert!(matches!(closed[goal], ClosedListState::PathFrom(5, 4)));
+    }
+}
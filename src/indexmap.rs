@@ -0,0 +1,111 @@
+//! IndexMap is a dense array of values indexed by SolarSystemIndex, usually one for every system
+//! in a Map. It saves the `usize::from(idx)` dance of a plain Vec.
+use crate::evemap::{Map, RawIndex, SolarSystemIndex};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexMap<T>(Vec<T>);
+
+impl<T: Clone> IndexMap<T> {
+    /// new creates an IndexMap with `value` for every system in the map
+    pub fn new(map: &Map, value: T) -> Self {
+        Self::with_len(map.system_count(), value)
+    }
+
+    /// with_len creates an IndexMap with `value` for each of the first `len` SolarSystemIndexes
+    pub fn with_len(len: usize, value: T) -> Self {
+        IndexMap(vec![value; len])
+    }
+}
+
+impl<T> IndexMap<T> {
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// get is the checked lookup, None when `i` is beyond the end (eg. it's from a bigger map)
+    #[inline]
+    pub fn get(&self, i: SolarSystemIndex) -> Option<&T> {
+        self.0.get(usize::from(i))
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self, i: SolarSystemIndex) -> Option<&mut T> {
+        self.0.get_mut(usize::from(i))
+    }
+
+    /// get_unchecked skips the bounds check
+    ///
+    /// # Safety
+    /// `i` must be less than len(), which holds for any index from the map the IndexMap was sized to
+    #[inline]
+    pub unsafe fn get_unchecked(&self, i: SolarSystemIndex) -> &T {
+        self.0.get_unchecked(usize::from(i))
+    }
+
+    /// iter yields each SolarSystemIndex and its value, in order
+    pub fn iter(&self) -> impl Iterator<Item = (SolarSystemIndex, &T)> + '_ {
+        self.0.iter().enumerate().map(|(i, v)| (SolarSystemIndex(i as RawIndex), v))
+    }
+}
+
+/// Indexing panics when `i` is beyond the end, like a Vec. See get for the checked version.
+impl<T> std::ops::Index<SolarSystemIndex> for IndexMap<T> {
+    type Output = T;
+
+    #[inline]
+    fn index(&self, i: SolarSystemIndex) -> &Self::Output {
+        &self.0[usize::from(i)]
+    }
+}
+
+impl<T> std::ops::IndexMut<SolarSystemIndex> for IndexMap<T> {
+    #[inline]
+    fn index_mut(&mut self, i: SolarSystemIndex) -> &mut Self::Output {
+        &mut self.0[usize::from(i)]
+    }
+}
+
+impl<T> From<Vec<T>> for IndexMap<T> {
+    /// from takes a Vec whose offsets are already SolarSystemIndexes
+    fn from(values: Vec<T>) -> Self {
+        IndexMap(values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::line_map;
+
+    #[test]
+    fn new_has_a_value_for_every_system() {
+        let map = line_map(3);
+        let mut distances = IndexMap::new(&map, 0u32);
+        assert_eq!(distances.len(), 3);
+        distances[SolarSystemIndex(2)] = 7;
+        *distances.get_mut(SolarSystemIndex(1)).unwrap() += 1;
+        assert_eq!(distances.iter().map(|(_, d)| *d).collect::<Vec<_>>(), [0, 1, 7]);
+        assert_eq!(distances.iter().last(), Some((SolarSystemIndex(2), &7)));
+    }
+
+    #[test]
+    fn get_is_none_beyond_the_end() {
+        let small = IndexMap::from(vec![1, 2]);
+        assert_eq!(small.get(SolarSystemIndex(1)), Some(&2));
+        assert_eq!(small.get(SolarSystemIndex(2)), None);
+        assert!(IndexMap::<u8>::with_len(0, 0).is_empty());
+    }
+
+    #[test]
+    #[should_panic]
+    fn indexing_beyond_the_end_panics() {
+        let small = IndexMap::with_len(2, 0u32);
+        let _ = small[SolarSystemIndex(2)];
+    }
+}
@@ -0,0 +1,153 @@
+//! Jump drive routes for capital ships, which jump straight to any system within range rather than
+//! taking stargates.
+//!
+//! Which systems are in range only depends on their positions and the ship's range, so for repeated
+//! queries at one range it pays to find them once up front, in a JumpGraph.
+use crate::astar;
+use crate::astar::ClosedListState::{PathFrom, StartingPoint, Unvisited};
+use crate::astar::{AStarError, ClosedList, OpenList};
+use crate::evemap::{Map, RawIndex, SolarSystemIndex};
+use crate::security::SecurityClass;
+use crate::simpleclosed::SimpleClosed;
+use crate::simpleopen::SimpleOpenList;
+use rayon::prelude::*;
+
+/// LIGHT_YEAR is a light year in meters, the unit jump drive ranges are given in
+pub const LIGHT_YEAR: f64 = 9_460_730_472_580_800.0;
+
+/// JumpGraph is the jumps a jump drive with a range of `max_ly` light years can make: from each
+/// capital capable system, to every other one in range.
+///
+/// Jump drives can't be used in high-sec or jump into it, so only low and null-sec systems with a
+/// known position take part; every other system has no jumps.
+#[derive(Debug, Clone)]
+pub struct JumpGraph {
+    max_ly: f64,
+    positions: Vec<Option<[f64; 3]>>,
+    neighbours: Vec<Vec<SolarSystemIndex>>,
+}
+
+impl Map {
+    /// build_jump_graph finds every jump within `max_ly` light years. That's a distance between
+    /// each pair of capital capable systems, so it's done once and in parallel.
+    pub fn build_jump_graph(&self, max_ly: f64) -> JumpGraph {
+        let positions: Vec<Option<[f64; 3]>> = self
+            .extended_systems
+            .iter()
+            .map(|ss| ss.position.filter(|_| ss.security_class() != SecurityClass::High))
+            .collect();
+
+        let neighbours = (0..positions.len())
+            .into_par_iter()
+            .map(|i| match positions[i] {
+                Some(from) => positions
+                    .iter()
+                    .enumerate()
+                    .filter(|(j, to)| *j != i && to.is_some_and(|to| light_years(from, to) <= max_ly))
+                    .map(|(j, _)| SolarSystemIndex(j as RawIndex))
+                    .collect(),
+                None => Vec::new(),
+            })
+            .collect();
+
+        JumpGraph {
+            max_ly,
+            positions,
+            neighbours,
+        }
+    }
+}
+
+impl JumpGraph {
+    /// max_ly is the jump range the graph was built for
+    pub fn max_ly(&self) -> f64 {
+        self.max_ly
+    }
+
+    /// neighbours are the systems a jump drive can reach from `i` in a single jump, in
+    /// SolarSystemIndex order
+    pub fn neighbours(&self, i: &SolarSystemIndex) -> &[SolarSystemIndex] {
+        &self.neighbours[usize::from(*i)]
+    }
+
+    /// distance is the light years between two capital capable systems, or None if either isn't
+    pub fn distance(&self, a: &SolarSystemIndex, b: &SolarSystemIndex) -> Option<f64> {
+        Some(light_years(self.positions[usize::from(*a)]?, self.positions[usize::from(*b)]?))
+    }
+
+    /// route finds the route from `from` to `to` with the fewest jumps. The heuristic is the
+    /// straight line distance to `to` in jumps of the full range, which a route can't beat.
+    pub fn route(&self, from: SolarSystemIndex, to: SolarSystemIndex) -> Result<Vec<SolarSystemIndex>, AStarError> {
+        let heuristic = |n: &SolarSystemIndex| {
+            self.distance(n, &to)
+                .map_or(0, |ly| (ly / self.max_ly).ceil() as u32)
+        };
+
+        let mut open = SimpleOpenList::new();
+        let mut closed = SimpleClosed::new(self.neighbours.len());
+        open.push_open(astar::OpenItem {
+            heuristic: heuristic(&from),
+            node: from,
+        });
+        closed[from] = StartingPoint(0u32);
+
+        let goal = astar::astar(
+            &mut open,
+            &mut closed,
+            |n| n == &to,
+            heuristic,
+            |n| self.neighbours(n).iter().map(|j| (1, *j)),
+        )?;
+
+        match closed[goal] {
+            PathFrom(..) | StartingPoint(_) => Ok(closed.unwind(goal)),
+            Unvisited => Err(AStarError::OpenItemNotInClosedList),
+        }
+    }
+}
+
+fn light_years(a: [f64; 3], b: [f64; 3]) -> f64 {
+    let [dx, dy, dz] = [a[0] - b[0], a[1] - b[1], a[2] - b[2]];
+    (dx * dx + dy * dy + dz * dz).sqrt() / LIGHT_YEAR
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{names, sde_map, system};
+
+    #[test]
+    fn positions_are_read_from_the_sde() {
+        let map = sde_map();
+        let jita = map.get_extended_solarsystem_info(&system(&map, "Jita"));
+        assert_eq!(jita.position, Some([6e16, 0.0, 0.0]));
+    }
+
+    #[test]
+    fn jump_graph_only_joins_low_and_null_sec_systems_in_range() {
+        let map = sde_map();
+        let (ashab, tama, ec_p8r) = (system(&map, "Ashab"), system(&map, "Tama"), system(&map, "EC-P8R"));
+        let graph = map.build_jump_graph(1.5);
+
+        assert_eq!(graph.max_ly(), 1.5);
+        assert_eq!(graph.neighbours(&ashab), [tama]);
+        assert_eq!(graph.neighbours(&tama), [ashab, ec_p8r]);
+        assert!(graph.neighbours(&system(&map, "Jita")).is_empty());
+        assert!((graph.distance(&ashab, &tama).unwrap() - 1e16 / LIGHT_YEAR).abs() < 1e-9);
+        assert_eq!(graph.distance(&ashab, &system(&map, "Amarr")), None);
+    }
+
+    #[test]
+    fn jump_graph_route_has_the_fewest_jumps_in_range() {
+        let map = sde_map();
+        let (ashab, ec_p8r, isolated) = (system(&map, "Ashab"), system(&map, "EC-P8R"), system(&map, "Isolated"));
+
+        let route = map.build_jump_graph(1.5).route(ashab, ec_p8r).unwrap();
+        assert_eq!(names(&map, &route), ["Ashab", "Tama", "EC-P8R"]);
+        let route = map.build_jump_graph(2.5).route(ashab, ec_p8r).unwrap();
+        assert_eq!(names(&map, &route), ["Ashab", "EC-P8R"]);
+
+        assert_eq!(map.build_jump_graph(2.5).route(ashab, isolated), Err(AStarError::PathNotFound));
+        assert_eq!(map.build_jump_graph(9.0).route(ashab, isolated).unwrap().len(), 3);
+    }
+}
@@ -0,0 +1,182 @@
+//! More than one route between a pair of systems, for when the shortest isn't the only option
+//! worth showing.
+//!
+//! Routes are always returned in the order defined by compare_routes: by jumps, then by the
+//! sequence of SolarSystemIds, so the same query gives the same routes in the same order.
+use crate::evemap::{Map, SolarSystemIndex};
+use std::cmp::Ordering;
+use std::collections::HashSet;
+
+/// compare_routes is the order of multi-route results: fewest jumps first, and routes with the
+/// same number of jumps ordered by comparing their SolarSystemIds one system at a time.
+/// SolarSystemIds (unlike SolarSystemIndexes) don't depend on how the map was built, so the order
+/// is the same for any map of the same SDE.
+pub fn compare_routes(map: &Map, a: &[SolarSystemIndex], b: &[SolarSystemIndex]) -> Ordering {
+    let ids = |route: &[SolarSystemIndex]| {
+        route
+            .iter()
+            .map(|i| map.get_extended_solarsystem_info(i).solar_system_id)
+            .collect::<Vec<_>>()
+    };
+    a.len().cmp(&b.len()).then_with(|| ids(a).cmp(&ids(b)))
+}
+
+impl Map {
+    /// k_shortest_routes finds up to `k` different routes (without loops) from `from` to `to`
+    /// with the fewest jumps, in the order of compare_routes. Fewer are returned if there aren't
+    /// `k` routes.
+    ///
+    /// This is Yen's algorithm: each route after the first is found by branching off an earlier
+    /// one at each of its systems, with the jumps the earlier routes took from there ruled out.
+    pub fn k_shortest_routes(&self, from: SolarSystemIndex, to: SolarSystemIndex, k: usize) -> Vec<Vec<SolarSystemIndex>> {
+        let mut found: Vec<Vec<SolarSystemIndex>> = Vec::new();
+        if k == 0 {
+            return found;
+        }
+        match self.bfs_route(&from, &to, |_| true) {
+            Some(route) => found.push(route),
+            None => return found,
+        }
+
+        let mut candidates: Vec<Vec<SolarSystemIndex>> = Vec::new();
+        while found.len() < k {
+            let last = &found[found.len() - 1];
+            for spur_at in 0..last.len() - 1 {
+                let root = &last[..=spur_at];
+                let spur = root[spur_at];
+
+                // jumps out of the spur already taken by routes sharing this root
+                let taken: HashSet<SolarSystemIndex> = found
+                    .iter()
+                    .filter(|r| r.len() > spur_at + 1 && r[..=spur_at] == *root)
+                    .map(|r| r[spur_at + 1])
+                    .collect();
+                // and the root's systems can't be visited again
+                let on_root: HashSet<SolarSystemIndex> = root[..spur_at].iter().copied().collect();
+
+                let ruled_out = |a: &SolarSystemIndex, b: &SolarSystemIndex| *a == spur && taken.contains(b);
+                let spur_route = self.bfs_route_jumps(&spur, &to, |a, b| !on_root.contains(b) && !ruled_out(a, b));
+                if let Some(spur_route) = spur_route {
+                    let mut route = root[..spur_at].to_vec();
+                    route.extend(spur_route);
+                    if !candidates.contains(&route) && !found.contains(&route) {
+                        candidates.push(route);
+                    }
+                }
+            }
+
+            let best = candidates
+                .iter()
+                .enumerate()
+                .min_by(|(_, a), (_, b)| compare_routes(self, a, b))
+                .map(|(i, _)| i);
+            match best {
+                Some(i) => found.push(candidates.swap_remove(i)),
+                None => break,
+            }
+        }
+
+        found.sort_by(|a, b| compare_routes(self, a, b));
+        found
+    }
+
+    /// route_stability is how much choice there is in getting from `from` to `to`: the jumps of
+    /// the shortest route over the jumps of the second of k_shortest_routes. 1.0 means there's
+    /// another route just as short, and the further below 1.0, the more the shortest route is
+    /// forced and the longer the way round if it's camped. 0.0 means there's no second route at
+    /// all (or no route), while a route from a system to itself has nothing to camp and is 1.0.
+    pub fn route_stability(&self, from: SolarSystemIndex, to: SolarSystemIndex) -> f64 {
+        if from == to {
+            return 1.0;
+        }
+        match self.k_shortest_routes(from, to, 2).as_slice() {
+            [best, second] => (best.len() - 1) as f64 / (second.len() - 1) as f64,
+            _ => 0.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{line_map, map_from_edges, names, sde_map, system};
+    use crate::route::route_legs;
+
+    #[test]
+    fn k_shortest_routes_are_in_compare_routes_order() {
+        let map = sde_map();
+        let routes = map.k_shortest_routes(system(&map, "Amarr"), system(&map, "Tama"), 5);
+        let routes: Vec<_> = routes.iter().map(|r| names(&map, r)).collect();
+        assert_eq!(
+            routes,
+            [
+                vec!["Amarr", "Sarum Prime", "Niarja", "Ashab", "Tama"],
+                vec!["Amarr", "Yulai", "Jita", "Perimeter", "Tama"],
+                vec!["Amarr", "Yulai", "Jita", "EC-P8R", "Tama"],
+                vec!["Amarr", "Sarum Prime", "Niarja", "Madirmilire", "Perimeter", "Tama"],
+                vec!["Amarr", "Sarum Prime", "Niarja", "Madirmilire", "Perimeter", "Jita", "EC-P8R", "Tama"],
+            ]
+        );
+    }
+
+    #[test]
+    fn k_shortest_routes_are_loopless_and_distinct() {
+        let map = sde_map();
+        let routes = map.k_shortest_routes(system(&map, "Jita"), system(&map, "Ashab"), 100);
+        for (i, route) in routes.iter().enumerate() {
+            let unique: HashSet<_> = route.iter().collect();
+            assert_eq!(unique.len(), route.len(), "{:?} has a loop", names(&map, route));
+            assert!(!routes[..i].contains(route));
+            assert!(route_legs(route).all(|(a, b)| map.get_neighbours(&a).any(|n| n == b)));
+        }
+        assert!(routes.windows(2).all(|w| compare_routes(&map, &w[0], &w[1]) == Ordering::Less));
+    }
+
+    #[test]
+    fn k_shortest_routes_returns_what_there_is() {
+        let map = line_map(4);
+        let (from, to) = (SolarSystemIndex(0), SolarSystemIndex(3));
+        assert_eq!(map.k_shortest_routes(from, to, 3), [map.shortest_route(from, to).unwrap()]);
+        assert!(map.k_shortest_routes(from, to, 0).is_empty());
+
+        let map = sde_map();
+        assert!(map.k_shortest_routes(system(&map, "Jita"), system(&map, "Isolated"), 3).is_empty());
+    }
+
+    #[test]
+    fn compare_routes_puts_fewer_jumps_first_then_lower_ids() {
+        let map = sde_map();
+        let (amarr, yulai, sarum) = (system(&map, "Amarr"), system(&map, "Yulai"), system(&map, "Sarum Prime"));
+        assert_eq!(compare_routes(&map, &[amarr, yulai], &[amarr]), Ordering::Greater);
+        assert_eq!(compare_routes(&map, &[amarr, sarum], &[amarr, yulai]), Ordering::Less);
+        assert_eq!(compare_routes(&map, &[amarr, sarum], &[amarr, sarum]), Ordering::Equal);
+    }
+
+    #[test]
+    fn route_stability_of_a_barbell_is_below_a_cluster() {
+        // two triangles A B C and D E F, joined only by the bridge C - D
+        let systems = [("A", 1.0), ("B", 1.0), ("C", 1.0), ("D", 1.0), ("E", 1.0), ("F", 1.0)];
+        let barbell = map_from_edges(&systems, &[(0, 1), (1, 2), (2, 0), (3, 4), (4, 5), (5, 3), (2, 3)]);
+        // a ring of the same systems, with a chord across it
+        let cluster = map_from_edges(&systems, &[(0, 1), (1, 2), (2, 3), (3, 4), (4, 5), (5, 0), (1, 4)]);
+        let (a, d) = (SolarSystemIndex(0), SolarSystemIndex(3));
+
+        // A - C - D, and the next best has to go round a triangle to reach the same bridge
+        assert_eq!(barbell.route_stability(a, d), 2.0 / 3.0);
+        // A - B - C - D, A - F - E - D and A - B - E - D are all as short
+        assert_eq!(cluster.route_stability(a, d), 1.0);
+        assert!(barbell.route_stability(a, d) < cluster.route_stability(a, d));
+    }
+
+    #[test]
+    fn route_stability_without_a_second_route() {
+        let map = line_map(3);
+        assert_eq!(map.route_stability(SolarSystemIndex(0), SolarSystemIndex(2)), 0.0);
+        assert_eq!(map.route_stability(SolarSystemIndex(1), SolarSystemIndex(1)), 1.0);
+
+        let map = sde_map();
+        assert_eq!(map.route_stability(system(&map, "Jita"), system(&map, "Isolated")), 0.0);
+        // Jita - Yulai - Amarr, or Jita - Perimeter - Madirmilire - Niarja - Sarum Prime - Amarr
+        assert_eq!(map.route_stability(system(&map, "Jita"), system(&map, "Amarr")), 2.0 / 5.0);
+    }
+}
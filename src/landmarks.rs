@@ -0,0 +1,98 @@
+//! Landmark (ALT) heuristics for counting jumps.
+//!
+//! A zero heuristic makes astar a Dijkstra, which explores in every direction. With the jumps from
+//! a few landmark systems to everywhere precomputed, the triangle inequality gives a lower bound on
+//! the jumps between any two systems: d(n, to) >= d(L, to) - d(L, n). That's admissible, so routes
+//! stay shortest, but points the search towards the destination.
+use crate::bfs::DistanceTable;
+use crate::evemap::{Map, SolarSystemIndex};
+
+#[derive(Debug, Clone)]
+pub struct Landmarks {
+    tables: Vec<DistanceTable>,
+}
+
+impl Landmarks {
+    /// new precomputes the jumps from each of the given landmark systems
+    pub fn new(map: &Map, landmarks: &[SolarSystemIndex]) -> Self {
+        Landmarks {
+            tables: landmarks.iter().map(|l| map.distances_from(l)).collect(),
+        }
+    }
+
+    /// select picks `count` landmarks spread out over the map: starting from the first system, each
+    /// next landmark is the system furthest (in jumps) from its nearest landmark so far.
+    /// Landmarks on the edges of the map give the best bounds.
+    pub fn select(map: &Map, count: usize) -> Self {
+        let mut tables: Vec<DistanceTable> = Vec::with_capacity(count);
+        let mut next = (map.system_count() > 0).then_some(SolarSystemIndex(0));
+
+        while let Some(landmark) = next.filter(|_| tables.len() < count) {
+            tables.push(map.distances_from(&landmark));
+            next = tables[tables.len() - 1]
+                .iter()
+                .map(|(i, _)| i)
+                .filter(|i| !tables.iter().any(|t| t.origin() == *i))
+                .max_by_key(|i| tables.iter().filter_map(|t| t.get(i)).min());
+        }
+
+        Landmarks { tables }
+    }
+
+    /// landmarks are the systems distances were precomputed from
+    pub fn landmarks(&self) -> impl Iterator<Item = SolarSystemIndex> + '_ {
+        self.tables.iter().map(|t| t.origin())
+    }
+
+    /// estimate is a lower bound on the jumps from `from` to `to`. Landmarks that can't reach both
+    /// systems say nothing about them, so if none can this is 0.
+    pub fn estimate(&self, from: &SolarSystemIndex, to: &SolarSystemIndex) -> u32 {
+        self.tables
+            .iter()
+            .filter_map(|t| Some(t.get(to)?.saturating_sub(t.get(from)?)))
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{indexes, line_map, sde_map};
+
+    #[test]
+    fn estimate_is_a_lower_bound_on_the_jumps() {
+        let map = sde_map();
+        let landmarks = Landmarks::select(&map, 4);
+        assert_eq!(landmarks.landmarks().count(), 4);
+        let mut exact = 0;
+        for from in indexes(&map) {
+            for to in indexes(&map) {
+                let estimate = landmarks.estimate(&from, &to);
+                match map.jumps_between(&from, &to) {
+                    Some(jumps) => {
+                        assert!(estimate <= jumps);
+                        exact += usize::from(estimate == jumps);
+                    }
+                    None => assert_eq!(estimate, 0),
+                }
+            }
+        }
+        assert!(exact > map.system_count());
+    }
+
+    #[test]
+    fn select_spreads_the_landmarks_to_the_ends_of_the_map() {
+        let map = line_map(10);
+        let landmarks = Landmarks::select(&map, 2);
+        assert_eq!(landmarks.landmarks().collect::<Vec<_>>(), [SolarSystemIndex(0), SolarSystemIndex(9)]);
+        // with a landmark at the end of a line, the bound is exact
+        assert_eq!(landmarks.estimate(&SolarSystemIndex(2), &SolarSystemIndex(7)), 5);
+    }
+
+    #[test]
+    fn select_stops_when_there_are_no_more_systems() {
+        assert_eq!(Landmarks::select(&line_map(3), 10).landmarks().count(), 3);
+        assert_eq!(Landmarks::select(&line_map(0), 4).landmarks().count(), 0);
+    }
+}
@@ -0,0 +1,37 @@
+//! Library side of the A* on Eve Online map implementation.
+//!
+//! The binary in main.rs is a thin wrapper around this: it reads the SDE, builds an
+//! [`evemap::Map`] and runs [`astar::astar`] over it with the simple open and closed lists.
+pub mod astar;
+pub mod bfs;
+pub mod bitset;
+pub mod builder;
+pub mod cache;
+pub mod cost;
+pub mod daryopen;
+pub mod evemap;
+#[cfg(test)]
+mod fixtures;
+pub mod hashclosed;
+pub mod indexmap;
+pub mod jumpdrive;
+pub mod kshortest;
+pub mod landmarks;
+pub mod output;
+pub mod patch;
+#[cfg(feature = "profiling")]
+pub mod profiling;
+pub mod rng;
+pub mod route;
+pub mod router;
+pub mod scenario;
+pub mod sde;
+pub mod security;
+pub mod snapshot;
+pub mod simpleclosed;
+pub mod simpleopen;
+#[cfg(feature = "testing")]
+pub mod test_support;
+pub mod validate;
+
+pub use crate::evemap::SolarSystemIndex;
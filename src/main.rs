@@ -11,23 +11,28 @@
 //! It's generic over integer/NotNan<f32>/NotNan<f64>
 extern crate core;
 
-mod astar;
-mod evemap;
-mod sde;
-pub(crate) mod simpleclosed;
-pub(crate) mod simpleopen;
-
 use std::io;
 
 /// https://developers.eveonline.com/resource/resources
 const EVE_SDE_ZIP_URL: &str =
     "https://eve-static-data-export.s3-eu-west-1.amazonaws.com/tranquility/sde.zip";
 
-use crate::astar::ClosedListState::StartingPoint;
-use crate::astar::{ClosedList, OpenList};
-use crate::evemap::SolarSystemIndex;
+/// HEURISTIC_LANDMARKS is how many landmarks the landmark heuristic of routing and --benchmark-route uses
+const HEURISTIC_LANDMARKS: usize = 4;
+
+/// MAX_SAFE_PENALTY is the largest --safe-penalty. It's far beyond the diameter of the map, but
+/// small enough that a route crossing a border at every jump can't overflow its u32 cost.
+const MAX_SAFE_PENALTY: u32 = 1_000_000;
+
 use clap::Parser;
-use eyre::eyre;
+use eyre::{eyre, WrapErr};
+use rust_eve_astar::cost::CostModel;
+use rust_eve_astar::simpleopen::SimpleOpenList;
+use rust_eve_astar::{astar, bfs, cost, evemap, landmarks, output, patch, route, sde, SolarSystemIndex};
+
+#[cfg(feature = "profiling")]
+#[global_allocator]
+static ALLOCATOR: rust_eve_astar::profiling::CountingAllocator = rust_eve_astar::profiling::CountingAllocator;
 
 /// Download the Eve Online SDE (Static Data Export) and run A* on the Eve Map Data, after loading
 /// it.
@@ -37,75 +42,761 @@ struct Args {
     /// Since the download is a 100MB file, this can add up (and slow you down) if you're running everything over and over
     #[arg(short, long)]
     sde_path: Option<String>,
+
+    /// Find the route with the lowest estimated travel time (align, warp and gate activation)
+    /// rather than the fewest jumps
+    #[arg(long)]
+    optimize_time: bool,
+
+    /// Seconds your ship takes to align, used with --optimize-time
+    #[arg(long, default_value_t = 5.0, value_parser = parse_align_time)]
+    align_time: f32,
+
+    /// Among routes of similar length, prefer the one through better connected systems, which
+    /// have more ways out
+    #[arg(long, conflicts_with = "optimize_time")]
+    prefer_connected: bool,
+
+    /// Prefer routes that stay in one security class, adding --safe-penalty for each jump from
+    /// one class into another (eg. high-sec into low-sec)
+    #[arg(long, conflicts_with_all = ["optimize_time", "prefer_connected"])]
+    prefer_safe: bool,
+
+    /// How many jumps crossing a security border is worth with --prefer-safe, twice this for high
+    /// straight into null. A penalty of 1 or 2 only picks between similar routes, while one at
+    /// least the diameter of the map (the most jumps any shortest route takes) means low-sec is
+    /// never entered when there's a high-sec route. "auto" is one more than that, worked out
+    /// from the map
+    #[arg(long, default_value = "2", requires = "prefer_safe", value_parser = parse_safe_penalty)]
+    safe_penalty: SafePenalty,
+
+    /// CSV file of "FROM,TO,WEIGHT" lines giving the cost of jumps, eg. from intel on gate camps.
+    /// Routes have the least total weight, and jumps that aren't listed weigh 1
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["optimize_time", "prefer_connected", "prefer_safe", "max_jumps"]
+    )]
+    weights: Option<String>,
+
+    /// Only accept routes of at most this many jumps
+    #[arg(long, conflicts_with = "optimize_time")]
+    max_jumps: Option<u32>,
+
+    /// Solar system to route from
+    #[arg(long, default_value = "Amarr")]
+    from: String,
+
+    /// Solar system to route to
+    #[arg(long, default_value = "Yulai")]
+    to: String,
+
+    /// File of routes to find, one "FROM TO" pair of solar system names per line
+    #[arg(long)]
+    queries: Option<String>,
+
+    /// JSON patch of systems and jumps added or removed since the SDE, applied after loading it
+    #[arg(long)]
+    patch: Option<String>,
+
+    /// Don't take the stargates between two systems, given as FROM-TO (eg. a camped gate). Both
+    /// systems can still be passed through using their other gates. Can be given more than once
+    #[arg(long, value_name = "FROM-TO")]
+    avoid_gate: Vec<String>,
+
+    /// Name systems as the SDE's name table does, rather than as its file paths do
+    #[arg(long)]
+    authoritative_names: bool,
+
+    /// Language to name systems in (eg. de, fr, ja, ru, zh), which also implies --authoritative-names.
+    /// Names are taken from the SDE's translations, falling back to English where there are none
+    #[arg(long, default_value = "en")]
+    lang: String,
+
+    /// Print more detail about the loaded map
+    #[arg(short, long)]
+    verbose: bool,
+
+    /// Check the loaded map is consistent, print a report and exit without routing
+    #[arg(long)]
+    validate: bool,
+
+    /// Your home system: route summaries say how many jumps from it the start and end are
+    #[arg(long, value_name = "NAME")]
+    home: Option<String>,
+
+    /// Print the id, region, constellation and security of a solar system and exit without routing
+    #[arg(long, value_name = "NAME")]
+    info: Option<String>,
+
+    /// Keep the map loaded and answer "FROM TO" queries read from stdin, one per line, writing a
+    /// JSON route per line to stdout. Runs until stdin is closed
+    #[arg(long, conflicts_with = "queries")]
+    serve: bool,
+
+    /// Print statistics about each search: how much was explored and how good the heuristic was
+    #[arg(long)]
+    explain: bool,
+
+    /// Instead of printing the route, compare the routes found by each of the search modes
+    /// (BFS, A* on jumps, A* with a landmark heuristic, and preferring safe borders) in a table
+    #[arg(long)]
+    benchmark_route: bool,
+
+    /// Print only the number of jumps on each route, one per line, for scripts. Nothing is printed
+    /// and the exit code is non-zero when there's no route (or none within --max-jumps)
+    #[arg(
+        long,
+        conflicts_with_all = ["optimize_time", "prefer_connected", "prefer_safe", "weights", "benchmark_route", "serve"]
+    )]
+    count_only: bool,
+
+    /// How to print the routes
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    format: OutputFormat,
+
+    /// With --format jsonl, indent each route over several lines for reading, rather than
+    /// writing one per line
+    #[arg(long)]
+    json_pretty: bool,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum OutputFormat {
+    /// a numbered list of the systems on each route
+    Text,
+    /// one JSON object per route per line, written as each route is found
+    Jsonl,
+    /// a link to each route on the dotlan map
+    Dotlan,
+    /// in-game links to each system on the route, to paste into the game and set waypoints from
+    Clipboard,
 }
 
 fn main() -> eyre::Result<()> {
     let args = Args::parse();
 
     let now = std::time::Instant::now();
-    let reader: Box<dyn io::Read + Send> = match args.sde_path {
+    #[cfg(feature = "profiling")]
+    let build_allocs = rust_eve_astar::profiling::AllocStats::read();
+    // the mapping has to outlive the reader, so it's opened out here
+    #[cfg(feature = "mmap")]
+    let mmapped = match &args.sde_path {
+        Some(filepath) => Some(sde::MmapSdeSource::open(filepath).wrap_err_with(|| format!("unable to map {filepath}"))?),
+        None => None,
+    };
+    let reader: Box<dyn io::Read + Send + '_> = match &args.sde_path {
         None => {
             #[cfg(feature = "download")]
             {
-                println!("Loading SDE from source NB: This is a 100MB download each time.\nDownload from here: {EVE_SDE_ZIP_URL}");
+                eprintln!("Loading SDE from source NB: This is a 100MB download each time.\nDownload from here: {EVE_SDE_ZIP_URL}");
                 Box::new(reqwest::blocking::get(crate::EVE_SDE_ZIP_URL).context("failed to download SDE")?)
             }
             #[cfg(not(feature = "download"))]
             return Err(eyre!("Cannot download SDE without \"download\" feature enabled. Download manually from here: {EVE_SDE_ZIP_URL}"));
         }
+        #[cfg(feature = "mmap")]
+        Some(_) => {
+            eprintln!("Loading SDE from disk (memory mapped).");
+            Box::new(io::Cursor::new(mmapped.as_ref().expect("mapped above").bytes()))
+        }
+        #[cfg(not(feature = "mmap"))]
         Some(filepath) => {
-            println!("Loading SDE from disk.");
-            let f = std::fs::File::open(&filepath).expect("Error: file not found");
+            eprintln!("Loading SDE from disk.");
+            let f = std::fs::File::open(filepath).expect("Error: file not found");
             Box::new(std::io::BufReader::new(f))
         }
     };
 
     let mut r = sde::SdeZipReader::new(reader);
-    let map = evemap::Map::new(&mut r)?;
+    if args.lang != "en" {
+        r = r.with_language(&args.lang);
+    } else if args.authoritative_names {
+        r = r.with_names();
+    }
+    let mut map = evemap::Map::new(&mut r)?;
+    if let Some(path) = &args.patch {
+        let json = std::fs::read_to_string(path).wrap_err_with(|| format!("unable to read patch {path}"))?;
+        map.apply_patch(&patch::SdePatch::from_json(&json)?)
+            .wrap_err_with(|| format!("unable to apply patch {path}"))?;
+    }
+    for gate in &args.avoid_gate {
+        let (a, b) = split_gate(&map, gate)?;
+        if !map.remove_edge(a, b) {
+            return Err(eyre!("no stargate to avoid between the systems in \"{gate}\""));
+        }
+    }
+
+    eprintln!("map loaded: {:.2} seconds", now.elapsed().as_secs_f32());
+    #[cfg(feature = "profiling")]
+    print_allocations("map build", &build_allocs);
+    if args.verbose {
+        eprintln!("SDE version: {}", map.sde_version().unwrap_or("unknown"));
+        eprintln!("wiring: {}", map.wiring_report());
+        eprintln!(
+            "{} of {} systems have too many jumps to hold them in place",
+            map.systems_exceeding_inplace().len(),
+            map.system_count()
+        );
+    }
+
+    if args.validate {
+        let report = map.validate();
+        println!("{report}");
+        return match report.passed() {
+            true => Ok(()),
+            false => Err(eyre!("map failed {} consistency checks", report.failed().count())),
+        };
+    }
+
+    if let Some(name) = &args.info {
+        // find_solarsystem says why the name doesn't resolve, eg. which systems share it
+        let info = map.get_extended_solarsystem_info(&map.find_solarsystem(name)?);
+        let named = |id| map.name_of(id).map_or(id.to_string(), |n| format!("{n} ({id})"));
+        println!("{} - {}", info.name, info.solar_system_id);
+        println!("region: {}", named(info.region_id));
+        println!("constellation: {}", named(info.constellation_id));
+        println!(
+            "security: {:.1} ({:?}, true security {:.3})",
+            info.security_rounded(),
+            info.security_class(),
+            info.security_true()
+        );
+        return Ok(());
+    }
+
+    let weights = match &args.weights {
+        Some(path) => {
+            let csv = std::fs::read_to_string(path).wrap_err_with(|| format!("unable to read weights {path}"))?;
+            Some(cost::EdgeWeightCostModel::from_csv(&map, &csv).wrap_err_with(|| format!("bad weights in {path}"))?)
+        }
+        None => None,
+    };
+
+    // one BFS from home covers every route's summary
+    let home = match &args.home {
+        Some(name) => Some(map.distances_from(&map.find_solarsystem(name)?)),
+        None => None,
+    };
+
+    // a few BFSes up front give every search a lower bound on the jumps left to go
+    let landmarks = landmarks::Landmarks::select(&map, HEURISTIC_LANDMARKS);
+
+    if args.serve {
+        eprintln!("serving queries from stdin");
+        let (input, output) = (io::stdin().lock(), io::stdout().lock());
+        return serve(&map, &args, weights.as_ref(), &landmarks, home.as_ref(), input, output);
+    }
+
+    let queries: Vec<(String, String)> = match &args.queries {
+        Some(path) => std::fs::read_to_string(path)
+            .wrap_err_with(|| format!("unable to read queries from {path}"))?
+            .lines()
+            .filter(|l| !l.trim().is_empty())
+            .map(|l| split_query(&map, l))
+            .collect::<eyre::Result<_>>()?,
+        None => vec![(args.from.clone(), args.to.clone())],
+    };
+
+    if args.count_only {
+        // the jumps are all that's wanted, so there's no route to build
+        for (from, to) in &queries {
+            let (from_idx, to_idx) = (map.find_solarsystem(from)?, map.find_solarsystem(to)?);
+            // a route longer than --max-jumps is no route, as when routing
+            let within = |jumps: &u32| args.max_jumps.is_none_or(|max| *jumps <= max);
+            match map.jumps_between(&from_idx, &to_idx).filter(within) {
+                Some(jumps) => println!("{jumps}"),
+                None => return Err(eyre!("no route from {from} to {to}")),
+            }
+        }
+        return Ok(());
+    }
+
+    if args.benchmark_route {
+        for (from, to) in &queries {
+            println!("{from} -> {to}");
+            benchmark_route(&map, &landmarks, from, to)?;
+        }
+        return Ok(());
+    }
+
+    let mut stdout = io::stdout().lock();
+    let mut open = OpenLists::default();
+    for (from, to) in &queries {
+        #[cfg(feature = "profiling")]
+        let pathfind_allocs = rust_eve_astar::profiling::AllocStats::read();
+        let pathfinder_start = std::time::Instant::now();
+        let mut explanation = Explanation::default();
+        let result = find_route(&map, &args, weights.as_ref(), &landmarks, &mut open, from, to, &mut explanation);
+        let ns_time = pathfinder_start.elapsed().as_nanos();
+        eprintln!("pathfind: {} ns ({} ms)", ns_time, ns_time/1000000);
+        if args.explain {
+            eprintln!("explain: {explanation}");
+        }
+        #[cfg(feature = "profiling")]
+        print_allocations("pathfind", &pathfind_allocs);
+
+        match args.format {
+            OutputFormat::Text => {
+                if queries.len() > 1 {
+                    println!("{from} -> {to}");
+                }
+                match result {
+                    Ok(p) => {
+                        for (i, id) in p.iter().enumerate() {
+                            let info = map.get_extended_solarsystem_info(id);
+                            println!("{} {} - {}", i + 1, info.name, info.solar_system_id);
+                        }
+                        let summary = route::RouteSummary::new(&map, &p);
+                        match &home {
+                            Some(home) => println!("{}", summary.with_home(&p, home)),
+                            None => println!("{summary}"),
+                        }
+                    }
+                    Err(e) => println!("route not found: {e}"),
+                }
+            }
+            OutputFormat::Jsonl => {
+                let record = output::RouteRecord::new_with_home(&map, from, to, &result, home.as_ref());
+                if args.json_pretty {
+                    output::write_json_pretty(&mut stdout, &record)?
+                } else {
+                    output::write_jsonl(&mut stdout, &record)?
+                }
+            }
+            OutputFormat::Dotlan | OutputFormat::Clipboard => match result {
+                Ok(p) if args.format == OutputFormat::Dotlan => println!("{}", output::dotlan_route_url(&map, &p)),
+                Ok(p) => println!("{}", output::clipboard_waypoints(&map, &p)),
+                Err(e) => println!("route not found: {e}"),
+            },
+        }
+    }
+
+    Ok(())
+}
 
-    let yulai_ssid = map.get_solarsystem_id_by_name("Yulai").unwrap();
-    let yulai_idx = map.get_solarsystem_idx(&yulai_ssid);
+/// serve answers each "FROM TO" line of `input` with a JSON route record on `output`. A line that
+/// can't be answered gets a record with the error, rather than ending the loop.
+fn serve<R: io::BufRead, W: io::Write>(
+    map: &evemap::Map,
+    args: &Args,
+    weights: Option<&cost::EdgeWeightCostModel>,
+    landmarks: &landmarks::Landmarks,
+    home: Option<&bfs::DistanceTable>,
+    input: R,
+    mut output: W,
+) -> eyre::Result<()> {
+    // one set of open lists for the life of the server, rather than allocating them per query
+    let mut open = OpenLists::default();
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let record = match split_query(map, &line) {
+            Ok((from, to)) => {
+                let explanation = &mut Explanation::default();
+                let result = find_route(map, args, weights, landmarks, &mut open, &from, &to, explanation);
+                output::RouteRecord::new_with_home(map, &from, &to, &result, home)
+            }
+            Err(e) => output::RouteRecord::invalid_query(&line, &e),
+        };
+        output::write_jsonl(&mut output, &record)?;
+    }
+
+    Ok(())
+}
+
+/// print_allocations reports the allocations made since `before` and the peak bytes, then resets
+/// the peak for the next thing to be measured
+#[cfg(feature = "profiling")]
+fn print_allocations(what: &str, before: &rust_eve_astar::profiling::AllocStats) {
+    let after = rust_eve_astar::profiling::AllocStats::read();
+    eprintln!(
+        "{what}: {} allocations, peak {} bytes",
+        after.allocations_since(before),
+        after.peak_bytes
+    );
+    rust_eve_astar::profiling::reset_peak();
+}
 
-    println!("map loaded: {:.2} seconds", now.elapsed().as_secs_f32());
-    let pathfinder_start = std::time::Instant::now();
+/// OpenLists are the open lists find_route searches with, one for each type of cost, kept from
+/// query to query so their allocations are reused
+#[derive(Default)]
+struct OpenLists {
+    jumps: SimpleOpenList<SolarSystemIndex, u32>,
+    seconds: SimpleOpenList<SolarSystemIndex, ordered_float::NotNan<f32>>,
+}
 
-    let mut open = simpleopen::SimpleOpenList::new();
-    // TODO: Capacity based on EveMap max-index
-    let mut closed = simpleclosed::SimpleClosed::new(9000);
+impl OpenLists {
+    /// jumps is the open list for costs in jumps, emptied of the last search
+    fn jumps(&mut self) -> &mut SimpleOpenList<SolarSystemIndex, u32> {
+        self.jumps.clear();
+        &mut self.jumps
+    }
 
-    // NOTE: can be a NotNan<f32>
-    let one_jump = 1;
-    // Also works (to satisfy Ord):
-    // ordered_float::NotNan::new(1.0f32)?;
+    /// seconds is the open list for costs in seconds (or any other NotNan<f32>), emptied of the
+    /// last search
+    fn seconds(&mut self) -> &mut SimpleOpenList<SolarSystemIndex, ordered_float::NotNan<f32>> {
+        self.seconds.clear();
+        &mut self.seconds
+    }
+}
 
-    let amarr_ssid = map.get_solarsystem_id_by_name("Amarr").unwrap();
-    let amarr_idx = map.get_solarsystem_idx(&amarr_ssid);
+/// find_route resolves the system names and finds the route between them, in the mode given by
+/// args, or by least total weight when there are `weights`. The heuristic is the landmarks' bound
+/// on the jumps left, times the least any jump can cost in the mode, so it stays admissible.
+#[allow(clippy::too_many_arguments)]
+fn find_route(
+    map: &evemap::Map,
+    args: &Args,
+    weights: Option<&cost::EdgeWeightCostModel>,
+    landmarks: &landmarks::Landmarks,
+    open: &mut OpenLists,
+    from: &str,
+    to: &str,
+    explanation: &mut Explanation,
+) -> eyre::Result<Vec<SolarSystemIndex>> {
+    let from_idx = map.find_solarsystem(from)?;
+    let to_idx = map.find_solarsystem(to)?;
+    let jumps_left = |n: &SolarSystemIndex| landmarks.estimate(n, &to_idx);
 
-    // Start things off
-    let one_jump_estimate = 1;
-    open.push_open(astar::OpenItem {
-        heuristic: one_jump_estimate,
-        node: amarr_idx,
-    });
-    closed[amarr_idx] = StartingPoint(0);
+    let (path, cost) = if args.optimize_time {
+        // Cost is a NotNan<f32> of seconds here, which satisfies Ord where a bare f32 would not
+        let model = cost::TimeCostModel::new(args.align_time);
+        // a jump is at least the align and the gate, even with the gate right by the star
+        let min_jump = model.align_time + model.gate_time;
+        let heuristic = |n: &SolarSystemIndex| {
+            ordered_float::NotNan::new(jumps_left(n) as f32 * min_jump).unwrap_or_default()
+        };
+        explanation.start_heuristic = heuristic(&from_idx).into_inner() as f64;
+        let (path, seconds) = map.route_with_open_list_and_stats(
+            from_idx,
+            to_idx,
+            |from, to| model.edge_cost(map, from, to),
+            heuristic,
+            None,
+            || open.seconds(),
+            &mut explanation.stats,
+        )?;
+        eprintln!("estimated travel time: {:.0} seconds", seconds);
+        (path, seconds.into_inner() as f64)
+    } else if let Some(model) = weights {
+        let min_jump = model.weights.values().copied().fold(model.default_weight, std::cmp::min);
+        let heuristic = |n: &SolarSystemIndex| min_jump * jumps_left(n) as f32;
+        explanation.start_heuristic = heuristic(&from_idx).into_inner() as f64;
+        let (path, weight) = map.route_with_open_list_and_stats(
+            from_idx,
+            to_idx,
+            |from, to| model.edge_cost(map, from, to),
+            heuristic,
+            None,
+            || open.seconds(),
+            &mut explanation.stats,
+        )?;
+        (path, weight.into_inner() as f64)
+    } else {
+        let border = match args.safe_penalty {
+            SafePenalty::Jumps(penalty) => cost::BorderCostModel::with_penalty(penalty),
+            SafePenalty::Auto => cost::BorderCostModel::with_derived_penalty(map),
+        };
+        let connectivity = cost::ConnectivityCostModel::default();
+        let (model, min_jump): (&dyn CostModel<Cost = u32>, u32) = if args.prefer_safe {
+            (&border, 1)
+        } else if args.prefer_connected {
+            (&connectivity, connectivity.min_edge_cost())
+        } else {
+            (&cost::JumpsCostModel, 1)
+        };
+        // when a jump isn't a cost of 1, max_jumps can't bound the search and is checked afterwards
+        let weighted = args.prefer_safe || args.prefer_connected;
 
+        let heuristic = |n: &SolarSystemIndex| jumps_left(n).saturating_mul(min_jump);
+        explanation.start_heuristic = heuristic(&from_idx) as f64;
+        let (path, cost) = map.route_with_open_list_and_stats(
+            from_idx,
+            to_idx,
+            |from, to| model.edge_cost(map, from, to),
+            heuristic,
+            args.max_jumps.filter(|_| !weighted),
+            || open.jumps(),
+            &mut explanation.stats,
+        )?;
+        if weighted && args.max_jumps.is_some_and(|max| path.len() - 1 > max as usize) {
+            return Err(astar::AStarError::ExceedsMaxCost.into());
+        }
+        (path, cost as f64)
+    };
+
+    explanation.cost = Some(cost);
+    Ok(path)
+}
 
-    let result = astar::astar(
-        &mut open,
-        &mut closed,
-        |n| n == &yulai_idx,
-        |_| one_jump_estimate,
-        |n| map.get_neighbours(n).map(|p| (one_jump, p)).collect(),
+/// BenchmarkSearch is one of the astar modes compared by benchmark_route, all of which cost in jumps
+type BenchmarkSearch<'a> =
+    &'a dyn Fn(&mut astar::AStarStats) -> Result<(Vec<SolarSystemIndex>, u32), astar::AStarError>;
+
+/// benchmark_route finds the route between two systems in each of the search modes, and prints
+/// a table comparing the routes and the work done to find them
+fn benchmark_route(
+    map: &evemap::Map,
+    landmarks: &landmarks::Landmarks,
+    from: &str,
+    to: &str,
+) -> eyre::Result<()> {
+    let from_idx = map.find_solarsystem(from)?;
+    let to_idx = map.find_solarsystem(to)?;
+    let border = cost::BorderCostModel::default();
+
+    println!(
+        "{:<15} {:>6} {:>6} {:>9} {:>7} {:>5} {:>10}",
+        "mode", "jumps", "cost", "expanded", "pushed", "peak", "time (us)"
     );
 
-    let ns_time = pathfinder_start.elapsed().as_nanos();
-    println!("pathfind: {} ns ({} ms)", ns_time, ns_time/1000000);
-    if let Ok(p) = result {
-        for (i, id) in closed.unwind(p).iter().enumerate() {
-            let info = map.get_extended_solarsystem_info(&id);
-            println!("{} {} - {}", i + 1, info.name, info.solar_system_id);
+    // BFS doesn't go through astar, so it has no stats to report
+    let start = std::time::Instant::now();
+    let table = map.distances_from(&from_idx);
+    let route = map.route_using_distances(&table, &to_idx);
+    let elapsed = start.elapsed().as_micros();
+    match route {
+        Some(p) => println!(
+            "{:<15} {:>6} {:>6} {:>9} {:>7} {:>5} {:>10}",
+            "bfs", p.len() - 1, p.len() - 1, "-", "-", "-", elapsed
+        ),
+        None => println!("{:<15} route not found", "bfs"),
+    }
+
+    let modes: [(&str, BenchmarkSearch); 3] = [
+        ("astar-jumps", &|stats| map.route_with_stats(from_idx, to_idx, |_, _| 1u32, |_| 0, None, stats)),
+        ("astar-landmark", &|stats| {
+            map.route_with_stats(from_idx, to_idx, |_, _| 1u32, |n| landmarks.estimate(n, &to_idx), None, stats)
+        }),
+        ("prefer-safe", &|stats| {
+            map.route_with_stats(from_idx, to_idx, |a, b| border.edge_cost(map, a, b), |_| 0, None, stats)
+        }),
+    ];
+
+    for (mode, find) in modes {
+        let mut stats = astar::AStarStats::default();
+        let start = std::time::Instant::now();
+        let result = find(&mut stats);
+        let elapsed = start.elapsed().as_micros();
+        match result {
+            Ok((p, cost)) => println!(
+                "{:<15} {:>6} {:>6} {:>9} {:>7} {:>5} {:>10}",
+                mode,
+                p.len() - 1,
+                cost,
+                stats.nodes_expanded,
+                stats.nodes_pushed,
+                stats.queue_peak,
+                elapsed
+            ),
+            Err(e) => println!("{mode:<15} route not found: {e}"),
         }
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Explanation is what --explain reports about a search
+#[derive(Debug, Default)]
+struct Explanation {
+    stats: astar::AStarStats,
+    /// the heuristic's estimate of the cost from the start to the destination
+    start_heuristic: f64,
+    /// the actual cost of the route, if one was found
+    cost: Option<f64>,
+}
+
+impl std::fmt::Display for Explanation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "expanded {} systems, pushed {} onto the open list (peak {}), heuristic at start {}",
+            self.stats.nodes_expanded, self.stats.nodes_pushed, self.stats.queue_peak, self.start_heuristic
+        )?;
+        match self.cost {
+            // for an admissible heuristic the ratio is at most 1, and closer to 1 is a tighter estimate
+            Some(cost) if cost > 0.0 => write!(
+                f,
+                ", actual cost {cost}, heuristic/actual {:.2}",
+                self.start_heuristic / cost
+            ),
+            Some(cost) => write!(f, ", actual cost {cost}"),
+            None => write!(f, ", no route found"),
+        }
+    }
+}
+
+/// SafePenalty is the --safe-penalty for crossing a security border
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SafePenalty {
+    /// a penalty of this many jumps
+    Jumps(u32),
+    /// a penalty outweighing any shortest route of the map, see BorderCostModel::with_derived_penalty
+    Auto,
+}
+
+/// parse_safe_penalty parses --safe-penalty: "auto", or jumps up to MAX_SAFE_PENALTY
+fn parse_safe_penalty(s: &str) -> Result<SafePenalty, String> {
+    if s == "auto" {
+        return Ok(SafePenalty::Auto);
+    }
+    let penalty: u32 = s.parse().map_err(|e| format!("{e}"))?;
+    if penalty > MAX_SAFE_PENALTY {
+        return Err(format!("{penalty} is not in 0..={MAX_SAFE_PENALTY}"));
+    }
+    Ok(SafePenalty::Jumps(penalty))
+}
+
+/// parse_align_time parses --align-time, which must be a finite number of seconds of at least 0:
+/// a NaN would poison every travel time, and a negative one could make a jump cost less than
+/// nothing, which A* can't route with
+fn parse_align_time(s: &str) -> Result<f32, String> {
+    let seconds: f32 = s.parse().map_err(|e| format!("{e}"))?;
+    if !seconds.is_finite() || seconds < 0.0 {
+        return Err(format!("{seconds} is not a finite number of seconds of at least 0"));
+    }
+    Ok(seconds)
+}
+
+/// split_query splits a "FROM TO" line into the two system names. Since names can contain spaces
+/// (eg. "Sarum Prime"), this tries each split point until both halves are known systems.
+fn split_query(map: &evemap::Map, line: &str) -> eyre::Result<(String, String)> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    (1..words.len())
+        .map(|i| (words[..i].join(" "), words[i..].join(" ")))
+        .find(|(from, to)| map.find_solarsystem(from).is_ok() && map.find_solarsystem(to).is_ok())
+        .ok_or_else(|| eyre!("unable to find two solar systems in query \"{line}\""))
+}
+
+/// split_gate splits a "FROM-TO" pair of solar system names for --avoid-gate. Names can have a '-'
+/// in them (eg. EC-P8R), so the pair is split at the first '-' that leaves a system on both sides
+fn split_gate(map: &evemap::Map, gate: &str) -> eyre::Result<(SolarSystemIndex, SolarSystemIndex)> {
+    gate.match_indices('-')
+        .find_map(|(i, _)| {
+            let from = map.find_solarsystem(&gate[..i]).ok()?;
+            Some((from, map.find_solarsystem(&gate[i + 1..]).ok()?))
+        })
+        .ok_or_else(|| eyre!("unable to find two solar systems in gate \"{gate}\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// fixture_map is the map of the small SDE in examples/fixtures
+    fn fixture_map() -> evemap::Map {
+        evemap::Map::from_zip_bytes(include_bytes!("../examples/fixtures/sde.zip")).unwrap()
+    }
+
+    /// explain runs find_route with the command line `args`, returning the route's length and the
+    /// explanation of the search
+    fn explain(map: &evemap::Map, args: &[&str], from: &str, to: &str) -> (usize, Explanation) {
+        let args = Args::parse_from(["rust-eve-astar"].iter().chain(args));
+        let landmarks = landmarks::Landmarks::select(map, HEURISTIC_LANDMARKS);
+        let mut explanation = Explanation::default();
+        let route = find_route(map, &args, None, &landmarks, &mut OpenLists::default(), from, to, &mut explanation);
+        (route.unwrap().len(), explanation)
+    }
+
+    #[test]
+    fn explain_stats_are_populated_and_the_heuristic_is_admissible() {
+        let map = fixture_map();
+        for mode in [&[][..], &["--optimize-time"], &["--prefer-safe"], &["--prefer-connected"]] {
+            let (len, explanation) = explain(&map, mode, "Tama", "Amarr");
+            let cost = explanation.cost.unwrap();
+            assert_eq!(len, 5, "{mode:?}");
+            assert!(explanation.start_heuristic > 0.0, "{mode:?}: {explanation}");
+            assert!(cost >= explanation.start_heuristic, "{mode:?}: {explanation}");
+            assert!(explanation.stats.nodes_expanded >= len - 1, "{mode:?}: {explanation}");
+            assert!(explanation.stats.nodes_pushed >= explanation.stats.nodes_expanded, "{mode:?}: {explanation}");
+            assert!(explanation.stats.queue_peak >= 1, "{mode:?}: {explanation}");
+        }
+    }
+
+    #[test]
+    fn explain_of_jumps_reports_the_tightness_of_the_heuristic() {
+        let (_, explanation) = explain(&fixture_map(), &[], "Jita", "Amarr");
+        assert_eq!(explanation.cost, Some(2.0));
+        let ratio = explanation.start_heuristic / 2.0;
+        assert!(explanation.to_string().ends_with(&format!("actual cost 2, heuristic/actual {ratio:.2}")));
+    }
+
+    #[test]
+    fn serve_answers_every_line_and_keeps_going_after_a_bad_one() {
+        let map = fixture_map();
+        let args = Args::parse_from(["rust-eve-astar", "--serve"]);
+        let landmarks = landmarks::Landmarks::select(&map, HEURISTIC_LANDMARKS);
+        let input = io::Cursor::new("Jita Amarr\n\nJita\nNowhere Amarr\nSarum Prime Tama\n");
+        let mut output = Vec::new();
+        serve(&map, &args, None, &landmarks, None, input, &mut output).unwrap();
+
+        let records: Vec<serde_json::Value> =
+            String::from_utf8(output).unwrap().lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+        assert_eq!(records.len(), 4);
+        assert_eq!((&records[0]["from"], &records[0]["jumps"]), (&"Jita".into(), &2.into()));
+        assert_eq!(records[1]["from"], "Jita");
+        assert!(records[1]["error"].is_string());
+        assert!(records[2]["error"].is_string());
+        assert_eq!((&records[3]["to"], &records[3]["jumps"]), (&"Tama".into(), &3.into()));
+    }
+
+    #[test]
+    fn split_gate_splits_at_the_dash_between_two_systems() {
+        let map = fixture_map();
+        let index = |name| map.find_solarsystem(name).unwrap();
+        assert_eq!(split_gate(&map, "Jita-Yulai").unwrap(), (index("Jita"), index("Yulai")));
+        assert_eq!(split_gate(&map, "EC-P8R-Tama").unwrap(), (index("EC-P8R"), index("Tama")));
+        assert_eq!(split_gate(&map, "Tama-EC-P8R").unwrap(), (index("Tama"), index("EC-P8R")));
+        assert!(split_gate(&map, "Jita").is_err());
+        assert!(split_gate(&map, "Jita-Nowhere").is_err());
+    }
+
+    #[test]
+    fn open_lists_are_handed_out_empty() {
+        use astar::OpenList;
+        let jita = fixture_map().find_solarsystem("Jita").unwrap();
+        let mut open = OpenLists::default();
+        open.jumps().push_open(astar::OpenItem { heuristic: 1, node: jita });
+        assert!(open.jumps().is_empty());
+        open.seconds().push_open(astar::OpenItem { heuristic: Default::default(), node: jita });
+        assert!(open.seconds().is_empty());
+    }
+
+    #[test]
+    fn serve_gives_the_same_answer_to_a_repeated_query() {
+        let map = fixture_map();
+        let landmarks = landmarks::Landmarks::select(&map, HEURISTIC_LANDMARKS);
+        for mode in [&[][..], &["--optimize-time"]] {
+            let args = Args::parse_from(["rust-eve-astar", "--serve"].iter().chain(mode));
+            let input = io::Cursor::new("Tama Amarr\nJita Isolated\nJita Amarr\nTama Amarr\n");
+            let mut output = Vec::new();
+            serve(&map, &args, None, &landmarks, None, input, &mut output).unwrap();
+
+            let output = String::from_utf8(output).unwrap();
+            let records: Vec<&str> = output.lines().collect();
+            assert_eq!(records.len(), 4, "{mode:?}");
+            assert_eq!(records[0], records[3], "{mode:?}");
+        }
+    }
+
+    #[test]
+    fn align_time_must_be_finite_and_not_negative() {
+        assert_eq!(parse_align_time("2.5"), Ok(2.5));
+        assert_eq!(parse_align_time("0"), Ok(0.0));
+        for bad in ["NaN", "inf", "-inf", "-1", "fast"] {
+            assert!(parse_align_time(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn safe_penalty_is_auto_or_a_number_of_jumps() {
+        assert_eq!(parse_safe_penalty("auto"), Ok(SafePenalty::Auto));
+        assert_eq!(parse_safe_penalty("7"), Ok(SafePenalty::Jumps(7)));
+        assert!(parse_safe_penalty(&(MAX_SAFE_PENALTY + 1).to_string()).is_err());
+        assert!(parse_safe_penalty("-1").is_err());
+    }
+}
@@ -0,0 +1,239 @@
+//! Serializable forms of routes, for machine readable output, and the formats players share routes in.
+//!
+//! The JSON fields of RouteRecord, RouteHop and RouteSummary are written in the order the structs
+//! declare them, and scripts may rely on that (eg. diffing output between SDE releases), so it's
+//! part of the format: new fields go at the end.
+use crate::bfs::DistanceTable;
+use crate::evemap::{Map, SolarSystemIndex};
+pub use crate::route::RouteHop;
+use crate::route::RouteSummary;
+use serde::Serialize;
+use std::fmt::Display;
+use std::io::Write;
+
+/// RouteRecord is the outcome of a single route query: either the route, or why there isn't one.
+/// Field order is part of the JSON format.
+#[derive(Debug, Clone, Serialize)]
+pub struct RouteRecord {
+    pub from: String,
+    pub to: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jumps: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub summary: Option<RouteSummary>,
+    pub route: Vec<RouteHop>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// the SDE build the route was computed against
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sde_version: Option<String>,
+}
+
+impl RouteRecord {
+    pub fn new<E: Display>(map: &Map, from: &str, to: &str, result: &Result<Vec<SolarSystemIndex>, E>) -> Self {
+        RouteRecord::new_with_home(map, from, to, result, None)
+    }
+
+    /// new_with_home is new, with the summary saying how far the route is from the origin of
+    /// `home` (see RouteSummary::with_home)
+    pub fn new_with_home<E: Display>(
+        map: &Map,
+        from: &str,
+        to: &str,
+        result: &Result<Vec<SolarSystemIndex>, E>,
+        home: Option<&DistanceTable>,
+    ) -> Self {
+        let (route, summary, error) = match result {
+            Ok(path) => (
+                path.iter().map(|i| RouteHop::new(map, i)).collect::<Vec<_>>(),
+                Some(match home {
+                    Some(home) => RouteSummary::new(map, path).with_home(path, home),
+                    None => RouteSummary::new(map, path),
+                }),
+                None,
+            ),
+            Err(e) => (Vec::new(), None, Some(e.to_string())),
+        };
+
+        RouteRecord {
+            from: from.to_string(),
+            to: to.to_string(),
+            jumps: error.is_none().then(|| route.len().saturating_sub(1)),
+            summary,
+            route,
+            error,
+            sde_version: map.sde_version().map(str::to_string),
+        }
+    }
+}
+
+impl RouteRecord {
+    /// invalid_query is the record for a query that couldn't be understood, eg. a line that isn't
+    /// two system names. The whole query is kept as `from`.
+    pub fn invalid_query<E: Display>(query: &str, error: &E) -> Self {
+        RouteRecord {
+            from: query.to_string(),
+            to: String::new(),
+            jumps: None,
+            summary: None,
+            route: Vec::new(),
+            error: Some(error.to_string()),
+            sde_version: None,
+        }
+    }
+}
+
+/// write_jsonl writes `record` as a single line of JSON and flushes it, so whatever is reading
+/// the other end of a pipe gets each record as soon as it's ready rather than when we're done
+pub fn write_jsonl<W: Write, T: Serialize>(w: &mut W, record: &T) -> eyre::Result<()> {
+    serde_json::to_writer(&mut *w, record)?;
+    w.write_all(b"\n")?;
+    w.flush()?;
+    Ok(())
+}
+
+/// write_json_pretty is write_jsonl, but indented over several lines for people to read. Records
+/// are still separated by a newline, and a JSON stream reader takes them in the same way.
+pub fn write_json_pretty<W: Write, T: Serialize>(w: &mut W, record: &T) -> eyre::Result<()> {
+    serde_json::to_writer_pretty(&mut *w, record)?;
+    w.write_all(b"\n")?;
+    w.flush()?;
+    Ok(())
+}
+
+/// DOTLAN_ROUTE_URL is the base of a route on the dotlan map, which is followed by the systems
+const DOTLAN_ROUTE_URL: &str = "https://evemaps.dotlan.net/route/";
+
+/// SOLAR_SYSTEM_TYPE_ID is the type of solar systems, which in-game links need along with the id
+const SOLAR_SYSTEM_TYPE_ID: u32 = 5;
+
+/// dotlan_route_url is a link to the route on dotlan, with the systems in order. Dotlan writes
+/// spaces in names as underscores; anything else that isn't URL safe is percent encoded.
+pub fn dotlan_route_url(map: &Map, route: &[SolarSystemIndex]) -> String {
+    let systems: Vec<String> = route
+        .iter()
+        .map(|i| dotlan_encode(&map.get_extended_solarsystem_info(i).name))
+        .collect();
+    format!("{DOTLAN_ROUTE_URL}{}", systems.join(":"))
+}
+
+fn dotlan_encode(name: &str) -> String {
+    let mut encoded = String::with_capacity(name.len());
+    for b in name.bytes() {
+        match b {
+            b' ' => encoded.push('_'),
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'~' => encoded.push(b as char),
+            _ => encoded.push_str(&format!("%{b:02X}")),
+        }
+    }
+    encoded
+}
+
+/// clipboard_waypoints is the route as in-game links to each system, one per line. Pasted into
+/// the in-game notepad or chat, each can be clicked to set it as a waypoint.
+pub fn clipboard_waypoints(map: &Map, route: &[SolarSystemIndex]) -> String {
+    route
+        .iter()
+        .map(|i| {
+            let info = map.get_extended_solarsystem_info(i);
+            format!("<url=showinfo:{SOLAR_SYSTEM_TYPE_ID}//{}>{}</url>", info.solar_system_id, info.name)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::astar::AStarError;
+    use crate::fixtures::{sde_map, system};
+
+    #[test]
+    fn route_record_has_the_route_or_the_error() {
+        let map = sde_map();
+        let route: Result<_, AStarError> = Ok(vec![system(&map, "Jita"), system(&map, "Yulai")]);
+        let found = RouteRecord::new(&map, "Jita", "Yulai", &route);
+        assert_eq!(found.jumps, Some(1));
+        assert_eq!(found.route.len(), 2);
+        assert!(found.error.is_none());
+
+        let missing = RouteRecord::new(&map, "Jita", "Isolated", &Err::<Vec<_>, _>(AStarError::PathNotFound));
+        assert_eq!(missing.jumps, None);
+        assert!(missing.route.is_empty());
+        assert_eq!(missing.error.as_deref(), Some("no path found"));
+    }
+
+    #[test]
+    fn write_jsonl_writes_a_record_per_line() {
+        let mut out = Vec::new();
+        write_jsonl(&mut out, &serde_json::json!({"a": [1, 2]})).unwrap();
+        write_jsonl(&mut out, &serde_json::json!({"b": 3})).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "{\"a\":[1,2]}\n{\"b\":3}\n");
+    }
+
+    #[test]
+    fn dotlan_route_url_joins_the_encoded_names() {
+        let map = sde_map();
+        let route = [system(&map, "Amarr"), system(&map, "Sarum Prime"), system(&map, "Niarja")];
+        assert_eq!(dotlan_route_url(&map, &route), "https://evemaps.dotlan.net/route/Amarr:Sarum_Prime:Niarja");
+        assert_eq!(dotlan_encode("EC-P8R"), "EC-P8R");
+        assert_eq!(dotlan_encode("Ab'é:1"), "Ab%27%C3%A9%3A1");
+    }
+
+    #[test]
+    fn clipboard_waypoints_links_each_system_on_a_line() {
+        let map = sde_map();
+        let route = [system(&map, "Jita"), system(&map, "Yulai")];
+        assert_eq!(
+            clipboard_waypoints(&map, &route),
+            "<url=showinfo:5//30000008>Jita</url>\n<url=showinfo:5//30000009>Yulai</url>"
+        );
+    }
+
+    #[test]
+    fn route_record_fields_are_written_in_declaration_order() {
+        let map = sde_map();
+        let route: Result<_, AStarError> = Ok(vec![system(&map, "Jita"), system(&map, "Yulai")]);
+        let mut out = Vec::new();
+        write_jsonl(&mut out, &RouteRecord::new(&map, "Jita", "Yulai", &route)).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            concat!(
+                r#"{"from":"Jita","to":"Yulai","jumps":1,"#,
+                r#""summary":{"jumps":1,"high_sec":2,"low_sec":0,"null_sec":0,"lowest_security":0.9,"all_high_sec":true},"#,
+                r#""route":[{"name":"Jita","solar_system_id":30000008,"security":0.9},"#,
+                r#"{"name":"Yulai","solar_system_id":30000009,"security":1.0}],"sde_version":"2967307"}"#,
+                "\n"
+            )
+        );
+    }
+
+    #[test]
+    fn write_json_pretty_indents_each_record_and_ends_it_with_a_newline() {
+        let mut out = Vec::new();
+        write_json_pretty(&mut out, &serde_json::json!({"a": [1]})).unwrap();
+        write_json_pretty(&mut out, &serde_json::json!({"b": 2})).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(out, "{\n  \"a\": [\n    1\n  ]\n}\n{\n  \"b\": 2\n}\n");
+
+        let records: Vec<serde_json::Value> =
+            serde_json::Deserializer::from_str(&out).into_iter().collect::<Result<_, _>>().unwrap();
+        assert_eq!(records.len(), 2);
+    }
+
+    #[test]
+    fn route_record_with_home_has_the_jumps_from_home_in_its_summary() {
+        let map = sde_map();
+        let route: Result<_, AStarError> = Ok(vec![system(&map, "Jita"), system(&map, "Yulai")]);
+        let home = map.distances_from(&system(&map, "Amarr"));
+        let record = RouteRecord::new_with_home(&map, "Jita", "Yulai", &route, Some(&home));
+        let summary = record.summary.as_ref().unwrap();
+        assert_eq!((summary.start_from_home, summary.end_from_home), (Some(2), Some(1)));
+        let json = serde_json::to_string(&record).unwrap();
+        assert!(json.contains("\"start_from_home\":2,\"end_from_home\":1"), "{json}");
+
+        // without a home, the fields are left out
+        let json = serde_json::to_string(&RouteRecord::new(&map, "Jita", "Yulai", &route)).unwrap();
+        assert!(!json.contains("from_home"), "{json}");
+    }
+}
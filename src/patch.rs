@@ -0,0 +1,299 @@
+//! Small updates to a built Map, between full SDE releases.
+//!
+//! A patch lists systems and jumps to add or remove, in JSON, using the same system and jump
+//! records as a MapSnapshot. Applying one edits the Map in place, so existing SolarSystemIndexes
+//! stay valid: new systems are appended, and removed systems keep their index.
+use crate::evemap::{
+    Map, Neighbours, RawIndex, SolarSystemEx, SolarSystemId, SolarSystemIndex, SolarSystemMapItem, SystemGate,
+};
+use crate::snapshot::{JumpSnapshot, SystemSnapshot};
+use eyre::eyre;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SdePatch {
+    #[serde(default)]
+    pub added_systems: Vec<SystemSnapshot>,
+    #[serde(default)]
+    pub removed_systems: Vec<SolarSystemId>,
+    /// jumps are one way, so a new pair of stargates is two jumps
+    #[serde(default)]
+    pub added_jumps: Vec<JumpSnapshot>,
+    /// (from, to) of jumps to remove, again one way
+    #[serde(default)]
+    pub removed_jumps: Vec<(SolarSystemId, SolarSystemId)>,
+}
+
+impl SdePatch {
+    /// from_json reads a patch from its JSON form
+    pub fn from_json(json: &str) -> eyre::Result<SdePatch> {
+        Ok(serde_json::from_str(json)?)
+    }
+}
+
+impl Map {
+    /// apply_patch updates the map with the systems and jumps in `patch`. The whole patch is checked
+    /// before anything is changed, so a patch that doesn't fit the map leaves it untouched.
+    ///
+    /// Removals happen before additions. A removed system loses all of its jumps and can no longer
+    /// be found by name, but keeps its index (and id lookup) so that every other index stays put.
+    /// Added systems are given the next indexes, so unlike a freshly built map they aren't in
+    /// SolarSystemId order.
+    pub fn apply_patch(&mut self, patch: &SdePatch) -> eyre::Result<()> {
+        self.check_patch(patch)?;
+
+        for (from, to) in &patch.removed_jumps {
+            let (from, to) = (self.get_solarsystem_idx(from), self.get_solarsystem_idx(to));
+            self.drop_jump(from, to);
+        }
+
+        for id in &patch.removed_systems {
+            let i = self.get_solarsystem_idx(id);
+            let outgoing: Vec<_> = self.get_extended_solarsystem_info(&i).gates.iter().map(|g| g.destination).collect();
+            let incoming: Vec<_> = self.get_predecessors(&i).collect();
+            for to in outgoing {
+                self.drop_jump(i, to);
+            }
+            for from in incoming {
+                self.drop_jump(from, i);
+            }
+            let name = &self.extended_systems[usize::from(i)].name;
+            if let Some(ids) = self.name_to_id.get_mut(name) {
+                ids.retain(|x| *x != id.0);
+                if ids.is_empty() {
+                    self.name_to_id.remove(name);
+                }
+            }
+        }
+
+        for s in &patch.added_systems {
+            let i = SolarSystemIndex(self.systems.len().try_into()?);
+            self.systems.push(SolarSystemMapItem {
+                solar_system_id: s.solar_system_id,
+                neighbours: Neighbours::InPlace(Default::default()),
+            });
+            self.extended_systems.push(SolarSystemEx {
+                name: s.name.clone(),
+                solar_system_id: s.solar_system_id,
+                constellation_id: s.constellation_id,
+                region_id: s.region_id,
+                security: s.security,
+                gates: Vec::new(),
+                position: s.position,
+            });
+            self.predecessors.push(Vec::new());
+            self.system_id_to_index.insert(s.solar_system_id, i);
+            self.name_to_id.entry(s.name.clone()).or_default().push(s.solar_system_id.0);
+        }
+
+        for j in &patch.added_jumps {
+            let (from, to) = (self.get_solarsystem_idx(&j.from), self.get_solarsystem_idx(&j.to));
+            self.systems[usize::from(from)].neighbours.insert(to);
+            self.extended_systems[usize::from(from)].gates.push(SystemGate {
+                destination: to,
+                stargate_id: j.stargate_id,
+                position: j.position,
+            });
+            self.predecessors[usize::from(to)].push(from);
+        }
+
+        // new systems need a label, and new jumps may have joined components together
+        self.components = self.component_labels();
+        self.max_route_length.take();
+        Ok(())
+    }
+
+    /// check_patch makes sure every system a patch refers to will be there when it's needed
+    fn check_patch(&self, patch: &SdePatch) -> eyre::Result<()> {
+        let capacity = (RawIndex::MAX as usize).saturating_add(1);
+        if self.system_count() + patch.added_systems.len() > capacity {
+            return Err(eyre!("patch would take the map beyond {capacity} systems"));
+        }
+
+        let mut added = HashSet::new();
+        for s in &patch.added_systems {
+            if self.system_id_to_index.contains_key(&s.solar_system_id) || !added.insert(s.solar_system_id) {
+                return Err(eyre!("patch adds system {} more than once", s.solar_system_id));
+            }
+        }
+
+        let removed: HashSet<_> = patch.removed_systems.iter().collect();
+        for id in &removed {
+            if !self.system_id_to_index.contains_key(id) {
+                return Err(eyre!("patch removes unknown system {id}"));
+            }
+        }
+
+        for (from, to) in &patch.removed_jumps {
+            let exists = self.system_id_to_index.get(from).zip(self.system_id_to_index.get(to)).is_some_and(
+                |(from, to)| self.get_extended_solarsystem_info(from).gates.iter().any(|g| g.destination == *to),
+            );
+            if !exists {
+                return Err(eyre!("patch removes jump {from} -> {to}, which isn't in the map"));
+            }
+        }
+
+        for j in &patch.added_jumps {
+            for id in [&j.from, &j.to] {
+                let known = self.system_id_to_index.contains_key(id) || added.contains(id);
+                if !known || removed.contains(id) {
+                    return Err(eyre!("patch adds jump {} -> {} to unknown system {id}", j.from, j.to));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// drop_jump takes a jump out of the map for good, along with its stargate and any record of
+    /// remove_edge having taken it out for now
+    fn drop_jump(&mut self, from: SolarSystemIndex, to: SolarSystemIndex) {
+        self.systems[usize::from(from)].neighbours.remove(to);
+        for g in &self.extended_systems[usize::from(from)].gates {
+            if let Some(id) = g.stargate_id.filter(|_| g.destination == to) {
+                self.stargates.remove(&id);
+            }
+        }
+        self.extended_systems[usize::from(from)].gates.retain(|g| g.destination != to);
+        self.predecessors[usize::from(to)].retain(|p| *p != from);
+        self.removed_edges.remove(&(from, to));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{names, sde_map, system};
+
+    /// ISOLATED and JITA are the SolarSystemIds of those fixture systems
+    const ISOLATED: u64 = 30_000_011;
+    const JITA: u64 = 30_000_008;
+
+    /// both_ways is a pair of jumps between `a` and `b`, without stargates
+    fn both_ways(a: u64, b: u64) -> Vec<JumpSnapshot> {
+        let jump = |from, to| JumpSnapshot {
+            from: SolarSystemId(from),
+            to: SolarSystemId(to),
+            stargate_id: None,
+            position: None,
+        };
+        vec![jump(a, b), jump(b, a)]
+    }
+
+    #[test]
+    fn apply_patch_adds_a_system_joining_isolated_to_jita() {
+        let mut map = sde_map();
+        let (jita, isolated) = (system(&map, "Jita"), system(&map, "Isolated"));
+        let json = format!(
+            r#"{{
+                "added_systems": [{{"solar_system_id": 31000000, "name": "Bridge", "constellation_id": 0,
+                                    "region_id": 0, "security": 0.2}}],
+                "added_jumps": [
+                    {{"from": {ISOLATED}, "to": 31000000, "stargate_id": null, "position": null}},
+                    {{"from": 31000000, "to": {ISOLATED}, "stargate_id": null, "position": null}},
+                    {{"from": {JITA}, "to": 31000000, "stargate_id": null, "position": null}},
+                    {{"from": 31000000, "to": {JITA}, "stargate_id": null, "position": null}}
+                ]
+            }}"#
+        );
+        map.apply_patch(&SdePatch::from_json(&json).unwrap()).unwrap();
+
+        // the new system is appended, so the existing indexes still hold
+        assert_eq!(system(&map, "Bridge"), SolarSystemIndex(11));
+        assert_eq!((system(&map, "Jita"), system(&map, "Isolated")), (jita, isolated));
+        let route = map.shortest_route(isolated, jita).unwrap();
+        assert_eq!(names(&map, &route), ["Isolated", "Bridge", "Jita"]);
+        assert!(map.validate().passed());
+    }
+
+    #[test]
+    fn apply_patch_removes_jumps_and_systems() {
+        let mut map = sde_map();
+        let (jita, amarr, yulai) = (system(&map, "Jita"), system(&map, "Amarr"), system(&map, "Yulai"));
+        assert_eq!(map.shortest_route(jita, amarr).unwrap().len(), 3);
+
+        let patch = SdePatch {
+            removed_jumps: vec![(SolarSystemId(JITA), map.get_system(&yulai).solar_system_id)],
+            ..Default::default()
+        };
+        map.apply_patch(&patch).unwrap();
+        assert_eq!(map.shortest_route(jita, amarr).unwrap().len(), 6);
+        assert_eq!(map.shortest_route(amarr, jita).unwrap().len(), 3);
+
+        let patch = SdePatch {
+            removed_systems: vec![map.get_system(&yulai).solar_system_id],
+            ..Default::default()
+        };
+        map.apply_patch(&patch).unwrap();
+        assert!(map.find_solarsystem("Yulai").is_err());
+        assert_eq!(map.get_solarsystem_idx(&map.get_system(&yulai).solar_system_id), yulai);
+        assert_eq!(map.get_neighbours(&yulai).count(), 0);
+        assert_eq!(map.get_predecessors(&yulai).count(), 0);
+        assert_eq!(map.shortest_route(amarr, jita).unwrap().len(), 6);
+    }
+
+    #[test]
+    fn apply_patch_leaves_the_map_alone_when_the_patch_does_not_fit() {
+        let mut map = sde_map();
+        let before = map.snapshot();
+        let patch = SdePatch {
+            removed_jumps: vec![(SolarSystemId(JITA), SolarSystemId(ISOLATED))],
+            added_jumps: both_ways(JITA, 31_000_000),
+            ..Default::default()
+        };
+
+        let err = map.apply_patch(&patch).unwrap_err();
+        assert_eq!(err.to_string(), "patch removes jump 30000008 -> 30000011, which isn't in the map");
+        assert_eq!(map.snapshot(), before);
+
+        let patch = SdePatch {
+            added_jumps: both_ways(JITA, 31_000_000),
+            ..Default::default()
+        };
+        assert!(map.apply_patch(&patch).is_err());
+        assert_eq!(map.snapshot(), before);
+    }
+
+    #[test]
+    fn apply_patch_drops_the_stargate_of_a_removed_jump() {
+        // Jita's gate 50000017 leads to Yulai's 50000018
+        let mut map = sde_map();
+        let yulai = map.get_system(&system(&map, "Yulai")).solar_system_id;
+        let patch = SdePatch {
+            removed_jumps: vec![(SolarSystemId(JITA), yulai)],
+            ..Default::default()
+        };
+        map.apply_patch(&patch).unwrap();
+        assert!(map.stargate(50_000_017).is_none());
+        assert!(map.stargate(50_000_018).is_some());
+    }
+
+    #[test]
+    fn apply_patch_forgets_the_max_route_length() {
+        let mut map = sde_map();
+        assert_eq!(map.max_route_length(), 4);
+        // without Jita - Yulai, Yulai is a dead end off Amarr
+        let yulai = map.get_system(&system(&map, "Yulai")).solar_system_id;
+        let patch = SdePatch {
+            removed_jumps: vec![(SolarSystemId(JITA), yulai), (yulai, SolarSystemId(JITA))],
+            ..Default::default()
+        };
+        map.apply_patch(&patch).unwrap();
+        assert_eq!(map.max_route_length(), 6);
+        assert_eq!(map.max_route_length(), map.diameter());
+    }
+
+    #[test]
+    fn apply_patch_keeps_removed_edges_joining_their_components() {
+        let mut map = crate::fixtures::line_map(5);
+        let (s0, s1, s2, s4) = (SolarSystemIndex(0), SolarSystemIndex(1), SolarSystemIndex(2), SolarSystemIndex(4));
+        assert!(map.remove_edge(s1, s2));
+        map.apply_patch(&SdePatch::default()).unwrap();
+        assert_eq!(map.shortest_route(s0, s4), Err(crate::astar::AStarError::PathNotFound));
+
+        assert!(map.restore_edge(s1, s2));
+        assert!(map.path_exists(&s0, &s4));
+        assert_eq!(map.shortest_route(s0, s4).map(|r| r.len()), Ok(5));
+    }
+}
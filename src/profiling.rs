@@ -0,0 +1,79 @@
+//! Allocation counting, to put numbers on how much memory the map build and pathfinding use.
+//!
+//! Only available with the "profiling" feature. The library doesn't install the allocator itself,
+//! the binary does that with `#[global_allocator]`.
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static CURRENT_BYTES: AtomicUsize = AtomicUsize::new(0);
+static PEAK_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+/// CountingAllocator wraps the system allocator, counting allocations and tracking the peak
+/// number of bytes allocated at once
+pub struct CountingAllocator;
+
+impl CountingAllocator {
+    fn record_alloc(size: usize) {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        let current = CURRENT_BYTES.fetch_add(size, Ordering::Relaxed) + size;
+        PEAK_BYTES.fetch_max(current, Ordering::Relaxed);
+    }
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let p = System.alloc(layout);
+        if !p.is_null() {
+            Self::record_alloc(layout.size());
+        }
+        p
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        CURRENT_BYTES.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let p = System.realloc(ptr, layout, new_size);
+        if !p.is_null() {
+            CURRENT_BYTES.fetch_sub(layout.size(), Ordering::Relaxed);
+            Self::record_alloc(new_size);
+        }
+        p
+    }
+}
+
+/// AllocStats is a reading of the counters
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AllocStats {
+    /// number of allocations (including reallocations) made
+    pub allocations: usize,
+    /// bytes allocated right now
+    pub current_bytes: usize,
+    /// most bytes allocated at once since the last reset_peak
+    pub peak_bytes: usize,
+}
+
+impl AllocStats {
+    /// read takes the current values of the counters
+    pub fn read() -> AllocStats {
+        AllocStats {
+            allocations: ALLOCATIONS.load(Ordering::Relaxed),
+            current_bytes: CURRENT_BYTES.load(Ordering::Relaxed),
+            peak_bytes: PEAK_BYTES.load(Ordering::Relaxed),
+        }
+    }
+
+    /// allocations_since is the number of allocations made between `earlier` and this reading
+    pub fn allocations_since(&self, earlier: &AllocStats) -> usize {
+        self.allocations - earlier.allocations
+    }
+}
+
+/// reset_peak starts tracking the peak again from the bytes currently allocated, so the peak of
+/// the next phase of work can be measured on its own
+pub fn reset_peak() {
+    PEAK_BYTES.store(CURRENT_BYTES.load(Ordering::Relaxed), Ordering::Relaxed);
+}
@@ -0,0 +1,532 @@
+//! Helpers that work on a resolved route: the list of systems from start to destination.
+//!
+//! Finding a route is up to router (and astar under it); everything done with one afterwards is
+//! here: the RouteHop and RouteSummary describing it, route_legs, remaining_route and
+//! navigation_steps to follow it, route_diff to compare it with another, and validate_path to
+//! check it.
+use crate::bfs::DistanceTable;
+use crate::evemap::{Map, SolarSystemId, SolarSystemIndex};
+use crate::security::SecurityClass;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt::{Debug, Display};
+
+/// RouteHop is a single system along a route. Field order is part of the JSON format.
+#[derive(Debug, Clone, Serialize)]
+pub struct RouteHop {
+    pub name: String,
+    pub solar_system_id: SolarSystemId,
+    pub security: f32,
+}
+
+impl RouteHop {
+    pub fn new(map: &Map, i: &SolarSystemIndex) -> Self {
+        let info = map.get_extended_solarsystem_info(i);
+        RouteHop {
+            name: info.name.clone(),
+            solar_system_id: info.solar_system_id,
+            security: info.security_rounded(),
+        }
+    }
+}
+
+/// RouteSummary is the headline information about a route, that a player reads before the hops.
+/// It's in the JSON output, where field order is part of the format.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RouteSummary {
+    pub jumps: usize,
+    /// number of high-sec systems on the route, including the start and destination
+    pub high_sec: usize,
+    pub low_sec: usize,
+    pub null_sec: usize,
+    /// the lowest true security of any system on the route, None for an empty route
+    pub lowest_security: Option<f32>,
+    /// true when every system on the route is high-sec
+    pub all_high_sec: bool,
+    /// jumps from the home system to the start of the route, when there's a home (see with_home)
+    /// that can reach it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_from_home: Option<u32>,
+    /// jumps from the home system to the destination
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_from_home: Option<u32>,
+}
+
+impl RouteSummary {
+    pub fn new(map: &Map, route: &[SolarSystemIndex]) -> Self {
+        let mut summary = RouteSummary {
+            jumps: route.len().saturating_sub(1),
+            high_sec: 0,
+            low_sec: 0,
+            null_sec: 0,
+            lowest_security: None,
+            all_high_sec: false,
+            start_from_home: None,
+            end_from_home: None,
+        };
+
+        for i in route {
+            let info = map.get_extended_solarsystem_info(i);
+            match info.security_class() {
+                SecurityClass::High => summary.high_sec += 1,
+                SecurityClass::Low => summary.low_sec += 1,
+                SecurityClass::Null => summary.null_sec += 1,
+            }
+            let security = info.security_true();
+            summary.lowest_security = Some(summary.lowest_security.map_or(security, |l| l.min(security)));
+        }
+
+        summary.all_high_sec = map.is_route_highsec(route, false);
+        summary
+    }
+
+    /// with_home adds how far the start and end of `route` are from home, the origin of `home`.
+    /// The table is from Map::distances_from, and can be kept for every route from the same home.
+    pub fn with_home(mut self, route: &[SolarSystemIndex], home: &DistanceTable) -> Self {
+        self.start_from_home = route.first().and_then(|i| home.get(i));
+        self.end_from_home = route.last().and_then(|i| home.get(i));
+        self
+    }
+}
+
+impl Display for RouteSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} jumps: {} high-sec, {} low-sec, {} null-sec",
+            self.jumps, self.high_sec, self.low_sec, self.null_sec
+        )?;
+        if let Some(lowest) = self.lowest_security {
+            write!(f, ", lowest security {:.2}", lowest)?;
+        }
+        if self.all_high_sec {
+            write!(f, " (all high-sec)")?;
+        }
+        if let (Some(start), Some(end)) = (self.start_from_home, self.end_from_home) {
+            write!(f, ", {start} and {end} jumps from home")?;
+        }
+        Ok(())
+    }
+}
+
+/// NavStep is one instruction for flying a route, as an in-game overlay would show it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NavStep {
+    /// in `system`, jump through the stargate to `destination`
+    Jump {
+        system: SolarSystemIndex,
+        destination: SolarSystemIndex,
+        /// the stargate to activate, None if the map has no stargate for the jump
+        stargate_id: Option<u64>,
+    },
+    /// the route is done, at `system`
+    Arrive { system: SolarSystemIndex },
+}
+
+/// route_legs are the jumps along a route, as consecutive (from, to) pairs: one fewer than there
+/// are systems on the route
+pub fn route_legs(route: &[SolarSystemIndex]) -> impl Iterator<Item = (SolarSystemIndex, SolarSystemIndex)> + '_ {
+    route.windows(2).map(|w| (w[0], w[1]))
+}
+
+/// RouteDiff is how a new route differs from an old one, eg. after replanning around a camp: the
+/// systems both start and end with, and the stretch in between that each takes instead.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouteDiff<'a> {
+    /// systems both routes start with
+    pub common_start: &'a [SolarSystemIndex],
+    /// systems of the old route, after the common start and before the common end
+    pub removed: &'a [SolarSystemIndex],
+    /// systems of the new route taking their place
+    pub added: &'a [SolarSystemIndex],
+    /// systems both routes end with
+    pub common_end: &'a [SolarSystemIndex],
+}
+
+impl RouteDiff<'_> {
+    /// is_same is true when the routes are the same
+    pub fn is_same(&self) -> bool {
+        self.removed.is_empty() && self.added.is_empty()
+    }
+}
+
+/// route_diff compares two routes from the outside in: the longest stretch they start with, the
+/// longest they end with (not overlapping the start), and what's left of each in between. Two
+/// routes that part ways more than once differ in one stretch from the first parting to the last.
+pub fn route_diff<'a>(old: &'a [SolarSystemIndex], new: &'a [SolarSystemIndex]) -> RouteDiff<'a> {
+    let start = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let end = old[start..]
+        .iter()
+        .rev()
+        .zip(new[start..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    RouteDiff {
+        common_start: &new[..start],
+        removed: &old[start..old.len() - end],
+        added: &new[start..new.len() - end],
+        common_end: &new[new.len() - end..],
+    }
+}
+
+impl Map {
+    /// remaining_route is what's left of `full_route` for a pilot now in `current`: the route
+    /// from `current` to the destination. None means the pilot has gone off the route, and it
+    /// should be planned again from `current`. Routes don't revisit systems, but if this one does,
+    /// the pilot is taken to be at the later visit.
+    pub fn remaining_route<'a>(
+        &self,
+        full_route: &'a [SolarSystemIndex],
+        current: SolarSystemIndex,
+    ) -> Option<&'a [SolarSystemIndex]> {
+        let at = full_route.iter().rposition(|i| *i == current)?;
+        Some(&full_route[at..])
+    }
+
+    /// is_route_highsec is true when every system on a non-empty route has a security of 0.5 or
+    /// more: the true security, or the one displayed in game when `rounded`. Only the true
+    /// security decides the game mechanics, so a route that is only high-sec when `rounded` passes
+    /// through low-sec systems that merely look like high-sec.
+    pub fn is_route_highsec(&self, route: &[SolarSystemIndex], rounded: bool) -> bool {
+        !route.is_empty()
+            && route.iter().all(|i| {
+                let info = self.get_extended_solarsystem_info(i);
+                let security = if rounded { info.security_rounded() } else { info.security_true() };
+                SecurityClass::from_security(security) == SecurityClass::High
+            })
+    }
+
+    /// first_common_system is where two routes first meet: the first system along `a` that's also
+    /// on `b`, with its position in each route. None if the routes don't share a system.
+    pub fn first_common_system(
+        &self,
+        a: &[SolarSystemIndex],
+        b: &[SolarSystemIndex],
+    ) -> Option<(SolarSystemIndex, usize, usize)> {
+        let on_b: HashMap<SolarSystemIndex, usize> = b.iter().enumerate().rev().map(|(at, i)| (*i, at)).collect();
+        a.iter().enumerate().find_map(|(at, i)| on_b.get(i).map(|at_b| (*i, at, *at_b)))
+    }
+
+    /// describe_route puts a route into a sentence, eg. for a chat bot to post: "12 jumps from
+    /// Jita to Amarr, crossing 3 regions, 4 low-sec systems, lowest security 0.2." Security is as
+    /// the game displays it, and the low and null-sec counts are left out when there are none.
+    pub fn describe_route(&self, route: &[SolarSystemIndex]) -> String {
+        let (Some(first), Some(last)) = (route.first(), route.last()) else {
+            return "No route.".to_string();
+        };
+        let name = |i: &SolarSystemIndex| self.get_extended_solarsystem_info(i).name.as_str();
+        let plural = |n: usize, what: &str| format!("{n} {what}{}", if n == 1 { "" } else { "s" });
+        if route.len() == 1 {
+            return format!("0 jumps, already in {}.", name(first));
+        }
+
+        let summary = RouteSummary::new(self, route);
+        let mut parts = vec![format!("{} from {} to {}", plural(summary.jumps, "jump"), name(first), name(last))];
+
+        let mut regions: Vec<u64> = route.iter().map(|i| self.get_extended_solarsystem_info(i).region_id).collect();
+        regions.sort_unstable();
+        regions.dedup();
+        parts.push(match regions[..] {
+            [region] => format!("within {}", self.name_of(region).unwrap_or("one region")),
+            _ => format!("crossing {}", plural(regions.len(), "region")),
+        });
+
+        if summary.all_high_sec {
+            parts.push("all high-sec".to_string());
+        } else {
+            if summary.low_sec > 0 {
+                parts.push(plural(summary.low_sec, "low-sec system"));
+            }
+            if summary.null_sec > 0 {
+                parts.push(plural(summary.null_sec, "null-sec system"));
+            }
+            let lowest = route
+                .iter()
+                .map(|i| self.get_extended_solarsystem_info(i).security_rounded())
+                .fold(f32::INFINITY, f32::min);
+            parts.push(format!("lowest security {lowest:.1}"));
+        }
+
+        format!("{}.", parts.join(", "))
+    }
+
+    /// navigation_steps turns a route into turn by turn instructions: a Jump for each jump on the
+    /// route, naming the stargate to take, followed by an Arrive at the destination. An empty
+    /// route has no steps.
+    pub fn navigation_steps(&self, route: &[SolarSystemIndex]) -> Vec<NavStep> {
+        let mut steps: Vec<NavStep> = route_legs(route)
+            .map(|(system, destination)| NavStep::Jump {
+                system,
+                destination,
+                stargate_id: self
+                    .get_extended_solarsystem_info(&system)
+                    .gates
+                    .iter()
+                    .find(|g| g.destination == destination)
+                    .and_then(|g| g.stargate_id),
+            })
+            .collect();
+
+        if let Some(system) = route.last() {
+            steps.push(NavStep::Arrive { system: *system });
+        }
+        steps
+    }
+}
+
+/// ValidationError is why validate_path rejected a path
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValidationError<Node> {
+    /// a path has to at least have a starting point
+    EmptyPath,
+    /// `to` isn't a neighbour of `from`, which are at `index` and `index + 1` in the path
+    NotAdjacent { index: usize, from: Node, to: Node },
+}
+
+impl<Node: Debug> std::fmt::Display for ValidationError<Node> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValidationError::EmptyPath => write!(f, "path is empty"),
+            ValidationError::NotAdjacent { index, from, to } => {
+                write!(f, "step {index} of the path, {from:?} to {to:?}, is not between neighbours")
+            }
+        }
+    }
+}
+
+impl<Node: Debug> std::error::Error for ValidationError<Node> {}
+
+/// validate_path checks that each node in `path` is a neighbour of the one before it and returns
+/// the total cost of the path, summing `cost` over each step. It doesn't care how the path was
+/// found, so it can check results from astar or anything else against the same neighbours.
+/// A path of a single node is valid and costs zero.
+pub fn validate_path<Node, Cost, Neighbours, NeighboursFn, CostFn>(
+    path: &[Node],
+    neighbours: NeighboursFn,
+    cost: CostFn,
+) -> Result<Cost, ValidationError<Node>>
+where
+    Node: Copy + PartialEq,
+    Cost: num::Zero + std::ops::Add<Output = Cost>,
+    Neighbours: IntoIterator<Item = Node>,
+    NeighboursFn: Fn(&Node) -> Neighbours,
+    CostFn: Fn(&Node, &Node) -> Cost,
+{
+    if path.is_empty() {
+        return Err(ValidationError::EmptyPath);
+    }
+
+    path.windows(2).enumerate().try_fold(Cost::zero(), |total, (index, step)| {
+        let (from, to) = (step[0], step[1]);
+        if !neighbours(&from).into_iter().any(|n| n == to) {
+            return Err(ValidationError::NotAdjacent { index, from, to });
+        }
+        Ok(total + cost(&from, &to))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{sde_map, system};
+
+    /// route is the systems called `names`, in order
+    fn route(map: &Map, names: &[&str]) -> Vec<SolarSystemIndex> {
+        names.iter().map(|n| system(map, n)).collect()
+    }
+
+    #[test]
+    fn route_summary_counts_each_security_class() {
+        let map = sde_map();
+        let route = route(&map, &["Jita", "EC-P8R", "Tama", "Ashab"]);
+        let summary = RouteSummary::new(&map, &route);
+        assert_eq!((summary.jumps, summary.high_sec, summary.low_sec, summary.null_sec), (3, 1, 2, 1));
+        assert_eq!(summary.lowest_security, Some(-0.1));
+        assert!(!summary.all_high_sec);
+        assert_eq!(
+            summary.to_string(),
+            "3 jumps: 1 high-sec, 2 low-sec, 1 null-sec, lowest security -0.10"
+        );
+    }
+
+    #[test]
+    fn route_summary_of_a_high_sec_route_from_home() {
+        let map = sde_map();
+        let route = route(&map, &["Jita", "Yulai", "Amarr"]);
+        let home = map.distances_from(&system(&map, "Perimeter"));
+        let summary = RouteSummary::new(&map, &route).with_home(&route, &home);
+        assert!(summary.all_high_sec);
+        assert_eq!((summary.start_from_home, summary.end_from_home), (Some(1), Some(3)));
+        assert_eq!(
+            summary.to_string(),
+            "2 jumps: 3 high-sec, 0 low-sec, 0 null-sec, lowest security 0.90 (all high-sec), 1 and 3 jumps from home"
+        );
+    }
+
+    #[test]
+    fn route_summary_of_an_empty_route() {
+        let summary = RouteSummary::new(&sde_map(), &[]);
+        assert_eq!((summary.jumps, summary.lowest_security), (0, None));
+    }
+
+    #[test]
+    fn navigation_steps_name_the_stargate_of_each_jump() {
+        let map = sde_map();
+        let route = route(&map, &["Jita", "Yulai", "Amarr"]);
+        assert_eq!(
+            map.navigation_steps(&route),
+            [
+                NavStep::Jump { system: route[0], destination: route[1], stargate_id: Some(50000017) },
+                NavStep::Jump { system: route[1], destination: route[2], stargate_id: Some(50000024) },
+                NavStep::Arrive { system: route[2] },
+            ]
+        );
+        assert!(map.navigation_steps(&[]).is_empty());
+    }
+
+    #[test]
+    fn navigation_steps_of_a_jump_without_a_stargate() {
+        let mut builder = crate::builder::MapBuilder::new();
+        builder.add_system(30_000_000, "A", 2, 1, 1.0).add_system(30_000_001, "B", 2, 1, 1.0);
+        builder.add_jump(30_000_000, 30_000_001, None);
+        let map = builder.build().unwrap();
+        let (a, b) = (SolarSystemIndex(0), SolarSystemIndex(1));
+        let steps = map.navigation_steps(&[a, b]);
+        assert_eq!(steps[0], NavStep::Jump { system: a, destination: b, stargate_id: None });
+    }
+
+    #[test]
+    fn validate_path_costs_a_route_found_by_astar() {
+        let map = sde_map();
+        let found = map.shortest_route(system(&map, "Amarr"), system(&map, "Tama")).unwrap();
+        assert_eq!(validate_path(&found, |n| map.get_neighbours(n), |_, _| 1u32), Ok(4));
+
+        let jita = [system(&map, "Jita")];
+        assert_eq!(validate_path(&jita, |n| map.get_neighbours(n), |_, _| 1u32), Ok(0));
+    }
+
+    #[test]
+    fn validate_path_rejects_a_step_between_systems_that_are_not_neighbours() {
+        let map = sde_map();
+        let path = route(&map, &["Jita", "Yulai", "Sarum Prime"]);
+        let err = validate_path(&path, |n| map.get_neighbours(n), |_, _| 1u32).unwrap_err();
+        assert_eq!(err, ValidationError::NotAdjacent { index: 1, from: path[1], to: path[2] });
+        assert!(err.to_string().starts_with("step 1 of the path"));
+
+        let empty: [SolarSystemIndex; 0] = [];
+        assert_eq!(validate_path(&empty, |n| map.get_neighbours(n), |_, _| 1u32), Err(ValidationError::EmptyPath));
+    }
+
+    #[test]
+    fn remaining_route_starts_where_the_pilot_is() {
+        let map = sde_map();
+        let full = route(&map, &["Jita", "Perimeter", "Tama", "Ashab"]);
+        assert_eq!(map.remaining_route(&full, full[1]), Some(&full[1..]));
+        assert_eq!(map.remaining_route(&full, full[0]), Some(&full[..]));
+        assert_eq!(map.remaining_route(&full, full[3]), Some(&full[3..]));
+        assert_eq!(map.remaining_route(&full, system(&map, "Amarr")), None);
+    }
+
+    #[test]
+    fn remaining_route_takes_the_later_visit_of_a_system() {
+        let map = sde_map();
+        let looping = route(&map, &["Jita", "Perimeter", "Jita", "Yulai"]);
+        assert_eq!(map.remaining_route(&looping, looping[0]), Some(&looping[2..]));
+    }
+
+    #[test]
+    fn first_common_system_is_the_first_along_a_on_b() {
+        let map = sde_map();
+        let a = route(&map, &["Amarr", "Yulai", "Jita", "Perimeter", "Tama"]);
+        let b = route(&map, &["Ashab", "Tama", "Perimeter", "Jita"]);
+        assert_eq!(map.first_common_system(&a, &b), Some((system(&map, "Jita"), 2, 3)));
+        assert_eq!(map.first_common_system(&b, &a), Some((system(&map, "Tama"), 1, 4)));
+
+        let c = route(&map, &["Sarum Prime", "Niarja"]);
+        assert_eq!(map.first_common_system(&a, &c), None);
+        assert_eq!(map.first_common_system(&a, &[]), None);
+    }
+
+    #[test]
+    fn route_legs_are_the_jumps_in_order() {
+        let (a, b, c) = (SolarSystemIndex(4), SolarSystemIndex(2), SolarSystemIndex(7));
+        assert_eq!(route_legs(&[a, b, c]).collect::<Vec<_>>(), [(a, b), (b, c)]);
+        assert_eq!(route_legs(&[a]).count(), 0);
+        assert_eq!(route_legs(&[]).count(), 0);
+    }
+
+    #[test]
+    fn is_route_highsec_by_true_or_rounded_security() {
+        let map = sde_map();
+        // Ashab's true security is 0.45, displayed as 0.5
+        let through_ashab = route(&map, &["Niarja", "Ashab", "Madirmilire"]);
+        assert!(!map.is_route_highsec(&through_ashab, false));
+        assert!(map.is_route_highsec(&through_ashab, true));
+        assert!(map.is_route_highsec(&route(&map, &["Jita", "Yulai", "Amarr"]), false));
+        assert!(!map.is_route_highsec(&route(&map, &["Jita", "EC-P8R"]), true));
+        assert!(!map.is_route_highsec(&[], true));
+    }
+
+    #[test]
+    fn route_hop_has_the_name_id_and_displayed_security() {
+        let map = sde_map();
+        let hop = RouteHop::new(&map, &system(&map, "Ashab"));
+        assert_eq!((hop.name.as_str(), hop.solar_system_id), ("Ashab", SolarSystemId(30_000_005)));
+        assert_eq!(hop.security, 0.5);
+    }
+
+    #[test]
+    fn route_diff_finds_the_stretch_that_changed() {
+        let map = sde_map();
+        let old = route(&map, &["Jita", "Perimeter", "Madirmilire", "Niarja", "Sarum Prime", "Amarr"]);
+        let new = route(&map, &["Jita", "Yulai", "Amarr"]);
+        let diff = route_diff(&old, &new);
+        assert_eq!(diff.common_start, &old[..1]);
+        assert_eq!(diff.removed, &old[1..5]);
+        assert_eq!(diff.added, &new[1..2]);
+        assert_eq!(diff.common_end, &old[5..]);
+        assert!(!diff.is_same());
+    }
+
+    #[test]
+    fn route_diff_of_routes_that_share_an_end_or_are_the_same() {
+        let map = sde_map();
+        let old = route(&map, &["Tama", "Perimeter", "Jita"]);
+        // a route that's only extended has nothing removed
+        let longer = route(&map, &["Tama", "Perimeter", "Jita", "Yulai"]);
+        let diff = route_diff(&old, &longer);
+        assert_eq!((diff.common_start, diff.removed, diff.added), (&old[..], &[][..], &longer[3..]));
+        assert!(diff.common_end.is_empty());
+
+        // the common end never overlaps the common start
+        let shorter = route(&map, &["Tama", "Jita"]);
+        let diff = route_diff(&old, &shorter);
+        assert_eq!((diff.common_start, diff.removed, diff.added), (&old[..1], &old[1..2], &[][..]));
+        assert_eq!(diff.common_end, &old[2..]);
+
+        let diff = route_diff(&old, &old);
+        assert!(diff.is_same());
+        assert_eq!((diff.common_start, diff.common_end), (&old[..], &[][..]));
+        assert!(route_diff(&[], &[]).is_same());
+    }
+
+    #[test]
+    fn describe_route_sums_up_jumps_regions_and_security() {
+        let map = sde_map();
+        assert_eq!(
+            map.describe_route(&route(&map, &["Jita", "Perimeter"])),
+            "1 jump from Jita to Perimeter, within The Forge, all high-sec."
+        );
+        assert_eq!(
+            map.describe_route(&route(&map, &["Jita", "EC-P8R", "Tama", "Ashab"])),
+            "3 jumps from Jita to Ashab, crossing 4 regions, 2 low-sec systems, 1 null-sec system, lowest security 0.0."
+        );
+    }
+
+    #[test]
+    fn describe_route_of_no_jumps() {
+        let map = sde_map();
+        assert_eq!(map.describe_route(&[]), "No route.");
+        assert_eq!(map.describe_route(&route(&map, &["Amarr"])), "0 jumps, already in Amarr.");
+    }
+}
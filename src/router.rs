@@ -0,0 +1,779 @@
+//! Convenience entry points to run astar over a Map.
+//!
+//! These set up the simple open and closed lists, seed them with the start and unwind the result,
+//! which is the same dance that every caller would otherwise have to repeat.
+use crate::astar;
+use crate::astar::ClosedListState::{PathFrom, StartingPoint, Unvisited};
+use crate::astar::{AStarError, AStarStats, CheckedCost, ClosedList, OpenList};
+use crate::cost::{CostModel, GateToGateTimeModel, RegionPreferenceCostModel};
+use crate::evemap::{Map, SolarSystemIndex};
+use crate::hashclosed::HashClosed;
+use crate::security::SecurityClass;
+use crate::simpleclosed::SimpleClosed;
+use crate::simpleopen::SimpleOpenList;
+use ordered_float::{NotNan, OrderedFloat};
+use rayon::prelude::*;
+use std::collections::{BinaryHeap, HashSet};
+
+impl Map {
+    /// route_with_cost runs A* from `from` to `to`, using `edge_cost` for the cost of each jump
+    /// and `heuristic` for the estimated cost from a system to `to`.
+    /// If `max_cost` is given, paths costing more than it are not explored.
+    /// It returns the path (including both ends) and its total cost.
+    ///
+    /// Like every route in this module, the route from a system to itself is just that system,
+    /// at a cost of zero.
+    pub fn route_with_cost<Cost, EdgeCostFn, HeuristicFn>(
+        &self,
+        from: SolarSystemIndex,
+        to: SolarSystemIndex,
+        edge_cost: EdgeCostFn,
+        heuristic: HeuristicFn,
+        max_cost: Option<Cost>,
+    ) -> Result<(Vec<SolarSystemIndex>, Cost), AStarError>
+    where
+        Cost: Ord + Copy + num::Zero + CheckedCost,
+        EdgeCostFn: Fn(&SolarSystemIndex, &SolarSystemIndex) -> Cost,
+        HeuristicFn: Fn(&SolarSystemIndex) -> Cost,
+    {
+        self.route_with_stats(from, to, edge_cost, heuristic, max_cost, &mut AStarStats::default())
+    }
+
+    /// route_with_stats is route_with_cost, additionally recording the work done in `stats`
+    pub fn route_with_stats<Cost, EdgeCostFn, HeuristicFn>(
+        &self,
+        from: SolarSystemIndex,
+        to: SolarSystemIndex,
+        edge_cost: EdgeCostFn,
+        heuristic: HeuristicFn,
+        max_cost: Option<Cost>,
+        stats: &mut AStarStats,
+    ) -> Result<(Vec<SolarSystemIndex>, Cost), AStarError>
+    where
+        Cost: Ord + Copy + num::Zero + CheckedCost,
+        EdgeCostFn: Fn(&SolarSystemIndex, &SolarSystemIndex) -> Cost,
+        HeuristicFn: Fn(&SolarSystemIndex) -> Cost,
+    {
+        self.route_filtered(from, to, edge_cost, heuristic, max_cost, |_, _| false, SimpleOpenList::new, stats)
+    }
+
+    /// route_with_open_list is route_with_cost, searching with an open list from `new_open` rather
+    /// than a SimpleOpenList, eg. `DaryOpenList::<_, _, 8>::new`. Every open list finds a route of the
+    /// same cost, so this is only a choice between their memory use and speed, which a single Map can
+    /// make query by query.
+    ///
+    /// `new_open` can also lend an open list kept between searches, eg.
+    /// `|| { open.clear(); &mut open }`, to save allocating one for every query.
+    pub fn route_with_open_list<Cost, EdgeCostFn, HeuristicFn, Open, NewOpenFn>(
+        &self,
+        from: SolarSystemIndex,
+        to: SolarSystemIndex,
+        edge_cost: EdgeCostFn,
+        heuristic: HeuristicFn,
+        max_cost: Option<Cost>,
+        new_open: NewOpenFn,
+    ) -> Result<(Vec<SolarSystemIndex>, Cost), AStarError>
+    where
+        Cost: Ord + Copy + num::Zero + CheckedCost,
+        EdgeCostFn: Fn(&SolarSystemIndex, &SolarSystemIndex) -> Cost,
+        HeuristicFn: Fn(&SolarSystemIndex) -> Cost,
+        Open: OpenList<astar::OpenItem<SolarSystemIndex, Cost>>,
+        NewOpenFn: FnOnce() -> Open,
+    {
+        self.route_with_open_list_and_stats(
+            from,
+            to,
+            edge_cost,
+            heuristic,
+            max_cost,
+            new_open,
+            &mut AStarStats::default(),
+        )
+    }
+
+    /// route_with_open_list_and_stats is route_with_open_list, additionally recording the work
+    /// done in `stats`
+    #[allow(clippy::too_many_arguments)]
+    pub fn route_with_open_list_and_stats<Cost, EdgeCostFn, HeuristicFn, Open, NewOpenFn>(
+        &self,
+        from: SolarSystemIndex,
+        to: SolarSystemIndex,
+        edge_cost: EdgeCostFn,
+        heuristic: HeuristicFn,
+        max_cost: Option<Cost>,
+        new_open: NewOpenFn,
+        stats: &mut AStarStats,
+    ) -> Result<(Vec<SolarSystemIndex>, Cost), AStarError>
+    where
+        Cost: Ord + Copy + num::Zero + CheckedCost,
+        EdgeCostFn: Fn(&SolarSystemIndex, &SolarSystemIndex) -> Cost,
+        HeuristicFn: Fn(&SolarSystemIndex) -> Cost,
+        Open: OpenList<astar::OpenItem<SolarSystemIndex, Cost>>,
+        NewOpenFn: FnOnce() -> Open,
+    {
+        self.route_filtered(from, to, edge_cost, heuristic, max_cost, |_, _| false, new_open, stats)
+    }
+
+    /// route_with_model is route_with_cost, with the cost of each jump coming from a CostModel.
+    /// `model` can be a `&dyn CostModel`, to pick the model at runtime. There's no heuristic, since
+    /// a lower bound depends on the model.
+    pub fn route_with_model<M>(
+        &self,
+        from: SolarSystemIndex,
+        to: SolarSystemIndex,
+        model: &M,
+        max_cost: Option<M::Cost>,
+    ) -> Result<(Vec<SolarSystemIndex>, M::Cost), AStarError>
+    where
+        M: CostModel + ?Sized,
+        M::Cost: Ord + Copy + num::Zero + CheckedCost,
+    {
+        let zero = <M::Cost as num::Zero>::zero();
+        self.route_with_cost(from, to, |a, b| model.edge_cost(self, a, b), |_| zero, max_cost)
+    }
+
+    /// route_bidirectional is route_with_cost without a heuristic, searching from both ends at once
+    /// with astar::bidirectional_dijkstra. For cost models with no useful heuristic (danger, time)
+    /// it explores roughly half as much.
+    pub fn route_bidirectional<Cost, EdgeCostFn>(
+        &self,
+        from: SolarSystemIndex,
+        to: SolarSystemIndex,
+        edge_cost: EdgeCostFn,
+    ) -> Result<(Vec<SolarSystemIndex>, Cost), AStarError>
+    where
+        Cost: Ord + Copy + num::Zero + CheckedCost,
+        EdgeCostFn: Fn(&SolarSystemIndex, &SolarSystemIndex) -> Cost,
+    {
+        self.route_bidirectional_with_stats(from, to, edge_cost, &mut AStarStats::default())
+    }
+
+    /// route_bidirectional_with_stats is route_bidirectional, additionally recording the work done
+    /// by both searches in `stats`
+    pub fn route_bidirectional_with_stats<Cost, EdgeCostFn>(
+        &self,
+        from: SolarSystemIndex,
+        to: SolarSystemIndex,
+        edge_cost: EdgeCostFn,
+        stats: &mut AStarStats,
+    ) -> Result<(Vec<SolarSystemIndex>, Cost), AStarError>
+    where
+        Cost: Ord + Copy + num::Zero + CheckedCost,
+        EdgeCostFn: Fn(&SolarSystemIndex, &SolarSystemIndex) -> Cost,
+    {
+        if !self.path_exists(&from, &to) {
+            return Err(AStarError::PathNotFound);
+        }
+
+        let (mut forward_open, mut backward_open) = (SimpleOpenList::new(), SimpleOpenList::new());
+        let mut forward_closed = SimpleClosed::new(self.system_count());
+        let mut backward_closed = SimpleClosed::new(self.system_count());
+        let edge_cost = &edge_cost;
+
+        astar::bidirectional_dijkstra(
+            from,
+            to,
+            astar::BidirectionalLists {
+                open: &mut forward_open,
+                closed: &mut forward_closed,
+            },
+            astar::BidirectionalLists {
+                open: &mut backward_open,
+                closed: &mut backward_closed,
+            },
+            |&n| self.get_neighbours_sorted(&n).map(move |p| (edge_cost(&n, &p), p)),
+            |&n| self.get_predecessors(&n).map(move |p| (edge_cost(&p, &n), p)),
+            stats,
+        )
+    }
+
+    /// route_gate_to_gate finds the fastest route from `from` to `to` by the GateToGateTimeModel,
+    /// with its estimated seconds. A jump's cost depends on the gate the ship arrived by, so the
+    /// search is over (system, previous system) pairs: a system can be reached more than once, by
+    /// different gates.
+    pub fn route_gate_to_gate(
+        &self,
+        from: SolarSystemIndex,
+        to: SolarSystemIndex,
+        model: &GateToGateTimeModel,
+    ) -> Result<(Vec<SolarSystemIndex>, NotNan<f32>), AStarError> {
+        let zero = NotNan::default();
+        if from == to {
+            return Ok((vec![from], zero));
+        }
+        if !self.path_exists(&from, &to) {
+            return Err(AStarError::PathNotFound);
+        }
+
+        let start = (from, None);
+        let mut open = SimpleOpenList::new();
+        let mut closed = HashClosed::new();
+        open.push_open(astar::OpenItem {
+            heuristic: zero,
+            node: start,
+        });
+        closed[start] = StartingPoint(zero);
+
+        let goal = astar::astar(
+            &mut open,
+            &mut closed,
+            |(system, _)| *system == to,
+            |_| zero,
+            |&(system, previous): &(SolarSystemIndex, Option<SolarSystemIndex>)| {
+                self.get_neighbours_sorted(&system)
+                    .map(move |n| (model.edge_cost(self, previous.as_ref(), &system, &n), (n, Some(system))))
+            },
+        )?;
+
+        let cost = match closed[goal] {
+            PathFrom(_, c) | StartingPoint(c) => c,
+            Unvisited => return Err(AStarError::OpenItemNotInClosedList),
+        };
+
+        Ok((closed.unwind(goal).into_iter().map(|(system, _)| system).collect(), cost))
+    }
+
+    /// route_avoiding finds the route with the fewest jumps that never enters a system for which
+    /// `avoid` is true. The predicate is checked as systems are expanded, so it can be any runtime
+    /// condition, eg. `|n| map.get_extended_solarsystem_info(n).security_true() < 0.3`, or
+    /// membership of a set of systems. The starting system is exempt, since you're already there.
+    pub fn route_avoiding<AvoidFn>(
+        &self,
+        from: SolarSystemIndex,
+        to: SolarSystemIndex,
+        avoid: AvoidFn,
+    ) -> Result<Vec<SolarSystemIndex>, AStarError>
+    where
+        AvoidFn: Fn(&SolarSystemIndex) -> bool,
+    {
+        self.route_with_cost_avoiding(from, to, |_, _| 1u32, |_| 0, None, avoid)
+            .map(|(path, _)| path)
+    }
+
+    /// route_avoiding_edges finds the route with the fewest jumps that doesn't take any of the
+    /// `forbidden` jumps, in either direction, eg. a camped gate. Unlike route_avoiding, the
+    /// systems at either end can still be passed through, using their other stargates.
+    pub fn route_avoiding_edges(
+        &self,
+        from: SolarSystemIndex,
+        to: SolarSystemIndex,
+        forbidden: &HashSet<(SolarSystemIndex, SolarSystemIndex)>,
+    ) -> Result<Vec<SolarSystemIndex>, AStarError> {
+        self.route_filtered(
+            from,
+            to,
+            |_, _| 1u32,
+            |_| 0,
+            None,
+            |a, b| forbidden.contains(&(*a, *b)) || forbidden.contains(&(*b, *a)),
+            SimpleOpenList::new,
+            &mut AStarStats::default(),
+        )
+        .map(|(path, _)| path)
+    }
+
+    /// route_with_cost_avoiding is route_with_cost, but never enters a system for which `avoid`
+    /// is true (see route_avoiding)
+    pub fn route_with_cost_avoiding<Cost, EdgeCostFn, HeuristicFn, AvoidFn>(
+        &self,
+        from: SolarSystemIndex,
+        to: SolarSystemIndex,
+        edge_cost: EdgeCostFn,
+        heuristic: HeuristicFn,
+        max_cost: Option<Cost>,
+        avoid: AvoidFn,
+    ) -> Result<(Vec<SolarSystemIndex>, Cost), AStarError>
+    where
+        Cost: Ord + Copy + num::Zero + CheckedCost,
+        EdgeCostFn: Fn(&SolarSystemIndex, &SolarSystemIndex) -> Cost,
+        HeuristicFn: Fn(&SolarSystemIndex) -> Cost,
+        AvoidFn: Fn(&SolarSystemIndex) -> bool,
+    {
+        self.route_filtered(
+            from,
+            to,
+            edge_cost,
+            heuristic,
+            max_cost,
+            |_, to| avoid(to),
+            SimpleOpenList::new,
+            &mut AStarStats::default(),
+        )
+    }
+
+    /// route_filtered is the search behind the route_with_* functions: astar over the map, not
+    /// taking jumps (from, to) that are avoided, with an open list from `new_open`
+    #[allow(clippy::too_many_arguments)]
+    fn route_filtered<Cost, EdgeCostFn, HeuristicFn, AvoidFn, Open, NewOpenFn>(
+        &self,
+        from: SolarSystemIndex,
+        to: SolarSystemIndex,
+        edge_cost: EdgeCostFn,
+        heuristic: HeuristicFn,
+        max_cost: Option<Cost>,
+        avoid: AvoidFn,
+        new_open: NewOpenFn,
+        stats: &mut AStarStats,
+    ) -> Result<(Vec<SolarSystemIndex>, Cost), AStarError>
+    where
+        Cost: Ord + Copy + num::Zero + CheckedCost,
+        EdgeCostFn: Fn(&SolarSystemIndex, &SolarSystemIndex) -> Cost,
+        HeuristicFn: Fn(&SolarSystemIndex) -> Cost,
+        AvoidFn: Fn(&SolarSystemIndex, &SolarSystemIndex) -> bool,
+        Open: OpenList<astar::OpenItem<SolarSystemIndex, Cost>>,
+        NewOpenFn: FnOnce() -> Open,
+    {
+        // already there: no jumps to take and nothing to search, whatever the costs
+        if from == to {
+            return Ok((vec![from], Cost::zero()));
+        }
+        // no need to search the whole component `from` is in, to find `to` isn't in it
+        if !self.path_exists(&from, &to) {
+            return Err(AStarError::PathNotFound);
+        }
+
+        let mut open = new_open();
+        let mut closed = SimpleClosed::new(self.system_count());
+        // borrowed, so the neighbour iterators that astar_bounded consumes lazily can capture them
+        let (edge_cost, avoid) = (&edge_cost, &avoid);
+
+        open.push_open(astar::OpenItem {
+            heuristic: heuristic(&from),
+            node: from,
+        });
+        closed[from] = StartingPoint(Cost::zero());
+
+        let goal = astar::astar_bounded(
+            &mut open,
+            &mut closed,
+            |n| n == &to,
+            heuristic,
+            |&n| {
+                self.get_neighbours_filtered(&n, move |p| avoid(&n, p))
+                    .map(move |p| (edge_cost(&n, &p), p))
+            },
+            max_cost,
+            stats,
+        )?;
+
+        let cost = match closed[goal] {
+            PathFrom(_, c) | StartingPoint(c) => c,
+            Unvisited => return Err(AStarError::OpenItemNotInClosedList),
+        };
+
+        Ok((closed.unwind(goal), cost))
+    }
+
+    /// shortest_route finds the route from `from` to `to` with the fewest jumps
+    pub fn shortest_route(
+        &self,
+        from: SolarSystemIndex,
+        to: SolarSystemIndex,
+    ) -> Result<Vec<SolarSystemIndex>, AStarError> {
+        self.route_with_cost(from, to, |_, _| 1u32, |_| 0, None)
+            .map(|(path, _)| path)
+    }
+
+    /// shortest_route_regional is shortest_route, but when systems are equally far along it
+    /// explores those in `to`'s region first. Routes within a region usually stay in it, so
+    /// the search tends to reach `to` before expanding the rest of the last ring of systems.
+    ///
+    /// It's only a tiebreak, the route has the fewest jumps all the same: each jump costs 2 and
+    /// systems outside `to`'s region get a heuristic of 1, which is less than any jump left to
+    /// take from them, so it reorders systems of the same cost without ever passing a cheaper one.
+    pub fn shortest_route_regional(
+        &self,
+        from: SolarSystemIndex,
+        to: SolarSystemIndex,
+    ) -> Result<Vec<SolarSystemIndex>, AStarError> {
+        self.shortest_route_regional_with_stats(from, to, &mut AStarStats::default())
+    }
+
+    /// shortest_route_regional_with_stats is shortest_route_regional, additionally recording the
+    /// work done in `stats`
+    pub fn shortest_route_regional_with_stats(
+        &self,
+        from: SolarSystemIndex,
+        to: SolarSystemIndex,
+        stats: &mut AStarStats,
+    ) -> Result<Vec<SolarSystemIndex>, AStarError> {
+        let region_id = self.get_extended_solarsystem_info(&to).region_id;
+        let outside_region = |n: &SolarSystemIndex| (self.get_extended_solarsystem_info(n).region_id != region_id) as u32;
+
+        self.route_with_stats(from, to, |_, _| 2u32, outside_region, None, stats)
+            .map(|(path, _)| path)
+    }
+
+    /// route_preferring_regions finds a route that keeps to the regions in `preferred`, most
+    /// preferred first, eg. a coalition's own space and then its allies'. It's a soft constraint
+    /// (see RegionPreferenceCostModel): other regions cost more to pass through, but aren't
+    /// avoided at any cost. To never enter a region, use route_avoiding.
+    pub fn route_preferring_regions(
+        &self,
+        from: SolarSystemIndex,
+        to: SolarSystemIndex,
+        preferred: &[u64],
+    ) -> Result<Vec<SolarSystemIndex>, AStarError> {
+        self.route_with_model(from, to, &RegionPreferenceCostModel::new(preferred), None)
+            .map(|(path, _)| path)
+    }
+
+    /// route_many runs shortest_route for each (from, to) pair, in parallel. The Map is only read,
+    /// and each search has its own open and closed lists, so the queries don't interfere.
+    /// Results are in the same order as the queries.
+    pub fn route_many(
+        &self,
+        queries: &[(SolarSystemIndex, SolarSystemIndex)],
+    ) -> Vec<Result<Vec<SolarSystemIndex>, AStarError>> {
+        queries
+            .par_iter()
+            .map(|(from, to)| self.shortest_route(*from, *to))
+            .collect()
+    }
+
+    /// shortest_route_within is shortest_route, but fails with ExceedsMaxCost if the route would
+    /// take more than `max_jumps` jumps. A route of exactly `max_jumps` is allowed.
+    pub fn shortest_route_within(
+        &self,
+        from: SolarSystemIndex,
+        to: SolarSystemIndex,
+        max_jumps: u32,
+    ) -> Result<Vec<SolarSystemIndex>, AStarError> {
+        self.route_with_cost(from, to, |_, _| 1u32, |_| 0, Some(max_jumps))
+            .map(|(path, _)| path)
+    }
+
+    /// safest_bottleneck_route finds the route whose least secure system is as secure as
+    /// possible, even if that makes it longer. This is a different objective to penalising
+    /// low security systems: only the single worst system on the route matters.
+    ///
+    /// It works in two passes. A "widest path" Dijkstra finds the best achievable minimum (true)
+    /// security, then a BFS restricted to systems at least that secure finds the fewest jumps
+    /// among the routes achieving it.
+    pub fn safest_bottleneck_route(
+        &self,
+        from: SolarSystemIndex,
+        to: SolarSystemIndex,
+    ) -> Option<Vec<SolarSystemIndex>> {
+        let security = |i: &SolarSystemIndex| OrderedFloat(self.get_extended_solarsystem_info(i).security_true());
+
+        let mut best: Vec<Option<OrderedFloat<f32>>> = vec![None; self.system_count()];
+        let mut heap = BinaryHeap::from([(security(&from), from)]);
+        best[usize::from(from)] = Some(security(&from));
+
+        let mut bottleneck = None;
+        while let Some((b, current)) = heap.pop() {
+            if best[usize::from(current)].is_some_and(|existing| existing > b) {
+                continue;
+            }
+            if current == to {
+                bottleneck = Some(b);
+                break;
+            }
+
+            for n in self.get_neighbours(&current) {
+                let candidate = b.min(security(&n));
+                if best[usize::from(n)].is_none_or(|existing| candidate > existing) {
+                    best[usize::from(n)] = Some(candidate);
+                    heap.push((candidate, n));
+                }
+            }
+        }
+
+        let bottleneck = bottleneck?;
+        self.bfs_route(&from, &to, |n| security(n) >= bottleneck)
+    }
+
+    /// route_with_security_classes finds the route with the fewest jumps that only enters systems
+    /// in the `allowed` security classes, eg. [High, Null] for "never low-sec, but null is fine".
+    /// The starting system is exempt, since you're already there.
+    pub fn route_with_security_classes(
+        &self,
+        from: SolarSystemIndex,
+        to: SolarSystemIndex,
+        allowed: &[SecurityClass],
+    ) -> Option<Vec<SolarSystemIndex>> {
+        self.bfs_route(&from, &to, |n| {
+            allowed.contains(&self.get_extended_solarsystem_info(n).security_class())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::daryopen::DaryOpenList;
+    use crate::fixtures::{indexes, line_map, map_from_edges, names, sde_map, system};
+
+    #[test]
+    fn shortest_route_within_allows_a_route_of_exactly_max_jumps() {
+        let map = line_map(5);
+        let route = map.shortest_route_within(SolarSystemIndex(0), SolarSystemIndex(4), 4).unwrap();
+        assert_eq!(route.len(), 5);
+    }
+
+    #[test]
+    fn shortest_route_within_fails_when_the_route_is_longer() {
+        let map = line_map(5);
+        let result = map.shortest_route_within(SolarSystemIndex(0), SolarSystemIndex(4), 3);
+        assert_eq!(result, Err(AStarError::ExceedsMaxCost));
+    }
+
+    #[test]
+    fn shortest_route_within_tells_no_path_from_too_long() {
+        let map = map_from_edges(&[("A", 1.0), ("B", 1.0), ("C", 1.0)], &[(0, 1)]);
+        let result = map.shortest_route_within(SolarSystemIndex(0), SolarSystemIndex(2), 10);
+        assert_eq!(result, Err(AStarError::PathNotFound));
+    }
+
+    #[test]
+    fn safest_bottleneck_route_takes_the_fewest_jumps_at_the_best_minimum_security() {
+        // A - B - D is short through low-sec B, A - C - E - D and A - F - G - H - D stay above 0.7
+        let map = map_from_edges(
+            &[("A", 1.0), ("B", 0.2), ("C", 0.8), ("D", 1.0), ("E", 0.7), ("F", 0.9), ("G", 0.7), ("H", 0.9)],
+            &[(0, 1), (1, 3), (0, 2), (2, 4), (4, 3), (0, 5), (5, 6), (6, 7), (7, 3)],
+        );
+        let (a, d) = (SolarSystemIndex(0), SolarSystemIndex(3));
+        assert_eq!(map.shortest_route(a, d).unwrap().len(), 3);
+        let route = map.safest_bottleneck_route(a, d).unwrap();
+        assert_eq!(names(&map, &route), ["A", "C", "E", "D"]);
+    }
+
+    #[test]
+    fn safest_bottleneck_route_is_none_without_a_route() {
+        let map = map_from_edges(&[("A", 1.0), ("B", 1.0)], &[]);
+        assert_eq!(map.safest_bottleneck_route(SolarSystemIndex(0), SolarSystemIndex(1)), None);
+    }
+
+    #[test]
+    fn route_with_security_classes_only_enters_allowed_classes() {
+        let map = sde_map();
+        let (jita, ashab) = (system(&map, "Jita"), system(&map, "Ashab"));
+
+        let allowed = [SecurityClass::High, SecurityClass::Low];
+        let route = map.route_with_security_classes(jita, ashab, &allowed).unwrap();
+        assert_eq!(names(&map, &route), ["Jita", "Perimeter", "Tama", "Ashab"]);
+        assert_eq!(map.route_with_security_classes(jita, ashab, &[SecurityClass::High]), None);
+    }
+
+    #[test]
+    fn route_with_security_classes_can_allow_null_sec_but_not_low_sec() {
+        let map = sde_map();
+        let (jita, ec_p8r, ashab) = (system(&map, "Jita"), system(&map, "EC-P8R"), system(&map, "Ashab"));
+        let allowed = [SecurityClass::High, SecurityClass::Null];
+
+        let route = map.route_with_security_classes(jita, ec_p8r, &allowed).unwrap();
+        assert_eq!(names(&map, &route), ["Jita", "EC-P8R"]);
+        // Ashab is low-sec itself (as is Tama, the other way to it), so it's out of reach
+        assert_eq!(map.route_with_security_classes(jita, ashab, &allowed), None);
+    }
+
+    #[test]
+    fn route_with_security_classes_exempts_the_start() {
+        let map = sde_map();
+        let (tama, niarja) = (system(&map, "Tama"), system(&map, "Niarja"));
+        let route = map.route_with_security_classes(tama, niarja, &[SecurityClass::High]).unwrap();
+        assert_eq!(names(&map, &route), ["Tama", "Perimeter", "Madirmilire", "Niarja"]);
+    }
+
+    #[test]
+    fn route_many_matches_shortest_route_in_query_order() {
+        let map = sde_map();
+        let systems = indexes(&map);
+        let queries: Vec<_> = systems.iter().flat_map(|a| systems.iter().map(move |b| (*a, *b))).collect();
+        let sequential: Vec<_> = queries.iter().map(|(a, b)| map.shortest_route(*a, *b)).collect();
+        assert_eq!(map.route_many(&queries), sequential);
+        assert!(sequential.iter().any(Result::is_err));
+    }
+
+    #[test]
+    fn route_avoiding_never_enters_an_avoided_system() {
+        let map = sde_map();
+        let (amarr, tama, niarja) = (system(&map, "Amarr"), system(&map, "Tama"), system(&map, "Niarja"));
+        let avoid = |n: &SolarSystemIndex| *n == niarja || map.get_extended_solarsystem_info(n).security_true() < 0.0;
+
+        let route = map.route_avoiding(amarr, tama, avoid).unwrap();
+        assert_eq!(names(&map, &route), ["Amarr", "Yulai", "Jita", "Perimeter", "Tama"]);
+
+        let yulai = system(&map, "Yulai");
+        let result = map.route_avoiding(amarr, tama, |n| *n == yulai || *n == niarja);
+        assert_eq!(result, Err(AStarError::PathNotFound));
+    }
+
+    #[test]
+    fn route_avoiding_exempts_the_start() {
+        let map = sde_map();
+        let (ec_p8r, jita) = (system(&map, "EC-P8R"), system(&map, "Jita"));
+        let route = map.route_avoiding(ec_p8r, jita, |n| *n == ec_p8r).unwrap();
+        assert_eq!(names(&map, &route), ["EC-P8R", "Jita"]);
+        assert_eq!(map.route_avoiding(jita, jita, |_| true).unwrap(), [jita]);
+    }
+
+    #[test]
+    fn route_with_cost_avoiding_costs_the_detour() {
+        // A - B - D costs 2, but avoiding B leaves A - C - E - D
+        let map = map_from_edges(
+            &[("A", 1.0), ("B", 1.0), ("C", 1.0), ("D", 1.0), ("E", 1.0)],
+            &[(0, 1), (1, 3), (0, 2), (2, 4), (4, 3)],
+        );
+        let (a, b, d) = (SolarSystemIndex(0), SolarSystemIndex(1), SolarSystemIndex(3));
+        let (route, cost) = map.route_with_cost_avoiding(a, d, |_, _| 10u32, |_| 0, None, |n| *n == b).unwrap();
+        assert_eq!(names(&map, &route), ["A", "C", "E", "D"]);
+        assert_eq!(cost, 30);
+    }
+
+    #[test]
+    fn shortest_route_regional_is_as_short_as_shortest_route() {
+        let map = sde_map();
+        for from in indexes(&map) {
+            for to in indexes(&map) {
+                let regional = map.shortest_route_regional(from, to).map(|r| r.len());
+                assert_eq!(regional, map.shortest_route(from, to).map(|r| r.len()));
+            }
+        }
+    }
+
+    #[test]
+    fn shortest_route_regional_expands_the_destination_region_first() {
+        // A's neighbours B to F are in another region and come before G, the destination, in A's
+        let mut builder = crate::builder::MapBuilder::new();
+        builder.add_region(1, "Home").add_region(3, "Away");
+        builder.add_constellation(2, "Home").add_constellation(4, "Away");
+        for (i, name) in ["A", "B", "C", "D", "E", "F", "G"].iter().enumerate() {
+            let (constellation, region) = if (1..6).contains(&i) { (4, 3) } else { (2, 1) };
+            builder.add_system(30_000_000 + i as u64, name, constellation, region, 1.0);
+            if i > 0 {
+                builder.connect(30_000_000, 30_000_000 + i as u64);
+            }
+        }
+        let map = builder.build().unwrap();
+        let (a, g) = (SolarSystemIndex(0), SolarSystemIndex(6));
+
+        let mut stats = AStarStats::default();
+        let route = map.shortest_route_regional_with_stats(a, g, &mut stats).unwrap();
+        assert_eq!(names(&map, &route), ["A", "G"]);
+        // G is reached straight from A, without expanding any of the systems away
+        assert_eq!(stats.nodes_expanded, 1);
+        let mut plain = AStarStats::default();
+        map.route_with_stats(a, g, |_, _| 1u32, |_| 0, None, &mut plain).unwrap();
+        assert!(plain.nodes_expanded > 1, "{plain:?}");
+    }
+
+    #[test]
+    fn the_route_from_a_system_to_itself_is_that_system() {
+        let map = sde_map();
+        // Isolated has no jumps at all, and the costs are never asked for
+        for here in [system(&map, "Jita"), system(&map, "Isolated")] {
+            let never = |_: &SolarSystemIndex, _: &SolarSystemIndex| -> u32 { panic!("no jumps to cost") };
+            assert_eq!(map.shortest_route(here, here).unwrap(), [here]);
+            assert_eq!(map.route_with_cost(here, here, never, |_| 0, Some(0)).unwrap(), (vec![here], 0));
+            assert_eq!(map.route_bidirectional(here, here, never).unwrap(), (vec![here], 0));
+            assert_eq!(map.route_avoiding(here, here, |_| true).unwrap(), [here]);
+            assert_eq!(map.bfs_route(&here, &here, |_| false), Some(vec![here]));
+            let (route, seconds) = map.route_gate_to_gate(here, here, &GateToGateTimeModel::default()).unwrap();
+            assert_eq!((route, seconds.into_inner()), (vec![here], 0.0));
+        }
+    }
+
+    #[test]
+    fn route_bidirectional_agrees_with_route_with_cost() {
+        let map = sde_map();
+        let security_cost = |_: &SolarSystemIndex, b: &SolarSystemIndex| {
+            1 + (10.0 * (1.0 - map.get_extended_solarsystem_info(b).security_true())) as u32
+        };
+        for from in indexes(&map) {
+            for to in indexes(&map) {
+                let expected = map.route_with_cost(from, to, security_cost, |_| 0, None);
+                let mut stats = AStarStats::default();
+                let result = map.route_bidirectional_with_stats(from, to, security_cost, &mut stats);
+                assert_eq!(result.as_ref().map(|r| r.1), expected.as_ref().map(|r| r.1), "{from:?} to {to:?}");
+                if let Ok((route, cost)) = result {
+                    assert_eq!((route[0], route[route.len() - 1]), (from, to));
+                    assert_eq!(route.windows(2).map(|w| security_cost(&w[0], &w[1])).sum::<u32>(), cost);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn route_preferring_regions_goes_through_the_preferred_region() {
+        // Jita - Perimeter - Tama and Jita - EC-P8R - Tama are both two jumps
+        let map = sde_map();
+        let region = |name| map.get_extended_solarsystem_info(&system(&map, name)).region_id;
+        let (jita, tama) = (system(&map, "Jita"), system(&map, "Tama"));
+        let route = map.route_preferring_regions(jita, tama, &[region("Perimeter")]).unwrap();
+        assert_eq!(names(&map, &route), ["Jita", "Perimeter", "Tama"]);
+        let route = map.route_preferring_regions(jita, tama, &[region("EC-P8R")]).unwrap();
+        assert_eq!(names(&map, &route), ["Jita", "EC-P8R", "Tama"]);
+    }
+
+    #[test]
+    fn route_preferring_regions_leaves_them_for_a_much_shorter_route() {
+        // Jita - Yulai - Amarr cuts through Genesis, three jumps shorter than staying in The Forge and Domain
+        let map = sde_map();
+        let region = |name| map.get_extended_solarsystem_info(&system(&map, name)).region_id;
+        let route = map
+            .route_preferring_regions(system(&map, "Jita"), system(&map, "Amarr"), &[region("Jita"), region("Amarr")])
+            .unwrap();
+        assert_eq!(names(&map, &route), ["Jita", "Yulai", "Amarr"]);
+    }
+
+    #[test]
+    fn route_with_open_list_finds_the_same_cost_with_either_open_list() {
+        let map = sde_map();
+        let security_cost = |_: &SolarSystemIndex, b: &SolarSystemIndex| {
+            1 + (10.0 * (1.0 - map.get_extended_solarsystem_info(b).security_true())) as u32
+        };
+        for from in indexes(&map) {
+            for to in indexes(&map) {
+                let simple = map.route_with_open_list(from, to, security_cost, |_| 0, None, SimpleOpenList::new);
+                let dary = map.route_with_open_list(from, to, security_cost, |_| 0, None, DaryOpenList::<_, _>::new);
+                assert_eq!(simple.map(|r| r.1), dary.map(|r| r.1), "{from:?} to {to:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn route_avoiding_edges_goes_around_a_gate_in_either_direction() {
+        let map = sde_map();
+        let (jita, yulai, amarr) = (system(&map, "Jita"), system(&map, "Yulai"), system(&map, "Amarr"));
+        let forbidden = HashSet::from([(yulai, jita)]);
+        let route = map.route_avoiding_edges(jita, amarr, &forbidden).unwrap();
+        assert_eq!(names(&map, &route), ["Jita", "Perimeter", "Madirmilire", "Niarja", "Sarum Prime", "Amarr"]);
+
+        // Yulai itself can still be reached, through its other gate
+        let route = map.route_avoiding_edges(jita, yulai, &forbidden).unwrap();
+        assert_eq!(names(&map, &route)[5..], ["Amarr", "Yulai"]);
+        assert_eq!(map.route_avoiding_edges(jita, yulai, &HashSet::new()).unwrap(), [jita, yulai]);
+    }
+
+    #[test]
+    fn route_with_open_list_and_stats_can_reuse_one_open_list() {
+        let map = sde_map();
+        let mut open = SimpleOpenList::with_capacity(64);
+        let capacity = open.capacity();
+        for from in indexes(&map) {
+            for to in indexes(&map) {
+                let mut stats = AStarStats::default();
+                let reused = map.route_with_open_list_and_stats(
+                    from,
+                    to,
+                    |_, _| 1u32,
+                    |_| 0,
+                    None,
+                    || {
+                        open.clear();
+                        &mut open
+                    },
+                    &mut stats,
+                );
+                let fresh = map.route_with_cost(from, to, |_, _| 1u32, |_| 0, None);
+                assert_eq!(reused.as_ref().ok().map(|r| r.1), fresh.ok().map(|r| r.1), "{from:?} to {to:?}");
+                if from != to && reused.is_ok() {
+                    assert!(stats.nodes_expanded > 0 && stats.nodes_pushed > 0, "{from:?} to {to:?}");
+                }
+            }
+        }
+        assert_eq!(open.capacity(), capacity);
+    }
+}
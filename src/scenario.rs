@@ -0,0 +1,112 @@
+//! "What if" edits to a built Map, eg. what if this gate is down?
+//!
+//! Rather than rebuilding the whole Map, jumps are taken out of (and put back into) the
+//! neighbours in place, so pathfinding reflects them immediately.
+use crate::evemap::{Map, SolarSystemIndex};
+use std::collections::HashSet;
+
+/// EdgeSnapshot records which jumps were removed from a Map at a point in time
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EdgeSnapshot(HashSet<(SolarSystemIndex, SolarSystemIndex)>);
+
+impl Map {
+    /// remove_edge takes the jump between `a` and `b` out of the map, in both directions, since
+    /// a stargate that's down can't be used from either side.
+    /// Returns false if there was no jump between them to remove.
+    pub fn remove_edge(&mut self, a: SolarSystemIndex, b: SolarSystemIndex) -> bool {
+        let removed_ab = self.remove_jump(a, b);
+        let removed_ba = self.remove_jump(b, a);
+        removed_ab || removed_ba
+    }
+
+    /// restore_edge puts back the jumps between `a` and `b` that remove_edge took out.
+    /// Returns false if nothing between them had been removed.
+    pub fn restore_edge(&mut self, a: SolarSystemIndex, b: SolarSystemIndex) -> bool {
+        let restored_ab = self.restore_jump(a, b);
+        let restored_ba = self.restore_jump(b, a);
+        restored_ab || restored_ba
+    }
+
+    /// snapshot_edges records the currently removed jumps, to return to with restore_edges
+    pub fn snapshot_edges(&self) -> EdgeSnapshot {
+        EdgeSnapshot(self.removed_edges.clone())
+    }
+
+    /// restore_edges returns the map to the removed jumps recorded in `snapshot`, so several
+    /// scenarios can be evaluated one after another from the same starting point.
+    pub fn restore_edges(&mut self, snapshot: &EdgeSnapshot) {
+        let to_restore: Vec<_> = self.removed_edges.difference(&snapshot.0).copied().collect();
+        for (from, to) in to_restore {
+            self.restore_jump(from, to);
+        }
+
+        let to_remove: Vec<_> = snapshot.0.difference(&self.removed_edges).copied().collect();
+        for (from, to) in to_remove {
+            self.remove_jump(from, to);
+        }
+    }
+
+    fn remove_jump(&mut self, from: SolarSystemIndex, to: SolarSystemIndex) -> bool {
+        let removed = self.systems[usize::from(from)].neighbours.remove(to);
+
+        if removed {
+            self.predecessors[usize::from(to)].retain(|p| *p != from);
+            self.removed_edges.insert((from, to));
+            self.max_route_length.take();
+        }
+        removed
+    }
+
+    fn restore_jump(&mut self, from: SolarSystemIndex, to: SolarSystemIndex) -> bool {
+        if !self.removed_edges.remove(&(from, to)) {
+            return false;
+        }
+
+        self.systems[usize::from(from)].neighbours.insert(to);
+        self.predecessors[usize::from(to)].push(from);
+        self.max_route_length.take();
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fixtures::{names, sde_map, system};
+
+    #[test]
+    fn remove_edge_reroutes_and_restore_edge_puts_the_jump_back() {
+        let mut map = sde_map();
+        let (jita, yulai, amarr) = (system(&map, "Jita"), system(&map, "Yulai"), system(&map, "Amarr"));
+
+        assert!(map.remove_edge(jita, yulai));
+        assert!(!map.remove_edge(jita, yulai));
+        assert!(!map.get_neighbours(&yulai).any(|n| n == jita));
+        assert!(!map.get_predecessors(&jita).any(|n| n == yulai));
+        let route = map.shortest_route(jita, amarr).unwrap();
+        assert_eq!(names(&map, &route), ["Jita", "Perimeter", "Madirmilire", "Niarja", "Sarum Prime", "Amarr"]);
+
+        assert!(map.restore_edge(yulai, jita));
+        assert!(!map.restore_edge(yulai, jita));
+        assert_eq!(map.shortest_route(jita, amarr).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn restore_edges_returns_to_a_snapshot() {
+        let mut map = sde_map();
+        let (jita, yulai, perimeter) = (system(&map, "Jita"), system(&map, "Yulai"), system(&map, "Perimeter"));
+        let unchanged = map.snapshot_edges();
+
+        map.remove_edge(jita, yulai);
+        let without_yulai = map.snapshot_edges();
+        map.restore_edge(jita, yulai);
+        map.remove_edge(jita, perimeter);
+
+        map.restore_edges(&without_yulai);
+        let kept: Vec<_> = map.get_neighbours(&jita).filter(|n| *n == yulai || *n == perimeter).collect();
+        assert_eq!(kept, [perimeter]);
+
+        map.restore_edges(&unchanged);
+        assert_eq!(map.snapshot_edges(), unchanged);
+        assert_eq!(map.get_neighbours(&jita).count(), 3);
+    }
+}
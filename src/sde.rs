@@ -1,20 +1,124 @@
 
+//! SDE is the Eve Online Static Data Export
+//! this module is intended to help to download a copy of the data to be used by subsequently loading
+//! it into memory and performing pathfinding
+use std::collections::HashMap;
 use std::io;
 use std::io::{Read};
-/// SDE is the Eve Online Static Data Export
-/// this module is intended to help to download a copy of the data to be used by subsequently loading
-/// it into memory and performing pathfinding
+
+/// SDE_METADATA_FILE is the name of the file describing the SDE build, in SDEs that have one
+const SDE_METADATA_FILE: &str = "_sde.yaml";
+
+/// SDE_NAMES_FILE is the SDE's table of official item names, which includes every region,
+/// constellation and solar system
+const SDE_NAMES_FILE: &str = "bsd/invNames.yaml";
+
+/// SDE_TRANSLATIONS_FILE holds the text of the SDE in each of the languages Eve supports
+const SDE_TRANSLATIONS_FILE: &str = "bsd/trnTranslations.yaml";
+
+/// ITEM_NAME_TC_ID is the translation column (tcID) of item names, for which the keyID is the item ID
+const ITEM_NAME_TC_ID: u64 = 8;
+
+/// UNIVERSE_ITEM_IDS covers the IDs of regions (10M), constellations (20M) and solar systems (30M).
+/// The names table covers every item in the game, so only these are kept
+const UNIVERSE_ITEM_IDS: std::ops::Range<u64> = 10_000_000..40_000_000;
 
 pub struct SdeZipReader<T: io::Read> {
-    reader: T
+    reader: T,
+    version: Option<String>,
+    /// whether to read the names table, which is large and so skipped unless asked for
+    read_names: bool,
+    names: Option<HashMap<u64, String>>,
+    /// the language to read localized names for, if not English
+    language: Option<String>,
+    /// localized item names, keyed by language and then by ID
+    localized_names: HashMap<String, HashMap<u64, String>>,
 }
 
 impl<T:Read> SdeZipReader<T> {
     pub fn new(reader : T) -> SdeZipReader<T> {
-        SdeZipReader{reader}
+        SdeZipReader{
+            reader,
+            version: None,
+            read_names: false,
+            names: None,
+            language: None,
+            localized_names: HashMap::new(),
+        }
+    }
+
+    /// with_names reads the SDE's name table as well, so that the map can use the official names
+    /// of regions, constellations and systems rather than the ones in the file paths
+    pub fn with_names(mut self) -> Self {
+        self.read_names = true;
+        self
+    }
+
+    /// with_language reads the names in the given language (eg. "de") from the SDE's translations,
+    /// along with the name table for anything that has no translation. English names are the
+    /// name table itself, so "en" is the same as with_names.
+    pub fn with_language(mut self, language: &str) -> Self {
+        self.read_names = true;
+        if language != "en" {
+            self.language = Some(language.to_string());
+        }
+        self
+    }
+
+    /// language is the language that localized names are read for, if one was chosen
+    pub fn language(&self) -> Option<&str> {
+        self.language.as_deref()
+    }
+
+    /// localized_names is the name of each region, constellation and solar system in a language,
+    /// keyed by ID. It's filled in when the iterator passes the translations, for the language
+    /// chosen with with_language.
+    pub fn localized_names(&self, language: &str) -> Option<&HashMap<u64, String>> {
+        self.localized_names.get(language)
+    }
+
+    /// names is the official name of each region, constellation and solar system, keyed by ID.
+    /// Like version, it's only filled in once the iterator has passed the names table, and only
+    /// if the reader was created with_names and the SDE has the table.
+    pub fn names(&self) -> Option<&HashMap<u64, String>> {
+        self.names.as_ref()
+    }
+
+    /// version is the SDE build number (or failing that, its release date) if the zip had
+    /// metadata describing it. It's only known once the iterator has passed the metadata file,
+    /// so check it after reading everything.
+    pub fn version(&self) -> Option<&str> {
+        self.version.as_deref()
     }
 }
 
+#[derive(serde::Deserialize)]
+struct SdeMetadata {
+    #[serde(rename = "buildNumber")]
+    build_number: Option<u64>,
+    #[serde(rename = "releaseDate")]
+    release_date: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+struct InvName {
+    #[serde(rename = "itemID")]
+    item_id: u64,
+    #[serde(rename = "itemName")]
+    item_name: String,
+}
+
+#[derive(serde::Deserialize)]
+struct Translation {
+    #[serde(rename = "keyID")]
+    key_id: u64,
+    #[serde(rename = "languageID")]
+    language_id: String,
+    #[serde(rename = "tcID")]
+    tc_id: u64,
+    text: String,
+}
+
 /// Read SDE yaml files incrementally and return a buffer of their contents
 /// plus the filename.
 impl<T: Read> Iterator for SdeZipReader<T> {
@@ -29,6 +133,53 @@ impl<T: Read> Iterator for SdeZipReader<T> {
             }
 
             let zip_file_name = x.name().to_string();
+            if zip_file_name.rsplit('/').next() == Some(SDE_METADATA_FILE) {
+                let mut buf = Vec::new();
+                x.read_to_end(&mut buf).ok()?;
+                // metadata is a nice to have, so a file we can't make sense of is just ignored
+                if let Ok(metadata) = serde_yaml::from_slice::<SdeMetadata>(&buf) {
+                    self.version = metadata.build_number.map(|b| b.to_string()).or(metadata.release_date);
+                }
+                continue;
+            }
+
+            if self.read_names && zip_file_name.ends_with(SDE_NAMES_FILE) {
+                let mut buf = Vec::new();
+                x.read_to_end(&mut buf).ok()?;
+                // a names table we can't read only costs us the official names, the paths still have them
+                if let Ok(names) = serde_yaml::from_slice::<Vec<InvName>>(&buf) {
+                    self.names = Some(
+                        names
+                            .into_iter()
+                            .filter(|n| UNIVERSE_ITEM_IDS.contains(&n.item_id))
+                            .map(|n| (n.item_id, n.item_name))
+                            .collect(),
+                    );
+                }
+                continue;
+            }
+
+            if let Some(language) = self.language.as_deref() {
+                if zip_file_name.ends_with(SDE_TRANSLATIONS_FILE) {
+                    let mut buf = Vec::new();
+                    x.read_to_end(&mut buf).ok()?;
+                    // as with the names table, anything missing falls back to the English name
+                    if let Ok(translations) = serde_yaml::from_slice::<Vec<Translation>>(&buf) {
+                        let names = translations
+                            .into_iter()
+                            .filter(|t| {
+                                t.tc_id == ITEM_NAME_TC_ID
+                                    && t.language_id == language
+                                    && UNIVERSE_ITEM_IDS.contains(&t.key_id)
+                            })
+                            .map(|t| (t.key_id, t.text))
+                            .collect();
+                        self.localized_names.insert(language.to_string(), names);
+                    }
+                    continue;
+                }
+            }
+
             if zip_file_name.starts_with("sde/fsd/universe/eve")
                 && zip_file_name.ends_with(".staticdata")
             {
@@ -41,4 +192,171 @@ impl<T: Read> Iterator for SdeZipReader<T> {
 
         None
     }
-}
\ No newline at end of file
+}
+/// MmapSdeSource is a local SDE zip mapped into memory. Reading the zip through the map saves the
+/// copying of buffered file reads, which adds up over the thousands of files in the SDE.
+#[cfg(feature = "mmap")]
+pub struct MmapSdeSource {
+    mmap: memmap2::Mmap,
+}
+
+#[cfg(feature = "mmap")]
+impl MmapSdeSource {
+    pub fn open<P: AsRef<std::path::Path>>(path: P) -> io::Result<MmapSdeSource> {
+        let file = std::fs::File::open(path)?;
+        // Safety: the mapping stays valid after the file is closed, but the memory behind it is the
+        // file, so it must not be changed or truncated while mapped. That holds for an SDE we've
+        // downloaded and only read, but not if something rewrites the zip during the map build.
+        let mmap = unsafe { memmap2::Mmap::map(&file)? };
+        Ok(MmapSdeSource { mmap })
+    }
+
+    /// bytes is the whole zip
+    pub fn bytes(&self) -> &[u8] {
+        &self.mmap
+    }
+
+    /// reader reads the SDE out of the mapped zip. It borrows the source, so the mapping can't
+    /// be dropped while the map is being built from it.
+    pub fn reader(&self) -> SdeZipReader<io::Cursor<&[u8]>> {
+        SdeZipReader::new(io::Cursor::new(self.bytes()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evemap::Map;
+    use std::io::Write;
+
+    /// zip_of is a zip holding each (name, contents) file
+    fn zip_of(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut zip = zip::ZipWriter::new(io::Cursor::new(Vec::new()));
+        for (name, contents) in files {
+            zip.start_file(*name, zip::write::FileOptions::default()).unwrap();
+            zip.write_all(contents.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap().into_inner()
+    }
+
+    /// version_of reads all of `zip` and returns the version it found
+    fn version_of(zip: &[u8]) -> Option<String> {
+        let mut reader = SdeZipReader::new(io::Cursor::new(zip));
+        reader.by_ref().for_each(drop);
+        reader.version().map(str::to_string)
+    }
+
+    #[test]
+    fn version_is_the_build_number_of_the_fixture() {
+        assert_eq!(version_of(crate::fixtures::SDE_ZIP).as_deref(), Some("2967307"));
+    }
+
+    #[test]
+    fn version_falls_back_to_the_release_date() {
+        let zip = zip_of(&[("sde/_sde.yaml", "releaseDate: 2024-05-01\n")]);
+        assert_eq!(version_of(&zip).as_deref(), Some("2024-05-01"));
+    }
+
+    #[test]
+    fn version_is_none_without_readable_metadata() {
+        assert_eq!(version_of(&zip_of(&[("sde/_sde.yaml", "[not, a, mapping]")])), None);
+        assert_eq!(version_of(&zip_of(&[("sde/other.yaml", "buildNumber: 1")])), None);
+    }
+
+    /// fixture_with is the fixture SDE with `extra` files added at the end
+    fn fixture_with(extra: &[(&str, &str)]) -> Vec<u8> {
+        let mut fixture = zip::ZipArchive::new(io::Cursor::new(crate::fixtures::SDE_ZIP)).unwrap();
+        let mut files = Vec::new();
+        for i in 0..fixture.len() {
+            let mut file = fixture.by_index(i).unwrap();
+            let mut contents = String::new();
+            file.read_to_string(&mut contents).unwrap();
+            files.push((file.name().to_string(), contents));
+        }
+        let files: Vec<(&str, &str)> = files
+            .iter()
+            .map(|(n, c)| (n.as_str(), c.as_str()))
+            .chain(extra.iter().copied())
+            .collect();
+        zip_of(&files)
+    }
+
+    const NAMES: &str = "- itemID: 30000008\n  itemName: Jita IV\n- itemID: 60003760\n  itemName: A station\n";
+
+    #[test]
+    fn with_names_reads_the_names_of_universe_items() {
+        let zip = fixture_with(&[("sde/bsd/invNames.yaml", NAMES)]);
+        let mut reader = SdeZipReader::new(io::Cursor::new(zip.as_slice())).with_names();
+        reader.by_ref().for_each(drop);
+        let names = reader.names().unwrap();
+        assert_eq!(names.get(&30000008).map(String::as_str), Some("Jita IV"));
+        assert!(!names.contains_key(&60003760));
+    }
+
+    #[test]
+    fn names_are_only_read_when_asked_for() {
+        let zip = fixture_with(&[("sde/bsd/invNames.yaml", NAMES)]);
+        let reader = || SdeZipReader::new(io::Cursor::new(zip.as_slice()));
+        let map = Map::new(&mut reader()).unwrap();
+        assert!(map.find_solarsystem("Jita").is_ok());
+
+        let map = Map::new(&mut reader().with_names()).unwrap();
+        assert!(map.find_solarsystem("Jita").is_err());
+        let jita = map.find_solarsystem("Jita IV").unwrap();
+        assert_eq!(map.get_neighbours(&jita).count(), 3);
+    }
+
+    const TRANSLATIONS: &str = "\
+- keyID: 30000008
+  languageID: de
+  tcID: 8
+  text: Jita auf Deutsch
+- keyID: 30000008
+  languageID: fr
+  tcID: 8
+  text: Jita en français
+- keyID: 30000001
+  languageID: de
+  tcID: 33
+  text: not a name
+";
+
+    #[test]
+    fn with_language_names_systems_from_the_translations() {
+        let zip = fixture_with(&[("sde/bsd/invNames.yaml", NAMES), ("sde/bsd/trnTranslations.yaml", TRANSLATIONS)]);
+        let mut reader = SdeZipReader::new(io::Cursor::new(zip.as_slice())).with_language("de");
+        assert_eq!(reader.language(), Some("de"));
+        let map = Map::new(&mut reader).unwrap();
+
+        assert!(map.find_solarsystem("Jita auf Deutsch").is_ok());
+        // anything without a translation keeps its English name
+        assert!(map.find_solarsystem("Amarr").is_ok());
+        assert_eq!(reader.localized_names("de").unwrap().len(), 1);
+        assert!(reader.localized_names("fr").is_none());
+    }
+
+    #[test]
+    fn with_language_en_is_the_name_table() {
+        let reader = SdeZipReader::new(io::Cursor::new(crate::fixtures::SDE_ZIP)).with_language("en");
+        assert_eq!(reader.language(), None);
+        assert!(reader.read_names);
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn mmap_source_builds_the_same_map_as_buffered_reads() {
+        let path = std::env::temp_dir().join(format!("rust-eve-astar-mmap-{}.zip", std::process::id()));
+        std::fs::write(&path, crate::fixtures::SDE_ZIP).unwrap();
+
+        let source = MmapSdeSource::open(&path).unwrap();
+        assert_eq!(source.bytes(), crate::fixtures::SDE_ZIP);
+        let mapped = Map::new(&mut source.reader()).unwrap();
+        let buffered = Map::new(&mut SdeZipReader::new(io::BufReader::new(std::fs::File::open(&path).unwrap()))).unwrap();
+        drop(source);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(mapped.system_count(), 11);
+        assert_eq!(mapped.snapshot(), buffered.snapshot());
+        assert!(MmapSdeSource::open(&path).is_err());
+    }
+}
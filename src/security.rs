@@ -0,0 +1,44 @@
+//! Security status classes of systems.
+//!
+//! Eve displays security rounded to one decimal place, but the mechanics use the true value.
+//! A system with a true security of 0.45 shows as 0.5 in game, but it is low-sec: there's no
+//! CONCORD response there. Everything in here works from the true value.
+
+/// SecurityClass is the broad class of a system's security that decides the game mechanics
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum SecurityClass {
+    /// true security of 0.5 and above
+    High,
+    /// true security above 0.0 and below 0.5
+    Low,
+    /// true security of 0.0 and below
+    Null,
+}
+
+impl SecurityClass {
+    /// from_security classifies a *true* security status. Don't pass a display rounded value.
+    pub fn from_security(security: f32) -> SecurityClass {
+        if security >= 0.5 {
+            SecurityClass::High
+        } else if security > 0.0 {
+            SecurityClass::Low
+        } else {
+            SecurityClass::Null
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_security_splits_at_the_class_boundaries() {
+        assert_eq!(SecurityClass::from_security(1.0), SecurityClass::High);
+        assert_eq!(SecurityClass::from_security(0.5), SecurityClass::High);
+        assert_eq!(SecurityClass::from_security(0.45), SecurityClass::Low);
+        assert_eq!(SecurityClass::from_security(0.01), SecurityClass::Low);
+        assert_eq!(SecurityClass::from_security(0.0), SecurityClass::Null);
+        assert_eq!(SecurityClass::from_security(-0.8), SecurityClass::Null);
+    }
+}
@@ -1,29 +1,81 @@
 use crate::SolarSystemIndex;
 use crate::astar::{ClosedList, ClosedListState};
+use crate::indexmap::IndexMap;
 
-pub struct SimpleClosed<Cost> (Vec<ClosedListState<SolarSystemIndex, Cost>>);
+pub struct SimpleClosed<Cost> (IndexMap<ClosedListState<SolarSystemIndex, Cost>>);
 
 impl<Cost: Copy> SimpleClosed<Cost> {
     pub fn new(capacity: usize) -> Self {
-        SimpleClosed(std::iter::repeat(ClosedListState::Unvisited).take(capacity).collect())
+        SimpleClosed(IndexMap::with_len(capacity, ClosedListState::Unvisited))
     }
 }
 
-// TODO: using Index implies that anything looking for an item beyond the bounds will cause a panic
-//       this is probably sufficient, but it might be nice to ensure that this could not happen.
+impl<Cost> SimpleClosed<Cost> {
+    /// get is the checked lookup, None when `index` is beyond the capacity the list was made with
+    #[inline]
+    pub fn get(&self, index: SolarSystemIndex) -> Option<&ClosedListState<SolarSystemIndex, Cost>> {
+        self.0.get(index)
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self, index: SolarSystemIndex) -> Option<&mut ClosedListState<SolarSystemIndex, Cost>> {
+        self.0.get_mut(index)
+    }
+}
+
+/// Indexing panics when `index` is beyond the capacity the list was made with, which means the
+/// index is from a different (bigger) map than the list was made for. The panic says so, rather
+/// than being a bare index out of bounds. See get for the checked version.
 impl<Cost> std::ops::Index<SolarSystemIndex> for SimpleClosed<Cost> {
     type Output = ClosedListState<SolarSystemIndex, Cost>;
 
     fn index(&self, index: SolarSystemIndex) -> &Self::Output {
-        &self.0[usize::from(index)]
+        let capacity = self.0.len();
+        self.0.get(index).unwrap_or_else(|| out_of_bounds(index, capacity))
     }
 }
 
 impl<Cost> std::ops::IndexMut<SolarSystemIndex> for SimpleClosed<Cost> {
     fn index_mut(&mut self, index: SolarSystemIndex) -> &mut Self::Output {
-        &mut self.0[usize::from(index)]
+        let capacity = self.0.len();
+        self.0.get_mut(index).unwrap_or_else(|| out_of_bounds(index, capacity))
     }
 }
 
+#[cold]
+fn out_of_bounds(index: SolarSystemIndex, capacity: usize) -> ! {
+    panic!(
+        "SimpleClosed made for {capacity} systems was indexed with SolarSystemIndex {}: is the index from \
+         a different map?",
+        usize::from(index)
+    )
+}
+
 // Implement the super-trait
-impl<Cost> ClosedList<SolarSystemIndex, Cost> for SimpleClosed<Cost> {}
\ No newline at end of file
+impl<Cost> ClosedList<SolarSystemIndex, Cost> for SimpleClosed<Cost> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::astar::ClosedListState::{PathFrom, StartingPoint, Unvisited};
+
+    #[test]
+    fn get_is_none_beyond_the_capacity() {
+        let mut closed: SimpleClosed<u32> = SimpleClosed::new(3);
+        closed[SolarSystemIndex(2)] = StartingPoint(0);
+        assert!(matches!(closed.get(SolarSystemIndex(2)), Some(StartingPoint(0))));
+        assert!(matches!(closed.get(SolarSystemIndex(0)), Some(Unvisited)));
+        assert!(closed.get(SolarSystemIndex(3)).is_none());
+
+        *closed.get_mut(SolarSystemIndex(1)).unwrap() = PathFrom(SolarSystemIndex(2), 1);
+        assert_eq!(closed.unwind(SolarSystemIndex(1)), [SolarSystemIndex(2), SolarSystemIndex(1)]);
+        assert!(closed.get_mut(SolarSystemIndex(3)).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "SimpleClosed made for 3 systems was indexed with SolarSystemIndex 3")]
+    fn indexing_beyond_the_capacity_says_why() {
+        let closed: SimpleClosed<u32> = SimpleClosed::new(3);
+        let _ = &closed[SolarSystemIndex(3)];
+    }
+}
@@ -31,6 +31,33 @@ where
             //node_check: vec![],
         }
     }
+
+    /// with_capacity is an empty open list with room for `capacity` items before it reallocates
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            ordering: BinaryHeap::with_capacity(capacity),
+        }
+    }
+
+    /// clear empties the list but keeps its allocation, so one open list can be reused for search
+    /// after search (eg. by a server answering queries) without growing it from nothing each time
+    pub fn clear(&mut self) {
+        self.ordering.clear();
+    }
+
+    /// capacity is how many items the list can hold before it reallocates
+    pub fn capacity(&self) -> usize {
+        self.ordering.capacity()
+    }
+}
+
+impl<N, Cost: Ord> Default for SimpleOpenList<N, Cost>
+where
+    astar::OpenItem<N, Cost>: Ord,
+{
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl<N, Cost: Ord> astar::OpenList<astar::OpenItem<N, Cost>> for SimpleOpenList<N, Cost>
@@ -41,6 +68,10 @@ where
         self.ordering.is_empty()
     }
 
+    fn len(&self) -> usize {
+        self.ordering.len()
+    }
+
     fn push_open(&mut self, e: astar::OpenItem<N, Cost>) {
         // TODO: There's no need for multiple items of the same node
         //       we only need the lowest cost item.
@@ -52,4 +83,42 @@ where
     fn pop_min(&mut self) -> Option<astar::OpenItem<N, Cost>> {
         self.ordering.pop()
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::astar::{OpenItem, OpenList};
+
+    #[test]
+    fn clear_empties_the_list_but_keeps_its_capacity() {
+        let mut open: SimpleOpenList<u32, u32> = SimpleOpenList::with_capacity(16);
+        let capacity = open.capacity();
+        assert!(capacity >= 16);
+        for heuristic in [4, 2, 9] {
+            open.push_open(OpenItem { heuristic, node: heuristic });
+        }
+        open.clear();
+        assert!(open.is_empty());
+        assert_eq!(open.capacity(), capacity);
+
+        // and it still works after being cleared
+        open.push_open(OpenItem { heuristic: 7, node: 1 });
+        open.push_open(OpenItem { heuristic: 3, node: 2 });
+        assert_eq!(open.pop_min().map(|item| item.node), Some(2));
+    }
+
+    #[test]
+    fn a_borrowed_open_list_is_an_open_list() {
+        /// push_two pushes onto any open list it's given, taking ownership of it
+        fn push_two(mut open: impl OpenList<OpenItem<u32, u32>>) -> usize {
+            open.push_open(OpenItem { heuristic: 5, node: 1 });
+            open.push_open(OpenItem { heuristic: 1, node: 2 });
+            open.len()
+        }
+
+        let mut open: SimpleOpenList<u32, u32> = SimpleOpenList::new();
+        assert_eq!(push_two(&mut open), 2);
+        assert_eq!(open.len(), 2);
+        assert_eq!(open.pop_min().map(|item| item.node), Some(2));
+    }
+}
@@ -0,0 +1,236 @@
+//! A plain, serializable form of a Map.
+//!
+//! Tools comparing SDE builds can serialize a snapshot of each and diff them. Everything in a
+//! snapshot is sorted, so the same map always produces the same snapshot.
+use crate::builder::MapBuilder;
+use crate::evemap::{Map, RawIndex, SolarSystemId, SolarSystemIndex};
+use eyre::eyre;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// SNAPSHOT_FORMAT_VERSION is stamped on every snapshot, and bumped whenever its layout changes
+pub const SNAPSHOT_FORMAT_VERSION: u32 = 3;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MapSnapshot {
+    /// the SNAPSHOT_FORMAT_VERSION the snapshot was written with
+    pub format_version: u32,
+    /// the SDE build the map was read from, if known
+    pub sde_version: Option<String>,
+    /// region ids and names
+    pub regions: Vec<(u64, String)>,
+    /// constellation ids and names
+    pub constellations: Vec<(u64, String)>,
+    pub systems: Vec<SystemSnapshot>,
+    pub jumps: Vec<JumpSnapshot>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SystemSnapshot {
+    pub solar_system_id: SolarSystemId,
+    pub name: String,
+    pub constellation_id: u64,
+    pub region_id: u64,
+    /// true security status
+    pub security: f64,
+    /// position in the universe (meters), if known
+    #[serde(default)]
+    pub position: Option<[f64; 3]>,
+}
+
+/// JumpSnapshot is a single (one way) jump between two systems
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JumpSnapshot {
+    pub from: SolarSystemId,
+    pub to: SolarSystemId,
+    /// the stargate the jump is made through, if it is one
+    pub stargate_id: Option<u64>,
+    /// position of the gate within `from`, if known
+    pub position: Option<[f64; 3]>,
+}
+
+impl Map {
+    /// snapshot creates a serializable copy of the map. It records the stargates of the map as built,
+    /// so jumps taken out with remove_edge are still in it.
+    pub fn snapshot(&self) -> MapSnapshot {
+        let systems: Vec<SystemSnapshot> = self
+            .extended_systems
+            .iter()
+            .map(|ss| SystemSnapshot {
+                solar_system_id: ss.solar_system_id,
+                name: ss.name.clone(),
+                constellation_id: ss.constellation_id,
+                region_id: ss.region_id,
+                security: ss.security,
+                position: ss.position,
+            })
+            .collect();
+
+        let mut jumps: Vec<JumpSnapshot> = self
+            .extended_systems
+            .iter()
+            .flat_map(|ss| {
+                ss.gates.iter().map(|g| JumpSnapshot {
+                    from: ss.solar_system_id,
+                    to: self.get_extended_solarsystem_info(&g.destination).solar_system_id,
+                    stargate_id: g.stargate_id,
+                    position: g.position,
+                })
+            })
+            .collect();
+        jumps.sort_by_key(|j| (j.from, j.to, j.stargate_id));
+
+        // The names lookup doesn't say what each name is, but the systems know their region
+        // and constellation ids
+        let id_to_name: BTreeMap<u64, &String> = self
+            .name_to_id
+            .iter()
+            .flat_map(|(name, ids)| ids.iter().map(move |id| (*id, name)))
+            .collect();
+        let named = |ids: BTreeSet<u64>| -> Vec<(u64, String)> {
+            ids.into_iter()
+                .filter_map(|id| id_to_name.get(&id).map(|name| (id, name.to_string())))
+                .collect()
+        };
+
+        MapSnapshot {
+            format_version: SNAPSHOT_FORMAT_VERSION,
+            sde_version: self.sde_version.clone(),
+            regions: named(systems.iter().map(|s| s.region_id).collect()),
+            constellations: named(systems.iter().map(|s| s.constellation_id).collect()),
+            systems,
+            jumps,
+        }
+    }
+
+    /// adjacency is each system's id and the ids of its neighbours, for exporting the jumps as
+    /// they are now (unlike snapshot, without those taken out by remove_edge). Both levels are in
+    /// SolarSystemId order, so the same map always exports the same way however it was built.
+    pub fn adjacency(&self) -> Vec<(SolarSystemId, Vec<SolarSystemId>)> {
+        let mut adjacency: Vec<(SolarSystemId, Vec<SolarSystemId>)> = self
+            .systems
+            .iter()
+            .enumerate()
+            .map(|(i, ss)| {
+                let neighbours = self.get_neighbours_by_id(&SolarSystemIndex(i as RawIndex)).map(|(id, _)| id).collect();
+                (ss.solar_system_id, neighbours)
+            })
+            .collect();
+        adjacency.sort_unstable_by_key(|(id, _)| *id);
+        adjacency
+    }
+
+    /// from_snapshot rebuilds a Map from a snapshot. Snapshots from a different
+    /// SNAPSHOT_FORMAT_VERSION are rejected.
+    pub fn from_snapshot(snapshot: &MapSnapshot) -> eyre::Result<Map> {
+        if snapshot.format_version != SNAPSHOT_FORMAT_VERSION {
+            return Err(eyre!(
+                "snapshot format version {} is not supported (expected {})",
+                snapshot.format_version,
+                SNAPSHOT_FORMAT_VERSION
+            ));
+        }
+
+        let mut builder = MapBuilder::new();
+        if let Some(version) = &snapshot.sde_version {
+            builder.sde_version(version);
+        }
+        for (id, name) in &snapshot.regions {
+            builder.add_region(*id, name);
+        }
+        for (id, name) in &snapshot.constellations {
+            builder.add_constellation(*id, name);
+        }
+        for s in &snapshot.systems {
+            builder.add_system_at(s.solar_system_id.0, &s.name, s.constellation_id, s.region_id, s.security, s.position);
+        }
+        for j in &snapshot.jumps {
+            builder.add_jump_through(j.from.0, j.to.0, j.stargate_id, j.position);
+        }
+
+        builder.build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{names, sde_map, system};
+
+    #[test]
+    fn snapshot_round_trips_through_json() {
+        let map = sde_map();
+        let snapshot = map.snapshot();
+        assert_eq!(snapshot.format_version, SNAPSHOT_FORMAT_VERSION);
+        assert_eq!(snapshot.sde_version.as_deref(), Some("2967307"));
+        assert_eq!((snapshot.regions.len(), snapshot.systems.len()), (5, 11));
+
+        let json = serde_json::to_string(&snapshot).unwrap();
+        let rebuilt = Map::from_snapshot(&serde_json::from_str(&json).unwrap()).unwrap();
+        assert_eq!(rebuilt.snapshot(), snapshot);
+        let route = rebuilt.shortest_route(system(&rebuilt, "Tama"), system(&rebuilt, "Sarum Prime")).unwrap();
+        assert_eq!(names(&rebuilt, &route), ["Tama", "Ashab", "Niarja", "Sarum Prime"]);
+    }
+
+    #[test]
+    fn snapshot_is_the_same_for_the_same_map() {
+        assert_eq!(
+            serde_json::to_string(&sde_map().snapshot()).unwrap(),
+            serde_json::to_string(&sde_map().snapshot()).unwrap()
+        );
+    }
+
+    #[test]
+    fn from_snapshot_rejects_other_format_versions() {
+        let mut snapshot = sde_map().snapshot();
+        snapshot.format_version = SNAPSHOT_FORMAT_VERSION + 1;
+        assert!(Map::from_snapshot(&snapshot).is_err());
+    }
+
+    #[test]
+    fn adjacency_is_in_id_order_however_the_map_was_built() {
+        let systems = [("A", 1.0), ("B", 1.0), ("C", 1.0)];
+        let forwards = crate::fixtures::map_from_edges(&systems, &[(0, 1), (0, 2)]);
+        let backwards = crate::fixtures::map_from_edges(&systems, &[(0, 2), (0, 1)]);
+        let id = |i: u64| SolarSystemId(crate::fixtures::FIRST_ID + i);
+        let expected = vec![(id(0), vec![id(1), id(2)]), (id(1), vec![id(0)]), (id(2), vec![id(0)])];
+        assert_eq!(forwards.adjacency(), expected);
+        assert_eq!(backwards.adjacency(), expected);
+
+        // a patched in system has the last index, but the lowest id
+        let z = SolarSystemId(crate::fixtures::FIRST_ID - 1);
+        let mut patched = backwards;
+        let patch = crate::patch::SdePatch {
+            added_systems: vec![SystemSnapshot {
+                solar_system_id: z,
+                name: "Z".to_string(),
+                constellation_id: 2,
+                region_id: 1,
+                security: 1.0,
+                position: None,
+            }],
+            added_jumps: vec![JumpSnapshot {
+                from: id(2),
+                to: z,
+                stargate_id: None,
+                position: None,
+            }],
+            ..Default::default()
+        };
+        patched.apply_patch(&patch).unwrap();
+        let adjacency = patched.adjacency();
+        assert_eq!(adjacency[0], (z, vec![]));
+        assert_eq!(adjacency[3], (id(2), vec![z, id(0)]));
+    }
+
+    #[test]
+    fn adjacency_leaves_out_removed_edges() {
+        let mut map = sde_map();
+        let (jita, yulai) = (system(&map, "Jita"), system(&map, "Yulai"));
+        map.remove_edge(jita, yulai);
+        let jita_id = map.get_system(&jita).solar_system_id;
+        let (_, neighbours) = map.adjacency().into_iter().find(|(id, _)| *id == jita_id).unwrap();
+        assert_eq!(neighbours.len(), 2);
+        assert!(!neighbours.contains(&map.get_system(&yulai).solar_system_id));
+    }
+}
@@ -0,0 +1,98 @@
+//! Synthetic maps for stress testing the pathfinding without needing the SDE.
+//!
+//! Everything here is driven by a seeded RNG, so a given seed always produces the same map.
+//! Only available with the "testing" feature.
+use crate::builder::MapBuilder;
+use crate::evemap::Map;
+pub use crate::rng::SplitMix64;
+use std::collections::HashSet;
+
+/// FIRST_SYSTEM_ID is the SolarSystemId of the first generated system, in line with Eve's 30000001
+const FIRST_SYSTEM_ID: u64 = 30_000_001;
+const REGION_ID: u64 = 10_000_001;
+const CONSTELLATION_ID: u64 = 20_000_001;
+
+/// random_connected_map generates a connected map of `systems` systems, with roughly
+/// `average_degree` stargates per system. Systems are named "SYS-<n>" and get a random security
+/// between -1.0 and 1.0.
+///
+/// It starts from a random spanning tree, which guarantees that everything is connected, and then
+/// adds random extra connections until the average degree is reached.
+pub fn random_connected_map(systems: usize, average_degree: f32, seed: u64) -> eyre::Result<Map> {
+    let mut rng = SplitMix64::new(seed);
+    let mut builder = MapBuilder::new();
+    builder
+        .add_region(REGION_ID, "Test Region")
+        .add_constellation(CONSTELLATION_ID, "Test Constellation");
+
+    for i in 0..systems {
+        builder.add_system(
+            FIRST_SYSTEM_ID + i as u64,
+            &format!("SYS-{i}"),
+            CONSTELLATION_ID,
+            REGION_ID,
+            rng.unit() * 2.0 - 1.0,
+        );
+    }
+
+    let mut edges = HashSet::new();
+    for i in 1..systems {
+        let j = rng.below(i);
+        edges.insert((j, i));
+    }
+
+    // a simple graph can't have more edges than this, so don't try forever to add them
+    let max_edges = systems * systems.saturating_sub(1) / 2;
+    let target_edges = ((systems as f32 * average_degree / 2.0) as usize).min(max_edges);
+    while edges.len() < target_edges {
+        let (a, b) = (rng.below(systems), rng.below(systems));
+        if a != b {
+            edges.insert((a.min(b), a.max(b)));
+        }
+    }
+
+    // sort for a deterministic order of stargates, since HashSet iteration isn't
+    let mut edges: Vec<_> = edges.into_iter().collect();
+    edges.sort_unstable();
+    for (a, b) in edges {
+        builder.connect(FIRST_SYSTEM_ID + a as u64, FIRST_SYSTEM_ID + b as u64);
+    }
+
+    builder.build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn random_connected_map_is_connected_with_the_requested_degree() {
+        let map = random_connected_map(200, 3.0, 7).unwrap();
+        assert_eq!(map.system_count(), 200);
+        assert_eq!(map.connected_components().len(), 1);
+        let gates: usize = map.into_iter().map(|ss| ss.get_neighbours().count()).sum();
+        assert_eq!(gates, 2 * 300);
+    }
+
+    #[test]
+    fn random_connected_map_is_the_same_for_a_seed() {
+        let a = random_connected_map(50, 2.5, 3).unwrap();
+        let b = random_connected_map(50, 2.5, 3).unwrap();
+        let c = random_connected_map(50, 2.5, 4).unwrap();
+        assert_eq!(a.snapshot(), b.snapshot());
+        assert_ne!(a.snapshot(), c.snapshot());
+    }
+
+    #[test]
+    fn random_connected_map_is_pathfindable_between_random_pairs() {
+        let map = random_connected_map(300, 2.5, 11).unwrap();
+        let mut rng = SplitMix64::new(11);
+        for _ in 0..100 {
+            let from = map.index_from_raw(rng.below(map.system_count()) as _).unwrap();
+            let to = map.index_from_raw(rng.below(map.system_count()) as _).unwrap();
+            let route = map.shortest_route(from, to).unwrap_or_else(|e| panic!("{from:?} to {to:?}: {e:?}"));
+            assert_eq!((route[0], route[route.len() - 1]), (from, to));
+            assert_eq!(Some(route.len() as u32 - 1), map.jumps_between(&from, &to), "{from:?} to {to:?}");
+        }
+    }
+}
@@ -0,0 +1,289 @@
+//! Consistency checks over a built Map.
+//!
+//! MapBuilder refuses maps that are obviously broken, but a truncated or hand edited SDE can still
+//! produce a map that builds and then routes strangely. These checks look for that.
+use crate::evemap::{Map, RawIndex, SolarSystemIndex};
+use std::collections::{HashSet, VecDeque};
+use std::fmt::Display;
+
+/// MIN_LARGEST_COMPONENT_SHARE is how much of the map the largest connected component should hold.
+/// Eve has a few isolated regions, but the bulk of known space is one component, so a map split
+/// into pieces points at missing stargates.
+const MIN_LARGEST_COMPONENT_SHARE: f64 = 0.5;
+
+/// Check is the outcome of a single check
+#[derive(Debug, Clone)]
+pub struct Check {
+    pub name: &'static str,
+    pub passed: bool,
+    /// what was found, eg. the first problem or a count
+    pub detail: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct ValidationReport {
+    pub checks: Vec<Check>,
+}
+
+impl ValidationReport {
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|c| c.passed)
+    }
+
+    /// failed yields the checks that didn't pass
+    pub fn failed(&self) -> impl Iterator<Item = &Check> {
+        self.checks.iter().filter(|c| !c.passed)
+    }
+}
+
+impl Display for ValidationReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for c in &self.checks {
+            writeln!(f, "{} {}: {}", if c.passed { "PASS" } else { "FAIL" }, c.name, c.detail)?;
+        }
+        write!(f, "{}", if self.passed() { "map is consistent" } else { "map is NOT consistent" })
+    }
+}
+
+impl Map {
+    /// validate runs every consistency check over the map and reports on each of them
+    pub fn validate(&self) -> ValidationReport {
+        // every other check indexes both vecs, which is only safe when they line up
+        if self.systems.len() != self.extended_systems.len() || self.predecessors.len() != self.systems.len() {
+            let detail = format!(
+                "{} systems, {} extended systems and {} predecessor lists",
+                self.systems.len(),
+                self.extended_systems.len(),
+                self.predecessors.len()
+            );
+            return ValidationReport {
+                checks: vec![check("system tables line up", Some(detail), String::new)],
+            };
+        }
+
+        ValidationReport {
+            checks: vec![
+                self.check_system_indexes(),
+                self.check_unique_ids(),
+                self.check_neighbours_in_range(),
+                self.check_gates_resolved(),
+                self.check_stargates_bidirectional(),
+                self.check_components(),
+            ],
+        }
+    }
+
+    /// every SolarSystemId lookup leads to the system with that id
+    fn check_system_indexes(&self) -> Check {
+        let mut problem = None;
+        for (id, i) in &self.system_id_to_index {
+            if problem.is_some() {
+                break;
+            }
+            if usize::from(*i) >= self.system_count() {
+                problem = Some(format!("{id} has index {} beyond the {} systems", usize::from(*i), self.system_count()));
+            } else if self.get_system(i).solar_system_id != *id
+                || self.get_extended_solarsystem_info(i).solar_system_id != *id
+            {
+                problem = Some(format!("{id} has index {}, which is a different system", usize::from(*i)));
+            }
+        }
+        if problem.is_none() && self.system_id_to_index.len() != self.system_count() {
+            problem = Some(format!(
+                "{} of the {} systems can be looked up by id",
+                self.system_id_to_index.len(),
+                self.system_count()
+            ));
+        }
+
+        check("system indexes resolve", problem, || format!("{} systems", self.system_count()))
+    }
+
+    fn check_unique_ids(&self) -> Check {
+        let mut seen = HashSet::new();
+        let duplicate = self
+            .extended_systems
+            .iter()
+            .find(|ss| !seen.insert(ss.solar_system_id))
+            .map(|ss| format!("{} is in the map more than once", ss.solar_system_id));
+
+        check("no duplicate system ids", duplicate, || "all unique".to_string())
+    }
+
+    fn check_neighbours_in_range(&self) -> Check {
+        let out_of_range = self.systems.iter().find_map(|ss| {
+            ss.get_neighbours()
+                .find(|n| usize::from(*n) >= self.system_count())
+                .map(|n| {
+                    format!(
+                        "{} has neighbour {} beyond the {} systems",
+                        ss.solar_system_id,
+                        usize::from(n),
+                        self.system_count()
+                    )
+                })
+        });
+
+        check("neighbour indexes in range", out_of_range, || "all in range".to_string())
+    }
+
+    /// every gate leads to a system in the map, and the neighbours are the gates' destinations
+    /// (less any jumps taken out with remove_edge)
+    fn check_gates_resolved(&self) -> Check {
+        let problem = self.extended_systems.iter().enumerate().find_map(|(i, ss)| {
+            let from = SolarSystemIndex(i as RawIndex);
+            if let Some(g) = ss.gates.iter().find(|g| usize::from(g.destination) >= self.system_count()) {
+                return Some(format!(
+                    "{} has a gate to unknown system index {}",
+                    ss.solar_system_id,
+                    usize::from(g.destination)
+                ));
+            }
+
+            let mut expected: Vec<_> = ss
+                .gates
+                .iter()
+                .map(|g| g.destination)
+                .filter(|d| !self.removed_edges.contains(&(from, *d)))
+                .collect();
+            let mut actual: Vec<_> = self.get_neighbours(&from).collect();
+            expected.sort_unstable();
+            actual.sort_unstable();
+            (expected != actual).then(|| format!("{}'s neighbours don't match its gates", ss.solar_system_id))
+        });
+
+        let gates: usize = self.extended_systems.iter().map(|ss| ss.gates.len()).sum();
+        check("stargate destinations resolved", problem, || format!("{gates} gates"))
+    }
+
+    /// stargates come in pairs, so each jump through a stargate has a jump back
+    fn check_stargates_bidirectional(&self) -> Check {
+        let one_way = self.extended_systems.iter().enumerate().find_map(|(i, ss)| {
+            let from = SolarSystemIndex(i as RawIndex);
+            ss.gates
+                .iter()
+                .filter(|g| g.stargate_id.is_some() && usize::from(g.destination) < self.system_count())
+                .find(|g| {
+                    !self
+                        .get_extended_solarsystem_info(&g.destination)
+                        .gates
+                        .iter()
+                        .any(|back| back.destination == from)
+                })
+                .map(|g| {
+                    format!(
+                        "{} has a stargate to {} with no stargate back",
+                        ss.solar_system_id,
+                        self.get_extended_solarsystem_info(&g.destination).solar_system_id
+                    )
+                })
+        });
+
+        check("stargates are bidirectional", one_way, || "all paired".to_string())
+    }
+
+    /// counts the connected components, ignoring the direction of jumps
+    fn check_components(&self) -> Check {
+        let mut component = vec![None; self.system_count()];
+        let mut sizes = Vec::new();
+
+        for start in 0..self.system_count() {
+            if component[start].is_some() {
+                continue;
+            }
+            let mut size = 0;
+            let mut queue = VecDeque::from([SolarSystemIndex(start as RawIndex)]);
+            component[start] = Some(sizes.len());
+            while let Some(current) = queue.pop_front() {
+                size += 1;
+                let adjacent = self
+                    .get_neighbours(&current)
+                    .chain(self.get_predecessors(&current))
+                    .filter(|n| usize::from(*n) < self.system_count());
+                for n in adjacent {
+                    if component[usize::from(n)].is_none() {
+                        component[usize::from(n)] = Some(sizes.len());
+                        queue.push_back(n);
+                    }
+                }
+            }
+            sizes.push(size);
+        }
+
+        let largest = sizes.iter().copied().max().unwrap_or(0);
+        let detail = format!(
+            "{} connected components, the largest has {largest} of {} systems",
+            sizes.len(),
+            self.system_count()
+        );
+        Check {
+            name: "plausible connected components",
+            passed: self.system_count() == 0
+                || largest as f64 >= self.system_count() as f64 * MIN_LARGEST_COMPONENT_SHARE,
+            detail,
+        }
+    }
+}
+
+/// check makes a Check that passed unless there's a problem, with `ok` describing a pass
+fn check(name: &'static str, problem: Option<String>, ok: impl FnOnce() -> String) -> Check {
+    match problem {
+        Some(detail) => Check {
+            name,
+            passed: false,
+            detail,
+        },
+        None => Check {
+            name,
+            passed: true,
+            detail: ok(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::MapBuilder;
+    use crate::fixtures::{line_map, map_from_edges, sde_map, FIRST_ID};
+
+    #[test]
+    fn validate_passes_the_fixture() {
+        let report = sde_map().validate();
+        assert!(report.passed(), "{report}");
+        assert_eq!(report.checks.len(), 6);
+        assert!(report.to_string().ends_with("map is consistent"));
+    }
+
+    #[test]
+    fn validate_fails_a_one_way_stargate() {
+        let mut builder = MapBuilder::new();
+        builder.add_region(1, "Region").add_constellation(2, "Constellation");
+        builder.add_system(FIRST_ID, "A", 2, 1, 1.0).add_system(FIRST_ID + 1, "B", 2, 1, 1.0);
+        builder.add_jump_through(FIRST_ID, FIRST_ID + 1, Some(50_000_000), None);
+        let report = builder.build().unwrap().validate();
+
+        assert!(!report.passed());
+        let failed: Vec<_> = report.failed().map(|c| c.name).collect();
+        assert_eq!(failed, ["stargates are bidirectional"]);
+        assert!(report.to_string().ends_with("map is NOT consistent"));
+    }
+
+    #[test]
+    fn validate_fails_a_map_in_pieces() {
+        let systems = [("A", 1.0), ("B", 1.0), ("C", 1.0), ("D", 1.0), ("E", 1.0)];
+        let report = map_from_edges(&systems, &[(0, 1), (2, 3)]).validate();
+        let failed: Vec<_> = report.failed().map(|c| c.name).collect();
+        assert_eq!(failed, ["plausible connected components"]);
+
+        assert!(line_map(5).validate().passed());
+    }
+
+    #[test]
+    fn validate_allows_removed_edges() {
+        let mut map = line_map(3);
+        map.remove_edge(SolarSystemIndex(0), SolarSystemIndex(1));
+        let report = map.validate();
+        assert!(report.checks.iter().find(|c| c.name == "stargate destinations resolved").unwrap().passed, "{report}");
+    }
+}
@@ -0,0 +1,265 @@
+//! Runs the rust-eve-astar binary on the fixture SDE in examples/fixtures, checking what it prints.
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+const FIXTURE_SDE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/examples/fixtures/sde.zip");
+
+/// run runs the binary on the fixture SDE with `args`
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_rust-eve-astar"))
+        .args(["-s", FIXTURE_SDE])
+        .args(args)
+        .output()
+        .expect("the binary runs")
+}
+
+/// stdout is the output of a run that's expected to succeed
+fn stdout(args: &[&str]) -> String {
+    let output = run(args);
+    assert!(output.status.success(), "{args:?} failed: {}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// temp_file writes `contents` to a file under the target directory for the binary to read
+fn temp_file(name: &str, contents: &str) -> PathBuf {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+#[test]
+fn from_and_to_pick_the_route() {
+    let out = stdout(&["--from", "Jita", "--to", "Amarr"]);
+    let lines: Vec<&str> = out.lines().collect();
+    assert_eq!(lines[..3], ["1 Jita - 30000008", "2 Yulai - 30000009", "3 Amarr - 30000001"]);
+}
+
+#[test]
+fn queries_in_jsonl_are_a_record_per_line() {
+    let queries = temp_file("queries.txt", "Jita Amarr\n\nSarum Prime Tama\nJita Isolated\n");
+    let out = stdout(&["--queries", queries.to_str().unwrap(), "--format", "jsonl"]);
+    let records: Vec<serde_json::Value> = out.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+
+    assert_eq!(records.len(), 3);
+    assert_eq!(records[0]["from"], "Jita");
+    assert_eq!(records[0]["jumps"], 2);
+    assert_eq!(records[1]["from"], "Sarum Prime");
+    assert_eq!(records[1]["to"], "Tama");
+    assert_eq!(records[1]["jumps"], 3);
+    assert_eq!(records[2]["error"], "no path found");
+    assert!(records[2].get("jumps").is_none());
+}
+
+#[test]
+fn benchmark_route_compares_every_mode() {
+    let out = stdout(&["--from", "Tama", "--to", "Amarr", "--benchmark-route"]);
+    let lines: Vec<&str> = out.lines().collect();
+    assert_eq!(lines.len(), 6);
+    assert_eq!(lines[0], "Tama -> Amarr");
+    for (line, mode) in lines[2..].iter().zip(["bfs", "astar-jumps", "astar-landmark", "prefer-safe"]) {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        assert_eq!(fields[0], mode);
+        assert_eq!(fields[1], "4", "{line}");
+    }
+}
+
+#[test]
+fn format_dotlan_prints_a_link_to_the_route() {
+    let out = stdout(&["--from", "Jita", "--to", "Amarr", "--format", "dotlan"]);
+    assert_eq!(out, "https://evemaps.dotlan.net/route/Jita:Yulai:Amarr\n");
+}
+
+#[test]
+fn validate_reports_each_check() {
+    let out = stdout(&["--validate"]);
+    let lines: Vec<&str> = out.lines().collect();
+    assert_eq!(lines.len(), 7);
+    assert!(lines[..6].iter().all(|l| l.starts_with("PASS ")), "{out}");
+    assert_eq!(lines[6], "map is consistent");
+}
+
+#[test]
+fn patch_is_applied_before_routing() {
+    let patch = temp_file(
+        "patch.json",
+        r#"{"removed_jumps": [[30000008, 30000009], [30000009, 30000008]]}"#,
+    );
+    let out = stdout(&["--from", "Jita", "--to", "Amarr", "--patch", patch.to_str().unwrap()]);
+    assert!(out.lines().any(|l| l == "6 Amarr - 30000001"), "{out}");
+    assert!(!out.contains("Yulai"));
+
+    let output = run(&["--from", "Jita", "--to", "Amarr", "--patch", "missing.json"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("unable to read patch missing.json"));
+}
+
+#[test]
+fn prefer_connected_takes_the_route_through_busier_systems() {
+    // Amarr to Tama is 4 jumps three ways: through Yulai, Jita and Perimeter has the most stargates
+    let out = stdout(&["--from", "Amarr", "--to", "Tama", "--prefer-connected"]);
+    let names: Vec<&str> = out.lines().take(5).map(|l| l.split(' ').nth(1).unwrap()).collect();
+    assert_eq!(names, ["Amarr", "Yulai", "Jita", "Perimeter", "Tama"]);
+}
+
+#[test]
+fn serve_answers_queries_from_stdin_until_it_closes() {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rust-eve-astar"))
+        .args(["-s", FIXTURE_SDE, "--serve"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("the binary runs");
+    child.stdin.take().unwrap().write_all(b"Jita Amarr\nAmarr Jita\n").unwrap();
+    let output = child.wait_with_output().unwrap();
+
+    assert!(output.status.success());
+    let records: Vec<serde_json::Value> =
+        String::from_utf8(output.stdout).unwrap().lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[1]["from"], "Amarr");
+    assert_eq!(records[1]["jumps"], 2);
+}
+
+#[test]
+fn safe_penalty_is_limited_so_costs_cannot_overflow() {
+    let output = run(&["--from", "Tama", "--to", "Amarr", "--prefer-safe", "--safe-penalty", "4294967295"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("is not in 0..=1000000"));
+
+    let out = stdout(&["--from", "Tama", "--to", "Amarr", "--prefer-safe", "--safe-penalty", "1000000"]);
+    assert!(out.contains("5 Amarr - 30000001"), "{out}");
+}
+
+#[test]
+fn safe_penalty_needs_prefer_safe() {
+    let output = run(&["--from", "Tama", "--to", "Amarr", "--safe-penalty", "5"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--prefer-safe"));
+
+    let out = stdout(&["--from", "Tama", "--to", "Amarr", "--prefer-safe", "--safe-penalty", "5"]);
+    assert!(out.contains("5 Amarr - 30000001"), "{out}");
+}
+
+#[test]
+fn align_time_rejects_nan_and_negative_seconds() {
+    for bad in ["--align-time=NaN", "--align-time=-3"] {
+        let output = run(&["--from", "Jita", "--to", "Amarr", "--optimize-time", bad]);
+        assert!(!output.status.success(), "{bad}");
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("invalid value") && !stderr.contains("panicked"), "{bad}: {stderr}");
+    }
+
+    let out = stdout(&["--from", "Jita", "--to", "Amarr", "--optimize-time", "--align-time", "0"]);
+    assert!(out.contains("Amarr - 30000001"), "{out}");
+}
+
+#[test]
+fn safe_penalty_auto_is_derived_from_the_map() {
+    // the fixture's longest shortest route is 4 jumps, so auto is a penalty of 5
+    let auto = stdout(&["--from", "Tama", "--to", "Amarr", "--prefer-safe", "--safe-penalty", "auto"]);
+    let five = stdout(&["--from", "Tama", "--to", "Amarr", "--prefer-safe", "--safe-penalty", "5"]);
+    assert_eq!(auto, five);
+    assert!(!run(&["--from", "Tama", "--to", "Amarr", "--prefer-safe", "--safe-penalty", "most"]).status.success());
+}
+
+#[test]
+fn json_pretty_records_can_be_read_as_a_stream() {
+    let queries = temp_file("pretty_queries.txt", "Jita Amarr\nJita Isolated\n");
+    let out = stdout(&["--queries", queries.to_str().unwrap(), "--format", "jsonl", "--json-pretty"]);
+    assert!(out.lines().count() > 2);
+    let records: Vec<serde_json::Value> =
+        serde_json::Deserializer::from_str(&out).into_iter().collect::<Result<_, _>>().unwrap();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0]["jumps"], 2);
+    assert_eq!(records[1]["error"], "no path found");
+}
+
+#[test]
+fn verbose_reports_systems_with_too_many_jumps_to_hold_in_place() {
+    let output = run(&["--from", "Jita", "--to", "Amarr", "--verbose"]);
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("0 of 11 systems have too many jumps to hold them in place"), "{stderr}");
+}
+
+#[test]
+fn info_describes_a_system_without_routing() {
+    let out = stdout(&["--info", "Ashab"]);
+    assert_eq!(
+        out.lines().collect::<Vec<_>>(),
+        [
+            "Ashab - 30000005",
+            "region: Domain (10000001)",
+            "constellation: Kador (20000002)",
+            "security: 0.5 (Low, true security 0.450)",
+        ]
+    );
+    assert!(!run(&["--info", "Nowhere"]).status.success());
+}
+
+#[test]
+fn avoid_gate_takes_the_stargates_out_of_the_map() {
+    let out = stdout(&["--from", "Jita", "--to", "Amarr", "--avoid-gate", "Yulai-Jita"]);
+    assert!(out.lines().next().unwrap().starts_with("1 Jita"));
+    assert!(!out.contains("Yulai"), "{out}");
+
+    let output = run(&["--from", "Jita", "--to", "Amarr", "--avoid-gate", "Jita-Amarr"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("no stargate to avoid"));
+}
+
+#[test]
+fn home_adds_the_jumps_from_home_to_the_summary() {
+    let out = stdout(&["--from", "Tama", "--to", "Amarr", "--home", "Jita"]);
+    assert!(out.trim_end().ends_with(", 2 and 2 jumps from home"), "{out}");
+    let out = stdout(&["--from", "Tama", "--to", "Amarr", "--home", "Jita", "--format", "jsonl"]);
+    assert!(out.contains("\"start_from_home\":2,\"end_from_home\":2"), "{out}");
+    assert!(!run(&["--from", "Tama", "--to", "Amarr", "--home", "Nowhere"]).status.success());
+}
+
+#[test]
+fn verbose_reports_how_the_stargates_were_wired() {
+    let output = run(&["--from", "Jita", "--to", "Amarr", "--verbose"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("wiring: 24 stargates seen, 24 destinations resolved, 0 unresolved, 24 jumps wired"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn count_only_prints_just_the_jumps() {
+    assert_eq!(stdout(&["--from", "Jita", "--to", "Amarr", "--count-only"]), "2\n");
+    let queries = temp_file("count_queries.txt", "Jita Amarr\nAmarr Tama\n");
+    assert_eq!(stdout(&["--queries", queries.to_str().unwrap(), "--count-only"]), "2\n4\n");
+
+    let output = run(&["--from", "Jita", "--to", "Isolated", "--count-only"]);
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+    assert!(!run(&["--from", "Jita", "--to", "Amarr", "--count-only", "--prefer-safe"]).status.success());
+
+    // --max-jumps limits the routes counted, as it does the routes printed
+    assert_eq!(stdout(&["--from", "Jita", "--to", "Amarr", "--count-only", "--max-jumps", "2"]), "2\n");
+    let output = run(&["--from", "Jita", "--to", "Amarr", "--count-only", "--max-jumps", "1"]);
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn weights_route_around_heavy_jumps() {
+    let weights = temp_file("weights.csv", "Jita,Yulai,10\n");
+    let out = stdout(&["--from", "Jita", "--to", "Amarr", "--weights", weights.to_str().unwrap()]);
+    assert!(!out.contains("Yulai"), "{out}");
+    // the weight is only on the jump from Jita
+    let out = stdout(&["--from", "Amarr", "--to", "Jita", "--weights", weights.to_str().unwrap()]);
+    assert!(out.contains("Yulai"), "{out}");
+
+    let bad = temp_file("bad_weights.csv", "Jita,Amarr,1\n");
+    let output = run(&["--from", "Jita", "--to", "Amarr", "--weights", bad.to_str().unwrap()]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("bad weights in"));
+}
@@ -0,0 +1,36 @@
+//! Runs the examples, so that they keep working as the library changes, not just keep compiling.
+use std::process::Command;
+
+/// run_example runs `cargo run --example name` with `args`, returning what it printed
+fn run_example(name: &str, args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO"))
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .args(["run", "--quiet", "--example", name, "--"])
+        .args(args)
+        .output()
+        .expect("cargo runs");
+    assert!(output.status.success(), "{name} failed: {}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn route_example_routes_on_the_fixture_sde() {
+    let out = run_example("route", &[]);
+    assert_eq!(
+        out.lines().collect::<Vec<_>>(),
+        [
+            "Amarr (1.0)",
+            "Yulai (1.0)",
+            "Jita (0.9)",
+            "2 jumps: 3 high-sec, 0 low-sec, 0 null-sec, lowest security 0.90 (all high-sec)",
+        ]
+    );
+}
+
+#[test]
+fn route_example_takes_the_sde_and_systems_as_arguments() {
+    let sde = concat!(env!("CARGO_MANIFEST_DIR"), "/examples/fixtures/sde.zip");
+    let out = run_example("route", &[sde, "Tama", "EC-P8R"]);
+    assert_eq!(out.lines().next(), Some("Tama (0.3)"));
+    assert_eq!(out.lines().last(), Some("1 jumps: 0 high-sec, 1 low-sec, 1 null-sec, lowest security -0.10"));
+}
@@ -0,0 +1,25 @@
+//! Checks the counting allocator of the profiling feature, installed here as it is in the binary.
+#![cfg(feature = "profiling")]
+use rust_eve_astar::profiling::{reset_peak, AllocStats, CountingAllocator};
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+// one test, since the counters are global and tests run in parallel
+#[test]
+fn counting_allocator_counts_allocations_and_the_peak() {
+    const SIZE: usize = 1 << 20;
+
+    let before = AllocStats::read();
+    let buffer = vec![1u8; SIZE];
+    let during = AllocStats::read();
+    assert!(during.allocations_since(&before) >= 1);
+    assert!(during.current_bytes >= SIZE);
+    assert!(during.peak_bytes >= during.current_bytes);
+
+    drop(buffer);
+    reset_peak();
+    let after = AllocStats::read();
+    assert!(after.current_bytes < during.current_bytes);
+    assert!(after.peak_bytes < during.peak_bytes);
+}
@@ -0,0 +1,68 @@
+//! Checks the jumps of well-known routes against tables of expected lengths, through every way the
+//! library has of finding the fewest jumps. They must all agree, so a search that gets weighted
+//! edges wrong (eg. by not relaxing a node reached more cheaply later) fails here rather than quietly
+//! returning a longer route.
+//!
+//! The fixture SDE's table always runs. The full SDE's needs the "sde-routes" feature and the SDE zip,
+//! which isn't in the repository, at $EVE_SDE_ZIP (or sde.zip in the crate root):
+//!
+//!     EVE_SDE_ZIP=path/to/sde.zip cargo test --features sde-routes --test route_lengths
+use rust_eve_astar::cost::JumpsCostModel;
+use rust_eve_astar::evemap::Map;
+
+/// FIXTURE_ROUTES are routes on the fixture SDE in examples/fixtures, with their jumps (None when
+/// there's no route at all)
+const FIXTURE_ROUTES: &[(&str, &str, Option<u32>)] = &[
+    ("Jita", "Jita", Some(0)),
+    ("Jita", "Amarr", Some(2)),
+    ("Amarr", "Jita", Some(2)),
+    ("Jita", "Ashab", Some(3)),
+    ("Amarr", "Tama", Some(4)),
+    ("Sarum Prime", "EC-P8R", Some(4)),
+    ("Jita", "Isolated", None),
+];
+
+/// SDE_ROUTES are the shortest routes between trade hubs, taking any security, as the in-game
+/// autopilot's "Shorter" setting gives them. They change when CCP rewires the map (eg. Niarja
+/// leaving high-sec for Pochven), so update them along with the SDE they're checked against.
+#[cfg(feature = "sde-routes")]
+const SDE_ROUTES: &[(&str, &str, Option<u32>)] = &[
+    ("Jita", "Perimeter", Some(1)),
+    ("Jita", "Amarr", Some(11)),
+    ("Jita", "Dodixie", Some(15)),
+];
+
+/// check_routes checks each route of `table` on `map` with BFS, A* and bidirectional Dijkstra
+fn check_routes(map: &Map, table: &[(&str, &str, Option<u32>)]) {
+    for (from_name, to_name, expected) in table {
+        let from = map.find_solarsystem(from_name).unwrap();
+        let to = map.find_solarsystem(to_name).unwrap();
+        let route = format!("{from_name} to {to_name}");
+
+        let bfs = map.jumps_between(&from, &to);
+        let astar = map.shortest_route(from, to).ok().map(|r| r.len() as u32 - 1);
+        let weighted = map.route_with_cost(from, to, |_, _| 1u32, |_| 0, None).ok().map(|r| r.1);
+        let model = map.route_with_model(from, to, &JumpsCostModel, None).ok().map(|r| r.1);
+        let bidirectional = map.route_bidirectional(from, to, |_, _| 1u32).ok().map(|r| r.1);
+
+        assert_eq!(bfs, *expected, "{route} by jumps_between");
+        assert_eq!(astar, *expected, "{route} by shortest_route");
+        assert_eq!(weighted, *expected, "{route} by route_with_cost");
+        assert_eq!(model, *expected, "{route} by route_with_model");
+        assert_eq!(bidirectional, *expected, "{route} by route_bidirectional");
+    }
+}
+
+#[test]
+fn fixture_routes_have_the_expected_jumps() {
+    let map = Map::from_zip_bytes(include_bytes!("../examples/fixtures/sde.zip")).unwrap();
+    check_routes(&map, FIXTURE_ROUTES);
+}
+
+#[cfg(feature = "sde-routes")]
+#[test]
+fn sde_routes_have_the_in_game_jumps() {
+    let path = std::env::var("EVE_SDE_ZIP").unwrap_or_else(|_| concat!(env!("CARGO_MANIFEST_DIR"), "/sde.zip").into());
+    let bytes = std::fs::read(&path).unwrap_or_else(|e| panic!("the sde-routes tests need the SDE at {path}: {e}"));
+    check_routes(&Map::from_zip_bytes(&bytes).unwrap(), SDE_ROUTES);
+}